@@ -1,15 +1,80 @@
-pub static MAX_CREEPS: usize = 10;
-pub static MAX_CREEPS_BODY_PARTS: usize = 12;
+use screeps::ResourceType;
+
+pub static COMPOUNDS: [ResourceType; 34] = [
+    ResourceType::Hydroxide,
+    ResourceType::ZynthiumKeanite,
+    ResourceType::UtriumLemergite,
+    ResourceType::Ghodium,
+    ResourceType::UtriumHydride,
+    ResourceType::UtriumAcid,
+    ResourceType::CatalyzedUtriumAcid,
+    ResourceType::UtriumOxide,
+    ResourceType::UtriumAlkalide,
+    ResourceType::CatalyzedUtriumAlkalide,
+    ResourceType::KeaniumHydride,
+    ResourceType::KeaniumAcid,
+    ResourceType::CatalyzedKeaniumAcid,
+    ResourceType::KeaniumOxide,
+    ResourceType::KeaniumAlkalide,
+    ResourceType::CatalyzedKeaniumAlkalide,
+    ResourceType::LemergiumHydride,
+    ResourceType::LemergiumAcid,
+    ResourceType::CatalyzedLemergiumAcid,
+    ResourceType::LemergiumOxide,
+    ResourceType::LemergiumAlkalide,
+    ResourceType::CatalyzedLemergiumAlkalide,
+    ResourceType::ZynthiumHydride,
+    ResourceType::ZynthiumAcid,
+    ResourceType::CatalyzedZynthiumAcid,
+    ResourceType::ZynthiumOxide,
+    ResourceType::ZynthiumAlkalide,
+    ResourceType::CatalyzedZynthiumAlkalide,
+    ResourceType::GhodiumHydride,
+    ResourceType::GhodiumAcid,
+    ResourceType::CatalyzedGhodiumAcid,
+    ResourceType::GhodiumOxide,
+    ResourceType::GhodiumAlkalide,
+    ResourceType::CatalyzedGhodiumAlkalide,
+];
+pub static MINERALS: [ResourceType; 7] = [
+    ResourceType::Hydrogen,
+    ResourceType::Oxygen,
+    ResourceType::Utrium,
+    ResourceType::Lemergium,
+    ResourceType::Keanium,
+    ResourceType::Zynthium,
+    ResourceType::Catalyst,
+];
+pub static BOOST_WAIT: u32 = 100;
+pub static DEFENSE_HITS: [u32; 9] = [
+    0, 0, 10_000, 30_000, 100_000, 300_000, 1_000_000, 3_000_000, 10_000_000,
+];
+pub static EXTRACTOR_RCL: u32 = 6;
+pub static LINK_BATCH: u32 = 400;
+pub static LINK_RANGE: u32 = 2;
+pub static MARKET_REPRICE: f64 = 0.05;
+pub static MAX_CONSTRUCTION_SITES: u32 = 3;
 pub static MAX_REPAIR_MULTIPLIER: u32 = 4;
+pub static PATH_PLAIN_COST: u8 = 2;
+pub static PATH_SWAMP_COST: u8 = 10;
 pub static PRIORITY_ATTACK: u32 = 0;
 pub static PRIORITY_BUILDING: u32 = 4;
+pub static PRIORITY_DELIVERING: u32 = 3;
+pub static PRIORITY_EXTRACTING: u32 = 1;
 pub static PRIORITY_HARVESTING: u32 = 6;
 pub static PRIORITY_HEALING: u32 = 1;
+pub static PRIORITY_LOOTING: u32 = 5;
 pub static PRIORITY_MAINTAINING: u32 = 3;
+pub static PRIORITY_MINING: u32 = 1;
+pub static PRIORITY_PICKING_UP: u32 = 5;
+pub static PRIORITY_REFUELING: u32 = 5;
 pub static PRIORITY_REPAIRING: u32 = 2;
+pub static PRIORITY_SALVAGING: u32 = 5;
 pub static PRIORITY_UPGRADING: u32 = 10;
+pub static PRIORITY_WITHDRAWING: u32 = 5;
 pub static RANGE_ATACK: u32 = 1;
 pub static RANGE_ATTACK_CONTROLLER: u32 = 1;
+pub static RANGE_BOOST: u32 = 1;
 pub static RANGE_BUILD: u32 = 3;
 pub static RANGE_CLAIM_CONTROLLER: u32 = 3;
 pub static RANGE_DISMANTLE: u32 = 1;
@@ -21,10 +86,28 @@ pub static RANGE_PULL: u32 = 1;
 pub static RANGE_RANGED_ATTACK: u32 = 3;
 pub static RANGE_RANGED_HEAL: u32 = 3;
 pub static RANGE_RANGED_MASS_ATTACK: u32 = 3;
+pub static RANGE_REACTION: u32 = 2;
 pub static RANGE_REPAIR: u32 = 3;
 pub static RANGE_RESERVE_CONTROLLER: u32 = 1;
 pub static RANGE_TRANSFER: u32 = 1;
 pub static RANGE_UPGRADE_CONTROLLER: u32 = 3;
 pub static RANGE_WITHDRAW: u32 = 1;
+pub static REACTION_BATCH: u32 = 1000;
+pub static REACTION_TARGET: u32 = 3000;
+pub static RENEW_BELOW: u32 = 300;
+pub static RENEW_MIN_COST: u32 = 1000;
+pub static RENEW_RANGE: u32 = 10;
+pub static ROAD_RCL: u32 = 3;
 pub static ROOM_X: u32 = 50;
 pub static ROOM_Y: u32 = 50;
+pub static SALVAGE_MIN_AMOUNT: u32 = 50;
+pub static SALVAGE_PER_CREEP: u32 = 100;
+pub static SALVAGE_TRAVEL_TICKS: u32 = 25;
+pub static SOURCE_CONTAINER_RCL: u32 = 2;
+pub static STOCK_ENERGY: u32 = 100_000;
+pub static STOCK_RESOURCE: u32 = 5000;
+pub static TERMINAL_BATCH: u32 = 10_000;
+pub static TERMINAL_ENERGY: u32 = 10_000;
+pub static TERMINAL_MIN_SEND: u32 = 1000;
+pub static TRAFFIC_ROAD_STEPS: u32 = 50;
+pub static TRAFFIC_WINDOW: u32 = 1500;
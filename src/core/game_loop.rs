@@ -1,51 +1,104 @@
-use super::cleanup_memory;
-use crate::creeps::{replenish_creeps, Regulator};
+use super::{cleanup_memory, load_memory, save_memory, Memory};
+use crate::creeps::{balance_terminals, plan_reactions, trade, Regulator};
+use crate::planner::{place_sites, plan_rooms};
+use crate::world::{GameWorld, World};
 use log::*;
 use screeps::RoomName;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::sync::Mutex;
 
 lazy_static::lazy_static! {
-    static ref REGULATORS: Mutex<HashMap<RoomName, Regulator>> = {
-        let mut m = HashMap::new();
-
-        for room in screeps::game::rooms::values() {
-            let name = room.name();
-            let mut regulator = Regulator::new(room);
-            regulator.scan();
-            m.insert(name, regulator);
-        }
+    static ref REGULATORS: Mutex<BTreeMap<RoomName, Regulator>> = Mutex::new(BTreeMap::new());
+}
+
+/// Creates regulators for newly visible rooms and drops the ones for rooms we lost sight
+/// of, handing their creeps to the regulator of the room they're currently in.
+fn sync_regulators(
+    world: &dyn World,
+    memory: &mut Memory,
+    regulators: &mut BTreeMap<RoomName, Regulator>,
+) {
+    let rooms = world.rooms();
+
+    for &room in &rooms {
+        regulators
+            .entry(room)
+            .or_insert_with(|| Regulator::new(world, memory, room));
+    }
+
+    let lost: Vec<_> = regulators
+        .keys()
+        .filter(|r| !rooms.contains(r))
+        .copied()
+        .collect();
 
-        Mutex::new(m)
-    };
+    for room in lost {
+        info!("lost sight of {}, dropping its regulator", room);
+        let mut old = regulators.remove(&room).unwrap();
+
+        for name in old.creep_names() {
+            let creep = old.release_creep(&name).unwrap();
+            match regulators.get_mut(&creep.get_pos().room_name()) {
+                Some(r) => r.adopt_creep(memory, creep),
+                None => warn!("no regulator to hand creep {} over to", name),
+            }
+        }
+    }
 }
 
 pub fn game_loop() {
-    trace!("loop starting! CPU: {}", screeps::game::cpu::get_used());
+    tick(&GameWorld, &mut REGULATORS.lock().unwrap());
+}
+
+pub fn tick(world: &dyn World, regulators: &mut BTreeMap<RoomName, Regulator>) {
+    trace!("loop starting! CPU: {}", world.cpu_used());
 
-    let spawned = replenish_creeps();
-    if let Err(e) = spawned {
-        warn!("couldn't spawn: {:?}", e);
+    let mut memory = load_memory(world);
+    let time = world.time();
+    let interval = memory.settings.scan_interval.max(1);
+    let scanning = time % interval == 3 % interval;
+
+    // Before spawning, the creeps spawned this tick aren't in the spawns yet
+    if scanning {
+        trace!("running memory cleanup");
+        cleanup_memory(world, &mut memory);
+    }
+
+    // Fresh after a global reset
+    if regulators.is_empty() {
+        sync_regulators(world, &mut memory, regulators);
+        for r in regulators.values_mut() {
+            r.scan(world);
+        }
     }
 
-    for (name, regulator) in REGULATORS.lock().unwrap().iter_mut() {
-        regulator.set_room(screeps::game::rooms::get(*name).unwrap());
-        if let Err(e) = regulator.distribute_jobs(spawned.unwrap_or(true)) {
+    for regulator in regulators.values_mut() {
+        if let Err(e) = regulator.spawn_creeps(world, &mut memory) {
+            warn!("{}", e);
+        }
+
+        if let Err(e) = regulator.distribute_jobs(world, &mut memory) {
             warn!("{}", e);
         }
     }
 
-    let time = screeps::game::time();
+    if scanning {
+        sync_regulators(world, &mut memory, regulators);
+        plan_rooms(world, &mut memory);
+        place_sites(world, &memory);
+        plan_reactions(world, &mut memory);
+        // Terminals trade instead of sharing this tick, both use up their cooldown
+        trade(world, &memory);
 
-    if time % 32 == 3 {
         debug!("running scan for tasks...");
-        for r in REGULATORS.lock().unwrap().values_mut() {
-            r.scan();
+        for r in regulators.values_mut() {
+            r.scan(world);
         }
-
-        trace!("running memory cleanup");
-        cleanup_memory().expect("expected Memory.creeps format to be a regular memory object");
+    } else {
+        balance_terminals(world);
     }
 
-    trace!("done! cpu: {}", screeps::game::cpu::get_used())
+    save_memory(world, &memory);
+
+    trace!("done! cpu: {}", world.cpu_used())
 }
@@ -1,23 +1,365 @@
+use crate::creeps::{Lifecycle, Reaction, Role};
+use crate::planner::{Layout, Traffic};
+use crate::world::World;
 use log::*;
-use std::collections::HashSet;
+use screeps::{RawObjectId, ResourceType, RoomName};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashSet};
 
-pub fn cleanup_memory() -> Result<(), Box<dyn std::error::Error>> {
-    let alive_creeps: HashSet<String> = screeps::game::creeps::keys().into_iter().collect();
+/// Each migration upgrades the raw memory from the version of its index to the next one.
+/// Memory written before versioning counts as version 0.
+const MIGRATIONS: &[fn(&mut Map<String, Value>)] = &[migrate_v0_to_v1];
 
-    let screeps_memory = match screeps::memory::root().dict("creeps")? {
-        Some(v) => v,
-        None => {
-            warn!("not cleaning game creep memory: no Memory.creeps dict");
-            return Ok(());
+pub const VERSION: u32 = MIGRATIONS.len() as u32;
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Memory {
+    pub version: u32,
+    pub settings: Settings,
+    pub rooms: BTreeMap<RoomName, RoomMemory>,
+    pub creeps: BTreeMap<String, CreepMemory>,
+    /// Keys the bot doesn't own, like `flags`, `spawns` or whatever is set from the
+    /// console, written back as they were
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// The keys `Memory` reads, everything else ends up in `extra`
+const KEYS: &[&str] = &["creeps", "rooms", "settings", "version"];
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self {
+            version: VERSION,
+            settings: Settings::default(),
+            rooms: BTreeMap::new(),
+            creeps: BTreeMap::new(),
+            extra: Map::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    pub market: MarketSettings,
+    /// Every how many ticks the regulators look for new jobs.
+    pub scan_interval: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            market: MarketSettings::default(),
+            scan_interval: 32,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct MarketSettings {
+    /// Credits spent per scan at most, order fees included
+    pub budget: f64,
+    /// Credits that never get spent
+    pub credit_reserve: f64,
+    /// The most we pay, as a share of the average price over the order history
+    pub price_ceiling: f64,
+    /// The least we sell for, as a share of the average price over the order history
+    pub price_floor: f64,
+}
+
+impl Default for MarketSettings {
+    fn default() -> Self {
+        Self {
+            budget: 5000.0,
+            credit_reserve: 10_000.0,
+            price_ceiling: 1.1,
+            price_floor: 0.9,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct RoomMemory {
+    /// Where the base goes once it's fully built
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layout: Option<Layout>,
+    /// What the labs are making
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reaction: Option<Reaction>,
+    pub towers: BTreeMap<String, JobMemory>,
+    /// Where creeps walk, for placing roads
+    pub traffic: Traffic,
+    /// Keys the bot doesn't own, written back as they were
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct CreepMemory {
+    /// Compounds to get boosted with before starting to work
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub boosts: Vec<ResourceType>,
+    pub home: Option<RoomName>,
+    pub job: Option<JobMemory>,
+    pub lifecycle: Option<Lifecycle>,
+    /// Where a hauler drops off what its current job picks up
+    pub next_job: Option<JobMemory>,
+    pub role: Option<Role>,
+    /// Keys the bot doesn't own, like the `_move` cache of `moveTo`
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// A job as it's remembered between global resets: the kind of job and the id of the
+/// object it's about.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct JobMemory {
+    #[serde(with = "id_string")]
+    pub id: RawObjectId,
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// For jobs moving a specific resource
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resource: Option<ResourceType>,
+}
+
+mod id_string {
+    use screeps::RawObjectId;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(id: &RawObjectId, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&id.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<RawObjectId, D::Error> {
+        String::deserialize(d)?.parse().map_err(D::Error::custom)
+    }
+}
+
+/// Version 1 moves tower jobs from `Memory.towers` into the rooms. The towers' rooms
+/// can't be told from their ids alone, so those jobs are dropped and picked again.
+fn migrate_v0_to_v1(memory: &mut Map<String, Value>) {
+    memory.remove("towers");
+    memory.entry("rooms").or_insert_with(|| json!({}));
+}
+
+/// Parses the raw memory and upgrades it to the current layout. Parts of it that can't
+/// be understood are logged and replaced with the defaults.
+pub fn parse_memory(raw: &str) -> Memory {
+    let mut memory = match serde_json::from_str::<Value>(raw) {
+        Ok(Value::Object(m)) => m,
+        Ok(_) | Err(_) if raw.trim().is_empty() => Map::new(),
+        Ok(v) => {
+            error!("Memory isn't an object, resetting it: {}", v);
+            Map::new()
+        }
+        Err(e) => {
+            error!("Couldn't parse memory, resetting it: {}", e);
+            Map::new()
         }
     };
 
-    for mem_name in screeps_memory.keys() {
-        if !alive_creeps.contains(&mem_name) {
-            trace!("cleaning up creep memory of dead creep {}", mem_name);
-            screeps_memory.del(&mem_name);
+    let version = memory.get("version").and_then(Value::as_u64).unwrap_or(0) as u32;
+    if version > VERSION {
+        error!(
+            "Memory version {} is newer than {}, resetting it",
+            version, VERSION
+        );
+        memory.retain(|k, _| !KEYS.contains(&k.as_str()));
+    } else {
+        for (v, migrate) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+            info!("Migrating memory from version {} to {}", v, v + 1);
+            migrate(&mut memory);
+        }
+    }
+    memory.insert("version".to_string(), VERSION.into());
+
+    match serde_json::from_value(Value::Object(memory.clone())) {
+        Ok(m) => m,
+        Err(e) => {
+            warn!("Memory doesn't match the schema: {}", e);
+            salvage_memory(memory)
+        }
+    }
+}
+
+/// Resets only the keys of the bot that can't be read, keeping the rest
+fn salvage_memory(mut raw: Map<String, Value>) -> Memory {
+    fn take<T: DeserializeOwned + Default>(raw: &mut Map<String, Value>, key: &str) -> T {
+        let value = match raw.remove(key) {
+            Some(v) => v,
+            None => return T::default(),
+        };
+        serde_json::from_value(value).unwrap_or_else(|e| {
+            warn!("Resetting memory.{}: {}", key, e);
+            T::default()
+        })
+    }
+
+    raw.remove("version");
+    Memory {
+        version: VERSION,
+        settings: take(&mut raw, "settings"),
+        rooms: take(&mut raw, "rooms"),
+        creeps: take(&mut raw, "creeps"),
+        extra: raw,
+    }
+}
+
+pub fn load_memory(world: &dyn World) -> Memory {
+    parse_memory(&world.raw_memory())
+}
+
+pub fn save_memory(world: &dyn World, memory: &Memory) {
+    match serde_json::to_string(memory) {
+        Ok(raw) => world.set_raw_memory(&raw),
+        Err(e) => error!("Couldn't serialize memory: {}", e),
+    }
+}
+
+pub fn cleanup_memory(world: &dyn World, memory: &mut Memory) {
+    let alive_creeps: HashSet<String> = world
+        .creeps()
+        .into_iter()
+        .map(|c| c.name)
+        .chain(world.spawns().into_iter().filter_map(|s| s.spawning))
+        .collect();
+
+    memory.creeps.retain(|name, _| {
+        let alive = alive_creeps.contains(name);
+        if !alive {
+            trace!("cleaning up creep memory of dead creep {}", name);
         }
+        alive
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_memory_gets_defaults() {
+        assert_eq!(parse_memory(""), Memory::default());
+        assert_eq!(parse_memory("{}"), Memory::default());
     }
 
-    Ok(())
+    #[test]
+    fn unversioned_memory_is_migrated() {
+        let raw = r#"{
+            "creeps": {
+                "1-0": {"home": "W1N1", "job": {"type": "harvest", "id": "5bbcaa9e9099fc012e6311d9"}},
+                "2-0": {}
+            },
+            "towers": {"5bbcaa9e9099fc012e6311da": {"job": {"type": "repair", "id": "5bbcaa9e9099fc012e6311db"}}}
+        }"#;
+
+        let memory = parse_memory(raw);
+
+        assert_eq!(memory.version, VERSION);
+        assert!(memory.rooms.is_empty());
+        assert_eq!(
+            memory.creeps["1-0"],
+            CreepMemory {
+                boosts: Vec::new(),
+                home: Some(RoomName::new("W1N1").unwrap()),
+                job: Some(JobMemory {
+                    id: "5bbcaa9e9099fc012e6311d9".parse().unwrap(),
+                    kind: "harvest".to_string(),
+                    resource: None,
+                }),
+                lifecycle: None,
+                next_job: None,
+                role: None,
+                extra: Map::new(),
+            }
+        );
+        assert_eq!(memory.creeps["2-0"], CreepMemory::default());
+    }
+
+    #[test]
+    fn memory_survives_a_round_trip() {
+        let mut memory = Memory::default();
+        memory.settings.scan_interval = 8;
+        memory.creeps.insert(
+            "1-0".to_string(),
+            CreepMemory {
+                boosts: Vec::new(),
+                home: Some(RoomName::new("W1N1").unwrap()),
+                job: None,
+                lifecycle: Some(Lifecycle::Recycle),
+                next_job: Some(JobMemory {
+                    id: "5bbcaa9e9099fc012e6311d9".parse().unwrap(),
+                    kind: "deliver".to_string(),
+                    resource: Some(ResourceType::Hydrogen),
+                }),
+                role: Some(Role::Miner),
+                extra: Map::new(),
+            },
+        );
+
+        let raw = serde_json::to_string(&memory).unwrap();
+
+        assert_eq!(parse_memory(&raw), memory);
+    }
+
+    #[test]
+    fn keys_the_bot_doesnt_own_are_kept() {
+        let raw = format!(
+            r#"{{"version": {}, "flags": {{"Flag1": {{}}}}, "spawns": {{}}}}"#,
+            VERSION
+        );
+
+        let memory = parse_memory(&raw);
+        let saved: Value = serde_json::to_value(&memory).unwrap();
+
+        assert_eq!(saved["flags"], json!({"Flag1": {}}));
+        assert_eq!(saved["spawns"], json!({}));
+        assert_eq!(parse_memory(&saved.to_string()), memory);
+    }
+
+    #[test]
+    fn creep_and_room_keys_the_bot_doesnt_own_are_kept() {
+        let raw = format!(
+            r#"{{"version": {}, "creeps": {{"1-0": {{"_move": {{"dest": {{"x": 10}}}}}}}}, "rooms": {{"W1N1": {{"avoid": true}}}}}}"#,
+            VERSION
+        );
+
+        let memory = parse_memory(&raw);
+        let saved: Value = serde_json::to_value(&memory).unwrap();
+
+        assert_eq!(saved["creeps"]["1-0"]["_move"], json!({"dest": {"x": 10}}));
+        assert_eq!(saved["rooms"]["W1N1"]["avoid"], json!(true));
+        assert_eq!(parse_memory(&saved.to_string()), memory);
+    }
+
+    #[test]
+    fn only_unreadable_keys_are_reset() {
+        let raw = format!(
+            r#"{{"version": {}, "settings": {{"scan_interval": 8}}, "creeps": [1], "flags": {{}}}}"#,
+            VERSION
+        );
+
+        let memory = parse_memory(&raw);
+
+        assert_eq!(memory.settings.scan_interval, 8);
+        assert!(memory.creeps.is_empty());
+        assert_eq!(memory.extra["flags"], json!({}));
+    }
+
+    #[test]
+    fn memory_from_the_future_is_reset() {
+        let raw = format!(
+            r#"{{"version": {}, "creeps": {{"1-0": {{}}}}}}"#,
+            VERSION + 1
+        );
+
+        assert_eq!(parse_memory(&raw), Memory::default());
+    }
 }
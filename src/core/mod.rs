@@ -6,6 +6,5 @@ pub mod memory;
 pub mod util;
 
 pub use game_loop::*;
-pub use logging::*;
 pub use memory::*;
 pub use util::*;
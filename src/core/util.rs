@@ -1,6 +1,7 @@
+use screeps::RoomName;
+
 pub trait NumHelper {
     fn limit_max(self, max: Self) -> Self;
-    fn limit_min(self, min: Self) -> Self;
 }
 
 impl NumHelper for u32 {
@@ -11,12 +12,25 @@ impl NumHelper for u32 {
             self
         }
     }
+}
 
-    fn limit_min(self, min: Self) -> Self {
-        if self < min {
-            min
-        } else {
-            self
-        }
+/// The energy it takes a terminal to send `amount` from one room to another
+pub fn transfer_cost(amount: u32, from: RoomName, to: RoomName) -> u32 {
+    let (dx, dy) = from - to;
+    let distance = dx.abs().max(dy.abs()) as f64;
+    (amount as f64 * (1.0 - (-distance / 30.0).exp())).ceil() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sending_costs_more_the_further_it_goes() {
+        let room = |name| RoomName::new(name).unwrap();
+
+        assert_eq!(transfer_cost(1000, room("W1N1"), room("W1N1")), 0);
+        assert_eq!(transfer_cost(1000, room("W1N1"), room("W2N1")), 33);
+        assert_eq!(transfer_cost(1000, room("W1N1"), room("E1S2")), 125);
     }
 }
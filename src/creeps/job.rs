@@ -1,34 +1,135 @@
-use crate::core::constants;
+use crate::core::{constants, JobMemory};
+use crate::world::{
+    ControllerData, CreepData, MineralData, RemainsData, ResourceData, SiteData, SourceData,
+    StructureData, World,
+};
 use log::*;
-use screeps::{ConstructionSite, Creep, HasId, Position, Source, Structure, StructureController};
+use screeps::{Position, RawObjectId, ResourceType, StructureType};
 
 #[derive(Clone)]
 pub enum Job {
-    Attack(Creep),
-    Build(ConstructionSite),
-    Harvest(Source),
-    Heal(Creep),
-    Maintain(Structure),
-    Repair(Structure),
-    Upgrade(StructureController),
+    Attack(CreepData),
+    Build(SiteData),
+    /// Dropping off a carried resource into a store
+    Deliver(StructureData, ResourceType),
+    /// Harvesting a mineral while parked on its container
+    Extract(MineralData, StructureData),
+    Harvest(SourceData),
+    Heal(CreepData),
+    /// Emptying a tombstone
+    Loot(RemainsData),
+    Maintain(StructureData),
+    /// Harvesting while parked on the source's container
+    Mine(SourceData, StructureData),
+    Pickup(ResourceData),
+    /// Taking energy to work with from a store
+    Refuel(StructureData),
+    Repair(StructureData),
+    /// Emptying a ruin
+    Salvage(RemainsData),
+    Upgrade(ControllerData),
+    Withdraw(StructureData, ResourceType),
+}
+
+/// The container within harvesting range of the position, if there's one.
+fn container_near(world: &dyn World, pos: Position) -> Option<StructureData> {
+    world.structures(pos.room_name()).into_iter().find(|s| {
+        s.structure_type == StructureType::Container
+            && s.pos.in_range_to(&pos, constants::RANGE_HARVEST)
+    })
+}
+
+pub fn mineral_container(world: &dyn World, mineral: &MineralData) -> Option<StructureData> {
+    container_near(world, mineral.pos)
+}
+
+pub fn source_container(world: &dyn World, source: &SourceData) -> Option<StructureData> {
+    container_near(world, source.pos)
 }
 
 impl Job {
+    /// Looks the target up again, `None` if it's gone or the kind is unknown.
+    pub fn from_memory(world: &dyn World, memory: &JobMemory) -> Option<Job> {
+        let id = memory.id;
+        let resource = memory.resource.unwrap_or(ResourceType::Energy);
+        match memory.kind.as_str() {
+            "attack" => world.get_creep(id).map(Job::Attack),
+            "build" => world.get_construction_site(id).map(Job::Build),
+            "deliver" => world.get_structure(id).map(|s| Job::Deliver(s, resource)),
+            "extract" => world
+                .get_mineral(id)
+                .and_then(|m| mineral_container(world, &m).map(|c| Job::Extract(m, c))),
+            "harvest" => world.get_source(id).map(Job::Harvest),
+            "heal" => world.get_creep(id).map(Job::Heal),
+            "loot" => world.get_tombstone(id).map(Job::Loot),
+            "maintain" => world.get_structure(id).map(Job::Maintain),
+            "mine" => world
+                .get_source(id)
+                .and_then(|s| source_container(world, &s).map(|c| Job::Mine(s, c))),
+            "pickup" => world.get_resource(id).map(Job::Pickup),
+            "refuel" => world.get_structure(id).map(Job::Refuel),
+            "repair" => world.get_structure(id).map(Job::Repair),
+            "salvage" => world.get_ruin(id).map(Job::Salvage),
+            "upgrade" => world.get_controller(id).map(Job::Upgrade),
+            "withdraw" => world.get_structure(id).map(|s| Job::Withdraw(s, resource)),
+            kind => {
+                warn!("Unknown job type {} in memory", kind);
+                None
+            }
+        }
+    }
+
+    pub fn get_id(&self) -> RawObjectId {
+        match self {
+            Job::Attack(c) | Job::Heal(c) => c.id,
+            Job::Build(c) => c.id,
+            Job::Extract(c, _) => c.id,
+            Job::Harvest(c) | Job::Mine(c, _) => c.id,
+            Job::Loot(c) | Job::Salvage(c) => c.id,
+            Job::Deliver(c, _)
+            | Job::Maintain(c)
+            | Job::Refuel(c)
+            | Job::Repair(c)
+            | Job::Withdraw(c, _) => c.id,
+            Job::Pickup(c) => c.id,
+            Job::Upgrade(c) => c.id,
+        }
+    }
+
+    pub fn to_memory(&self) -> JobMemory {
+        JobMemory {
+            id: self.get_id(),
+            kind: self.get_type().to_string(),
+            resource: match self {
+                Job::Deliver(_, r) | Job::Withdraw(_, r) => Some(*r),
+                _ => None,
+            },
+        }
+    }
+
     pub fn priority(&self) -> u32 {
         match self {
             Job::Attack(_) => constants::PRIORITY_ATTACK,
             Job::Build(_) => constants::PRIORITY_BUILDING,
+            Job::Deliver(..) => constants::PRIORITY_DELIVERING,
+            Job::Extract(..) => constants::PRIORITY_EXTRACTING,
             Job::Harvest(_) => constants::PRIORITY_HARVESTING,
             Job::Heal(_) => constants::PRIORITY_HEALING,
+            Job::Loot(_) => constants::PRIORITY_LOOTING,
             Job::Maintain(_) => constants::PRIORITY_MAINTAINING,
+            Job::Mine(..) => constants::PRIORITY_MINING,
+            Job::Pickup(_) => constants::PRIORITY_PICKING_UP,
+            Job::Refuel(_) => constants::PRIORITY_REFUELING,
             Job::Repair(_) => constants::PRIORITY_REPAIRING,
+            Job::Salvage(_) => constants::PRIORITY_SALVAGING,
             Job::Upgrade(_) => constants::PRIORITY_UPGRADING,
+            Job::Withdraw(..) => constants::PRIORITY_WITHDRAWING,
         }
     }
 
-    pub fn get_construction_site(&self) -> Option<ConstructionSite> {
+    pub fn get_construction_site(&self, world: &dyn World) -> Option<SiteData> {
         match self {
-            Job::Build(c) => screeps::game::get_object_typed(c.id()).unwrap(),
+            Job::Build(c) => world.get_construction_site(c.id),
             _ => {
                 error!(
                     "Tried to get construction site when job is a {}",
@@ -39,9 +140,9 @@ impl Job {
         }
     }
 
-    pub fn get_creep(&self) -> Option<Creep> {
+    pub fn get_creep(&self, world: &dyn World) -> Option<CreepData> {
         match self {
-            Job::Attack(c) | Job::Heal(c) => screeps::game::get_object_typed(c.id()).unwrap(),
+            Job::Attack(c) | Job::Heal(c) => world.get_creep(c.id),
             _ => {
                 error!("Tried to get creep when job is a {}", self.get_type());
                 unimplemented!()
@@ -49,33 +150,112 @@ impl Job {
         }
     }
 
+    /// Whether the job fills the creep up rather than using what it carries.
+    pub fn gathers(&self) -> bool {
+        matches!(
+            self,
+            Job::Harvest(_)
+                | Job::Loot(_)
+                | Job::Pickup(_)
+                | Job::Refuel(_)
+                | Job::Salvage(_)
+                | Job::Withdraw(..)
+        )
+    }
+
+    /// The same job for another resource, if it can move any.
+    pub fn with_resource(self, resource: ResourceType) -> Job {
+        match self {
+            Job::Deliver(s, _) => Job::Deliver(s, resource),
+            Job::Withdraw(s, _) => Job::Withdraw(s, resource),
+            job => job,
+        }
+    }
+
     pub fn get_type(&self) -> &'static str {
         match self {
             Job::Attack(_) => "attack",
             Job::Build(_) => "build",
+            Job::Deliver(..) => "deliver",
+            Job::Extract(..) => "extract",
             Job::Harvest(_) => "harvest",
             Job::Heal(_) => "heal",
+            Job::Loot(_) => "loot",
             Job::Maintain(_) => "maintain",
+            Job::Mine(..) => "mine",
+            Job::Pickup(_) => "pickup",
+            Job::Refuel(_) => "refuel",
             Job::Repair(_) => "repair",
+            Job::Salvage(_) => "salvage",
             Job::Upgrade(_) => "upgrade",
+            Job::Withdraw(..) => "withdraw",
         }
     }
 
     pub fn get_range_to(&self, pos: Position) -> u32 {
         match self {
-            Job::Attack(c) => pos.get_range_to(c),
-            Job::Build(c) => pos.get_range_to(c),
-            Job::Harvest(c) => pos.get_range_to(c),
-            Job::Heal(c) => pos.get_range_to(c),
-            Job::Maintain(c) => pos.get_range_to(c),
-            Job::Repair(c) => pos.get_range_to(c),
-            Job::Upgrade(c) => pos.get_range_to(c),
+            Job::Attack(c) => pos.get_range_to(&c.pos),
+            Job::Build(c) => pos.get_range_to(&c.pos),
+            Job::Deliver(c, _) => pos.get_range_to(&c.pos),
+            Job::Extract(_, c) => pos.get_range_to(&c.pos),
+            Job::Harvest(c) => pos.get_range_to(&c.pos),
+            Job::Heal(c) => pos.get_range_to(&c.pos),
+            Job::Loot(c) => pos.get_range_to(&c.pos),
+            Job::Maintain(c) => pos.get_range_to(&c.pos),
+            Job::Mine(_, c) => pos.get_range_to(&c.pos),
+            Job::Pickup(c) => pos.get_range_to(&c.pos),
+            Job::Refuel(c) => pos.get_range_to(&c.pos),
+            Job::Repair(c) => pos.get_range_to(&c.pos),
+            Job::Salvage(c) => pos.get_range_to(&c.pos),
+            Job::Upgrade(c) => pos.get_range_to(&c.pos),
+            Job::Withdraw(c, _) => pos.get_range_to(&c.pos),
         }
     }
 
-    pub fn get_source(&self) -> Option<Source> {
+    pub fn get_remains(&self, world: &dyn World) -> Option<RemainsData> {
         match self {
-            Job::Harvest(c) => screeps::game::get_object_typed(c.id()).unwrap(),
+            Job::Loot(c) => world.get_tombstone(c.id),
+            Job::Salvage(c) => world.get_ruin(c.id),
+            _ => {
+                error!("Tried to get remains when job is a {}", self.get_type());
+                None
+            }
+        }
+    }
+
+    pub fn get_mineral(&self, world: &dyn World) -> Option<MineralData> {
+        match self {
+            Job::Extract(c, _) => world.get_mineral(c.id),
+            _ => {
+                error!("Tried to get mineral when job is a {}", self.get_type());
+                None
+            }
+        }
+    }
+
+    pub fn get_resource(&self, world: &dyn World) -> Option<ResourceData> {
+        match self {
+            Job::Pickup(c) => world.get_resource(c.id),
+            _ => {
+                error!("Tried to get resource when job is a {}", self.get_type());
+                None
+            }
+        }
+    }
+
+    /// The resource the job moves, `None` if it takes whatever there is.
+    pub fn resource(&self) -> Option<ResourceType> {
+        match self {
+            Job::Deliver(_, r) | Job::Withdraw(_, r) => Some(*r),
+            Job::Maintain(_) | Job::Refuel(_) => Some(ResourceType::Energy),
+            Job::Pickup(r) => Some(r.resource_type),
+            _ => None,
+        }
+    }
+
+    pub fn get_source(&self, world: &dyn World) -> Option<SourceData> {
+        match self {
+            Job::Harvest(c) | Job::Mine(c, _) => world.get_source(c.id),
             _ => {
                 error!("Tried to get source when job is a {}", self.get_type());
                 unimplemented!()
@@ -83,9 +263,15 @@ impl Job {
         }
     }
 
-    pub fn get_structure(&self) -> Option<Structure> {
+    pub fn get_structure(&self, world: &dyn World) -> Option<StructureData> {
         match self {
-            Job::Maintain(c) | Job::Repair(c) => screeps::game::get_object_typed(c.id()).unwrap(),
+            Job::Deliver(c, _)
+            | Job::Extract(_, c)
+            | Job::Maintain(c)
+            | Job::Mine(_, c)
+            | Job::Refuel(c)
+            | Job::Repair(c)
+            | Job::Withdraw(c, _) => world.get_structure(c.id),
             _ => {
                 error!("Tried to get structure when job is a {}", self.get_type());
                 unimplemented!()
@@ -93,15 +279,15 @@ impl Job {
         }
     }
 
-    pub fn get_structure_controller(&self) -> StructureController {
+    pub fn get_structure_controller(&self, world: &dyn World) -> Option<ControllerData> {
         match self {
-            Job::Upgrade(c) => screeps::game::get_object_typed(c.id()).unwrap().unwrap(),
+            Job::Upgrade(c) => world.get_controller(c.id),
             _ => {
                 error!(
                     "Tried to get structure controller when job is a {}",
                     self.get_type()
                 );
-                unimplemented!()
+                None
             }
         }
     }
@@ -0,0 +1,541 @@
+use super::{Job, Logistics};
+use crate::core::{constants, Memory};
+use crate::world::{StructureData, World};
+use log::*;
+use screeps::{Boost, Part, ResourceType, ReturnCode, RoomName, StructureType};
+use screeps::{LAB_MINERAL_CAPACITY, LAB_REACTION_AMOUNT};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+/// What a room's labs are making
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Reaction {
+    pub product: ResourceType,
+    /// Splitting the product back into its reagents instead
+    pub reverse: bool,
+}
+
+impl Reaction {
+    /// `None` for a product that isn't a compound, like one edited into memory by hand
+    fn reagents(&self) -> Option<[ResourceType; 2]> {
+        self.product.reaction_components()
+    }
+}
+
+/// The part a compound boosts, `None` for what isn't a boost
+pub fn boosted_part(compound: ResourceType) -> Option<Part> {
+    Some(match compound.boost()? {
+        Boost::Harvest(_)
+        | Boost::BuildAndRepair(_)
+        | Boost::Dismantle(_)
+        | Boost::UpgradeController(_) => Part::Work,
+        Boost::Attack(_) => Part::Attack,
+        Boost::RangedAttack(_) => Part::RangedAttack,
+        Boost::Heal(_) => Part::Heal,
+        Boost::Carry(_) => Part::Carry,
+        Boost::Move(_) => Part::Move,
+        Boost::Tough(_) => Part::Tough,
+    })
+}
+
+/// How many parts of the body the compound boosts
+pub fn boosted_parts(body: &[Part], compound: ResourceType) -> u32 {
+    let part = boosted_part(compound);
+    body.iter().filter(|p| Some(**p) == part).count() as u32
+}
+
+/// A room's labs by what they do. The inputs hold the reagents, the outputs in range of
+/// both the product, and the boost labs set aside from the outputs a compound for
+/// creeps to be boosted with.
+pub struct Labs {
+    pub boosts: Vec<(StructureData, ResourceType)>,
+    pub inputs: [StructureData; 2],
+    pub outputs: Vec<StructureData>,
+}
+
+impl Labs {
+    /// Takes the two labs the most others are in range of as the inputs
+    pub fn find(world: &dyn World, room: RoomName) -> Option<Self> {
+        let labs: Vec<_> = world
+            .structures(room)
+            .into_iter()
+            .filter(|s| s.my && s.structure_type == StructureType::Lab)
+            .collect();
+        let outputs = |a: &StructureData, b: &StructureData| -> Vec<StructureData> {
+            labs.iter()
+                .filter(|l| l.id != a.id && l.id != b.id)
+                .filter(|l| {
+                    l.pos.in_range_to(&a.pos, constants::RANGE_REACTION)
+                        && l.pos.in_range_to(&b.pos, constants::RANGE_REACTION)
+                })
+                .cloned()
+                .collect()
+        };
+
+        let (a, b) = (0..labs.len())
+            .flat_map(|i| (i + 1..labs.len()).map(move |j| (i, j)))
+            .min_by_key(|&(i, j)| Reverse(outputs(&labs[i], &labs[j]).len()))?;
+        let outputs = outputs(&labs[a], &labs[b]);
+        if outputs.is_empty() {
+            return None;
+        }
+        Some(Self {
+            boosts: Vec::new(),
+            inputs: [labs[a].clone(), labs[b].clone()],
+            outputs,
+        })
+    }
+
+    /// Sets an output lab aside for every compound creeps are waiting for, preferring
+    /// the ones that hold it already
+    pub fn reserve_boosts(&mut self, compounds: &[ResourceType]) {
+        for &compound in compounds {
+            if self.boosts.iter().any(|(_, c)| *c == compound) {
+                continue;
+            }
+            let holding = self
+                .outputs
+                .iter()
+                .position(|l| held(l, compound).is_some_and(|a| a != 0));
+            let i = match holding.or_else(|| self.outputs.len().checked_sub(1)) {
+                Some(i) => i,
+                None => return,
+            };
+            self.boosts.push((self.outputs.remove(i), compound));
+        }
+    }
+
+    pub fn boost_lab(&self, compound: ResourceType) -> Option<&StructureData> {
+        self.boosts
+            .iter()
+            .find(|(_, c)| *c == compound)
+            .map(|(l, _)| l)
+    }
+}
+
+/// How much of `resource` a lab holds, `None` if it holds something else
+fn held(lab: &StructureData, resource: ResourceType) -> Option<u32> {
+    match lab.store.as_ref().and_then(|s| s.mineral()) {
+        Some((r, amount)) if r == resource => Some(amount),
+        Some(_) => None,
+        None => Some(0),
+    }
+}
+
+/// Everything in the room's storage, terminal and labs, what haulers can get at
+pub fn room_stock(world: &dyn World, room: RoomName) -> HashMap<ResourceType, u32> {
+    let mut stock = HashMap::new();
+    for s in world.structures(room) {
+        if !s.my
+            || !matches!(
+                s.structure_type,
+                StructureType::Lab | StructureType::Storage | StructureType::Terminal
+            )
+        {
+            continue;
+        }
+        for (resource, amount) in s.store.iter().flat_map(|s| &s.resources) {
+            *stock.entry(*resource).or_insert(0) += amount;
+        }
+    }
+    stock
+}
+
+/// Makes whatever compound is lowest on stock, as long as there's a batch of both its
+/// reagents. With nothing to make, splits a compound in surplus whose reagent runs low.
+/// The current reaction keeps going until its target is reached or a reagent runs out.
+pub fn pick_reaction(
+    stock: &HashMap<ResourceType, u32>,
+    current: Option<Reaction>,
+) -> Option<Reaction> {
+    let amount = |r: &ResourceType| stock.get(r).copied().unwrap_or(0);
+
+    if let Some((c, reagents)) = current.and_then(|c| Some((c, c.reagents()?))) {
+        let going = match c.reverse {
+            true => amount(&c.product) > constants::REACTION_TARGET,
+            false => {
+                amount(&c.product) < constants::REACTION_TARGET
+                    && reagents.iter().all(|r| amount(r) >= LAB_REACTION_AMOUNT)
+            }
+        };
+        if going {
+            return current;
+        }
+    }
+
+    let reagents = |c: &ResourceType| c.reaction_components().into_iter().flatten();
+    let forward = constants::COMPOUNDS
+        .iter()
+        .filter(|c| amount(c) < constants::REACTION_TARGET)
+        .filter(|c| reagents(c).all(|r| amount(&r) >= constants::REACTION_BATCH))
+        .min_by_key(|c| amount(c))
+        .map(|&product| Reaction {
+            product,
+            reverse: false,
+        });
+
+    forward.or_else(|| {
+        constants::COMPOUNDS
+            .iter()
+            .filter(|c| amount(c) >= constants::REACTION_TARGET + constants::REACTION_BATCH)
+            .filter(|c| reagents(c).any(|r| amount(&r) < constants::REACTION_BATCH))
+            .max_by_key(|c| amount(c))
+            .map(|&product| Reaction {
+                product,
+                reverse: true,
+            })
+    })
+}
+
+/// Picks the next reaction of every owned room with labs to run it in
+pub fn plan_reactions(world: &dyn World, memory: &mut Memory) {
+    for room in world.rooms() {
+        let owned = world
+            .room(room)
+            .and_then(|r| r.controller)
+            .is_some_and(|c| c.my);
+        if !owned || Labs::find(world, room).is_none() {
+            if let Some(room_memory) = memory.rooms.get_mut(&room) {
+                room_memory.reaction = None;
+            }
+            continue;
+        }
+
+        let room_memory = memory.rooms.entry(room).or_default();
+        let next = pick_reaction(&room_stock(world, room), room_memory.reaction);
+        if next != room_memory.reaction {
+            match next {
+                Some(r) if r.reverse => info!("{} reversing {:?}", room, r.product),
+                Some(r) => info!("{} making {:?}", room, r.product),
+                None => info!("{} has nothing left to react", room),
+            }
+        }
+        room_memory.reaction = next;
+    }
+}
+
+/// Runs the reaction in every output lab that has cooled down
+pub fn run_labs(world: &dyn World, labs: &Labs, reaction: Option<Reaction>) {
+    let (reaction, [r1, r2]) = match reaction.and_then(|r| Some((r, r.reagents()?))) {
+        Some(r) => r,
+        None => return,
+    };
+    let [in1, in2] = &labs.inputs;
+    let (mut left1, mut left2) = match (held(in1, r1), held(in2, r2)) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return,
+    };
+
+    for lab in labs.outputs.iter().filter(|l| l.cooldown == 0) {
+        let product = match held(lab, reaction.product) {
+            Some(amount) => amount,
+            None => continue,
+        };
+        let r = if reaction.reverse {
+            let room_left = LAB_MINERAL_CAPACITY - LAB_REACTION_AMOUNT;
+            if product < LAB_REACTION_AMOUNT || left1 > room_left || left2 > room_left {
+                continue;
+            }
+            left1 += LAB_REACTION_AMOUNT;
+            left2 += LAB_REACTION_AMOUNT;
+            world.lab_reverse_reaction(lab.id, in1.id, in2.id)
+        } else {
+            let fits = product + LAB_REACTION_AMOUNT <= LAB_MINERAL_CAPACITY;
+            if !fits || left1 < LAB_REACTION_AMOUNT || left2 < LAB_REACTION_AMOUNT {
+                continue;
+            }
+            left1 -= LAB_REACTION_AMOUNT;
+            left2 -= LAB_REACTION_AMOUNT;
+            world.lab_run_reaction(lab.id, in1.id, in2.id)
+        };
+        if r != ReturnCode::Ok {
+            warn!("Lab at {} couldn't react: {:?}", lab.pos, r);
+        }
+    }
+}
+
+/// Has haulers load the labs with what goes into the reaction and take out what comes
+/// of it, both in batches, and fill the boost labs. Anything else in the labs goes back
+/// to storage.
+pub fn supply_labs(labs: &Labs, reaction: Option<Reaction>, logistics: &mut Logistics) {
+    // What gets loaded into each lab and what comes out of it
+    let [in1, in2] = &labs.inputs;
+    let mut plan: Vec<(&StructureData, Option<ResourceType>, Option<ResourceType>)> = labs
+        .boosts
+        .iter()
+        .map(|(l, compound)| (l, Some(*compound), None))
+        .collect();
+    match reaction.and_then(|r| Some((r, r.reagents()?))) {
+        Some((r, [r1, r2])) if r.reverse => {
+            plan.push((in1, None, Some(r1)));
+            plan.push((in2, None, Some(r2)));
+            plan.extend(labs.outputs.iter().map(|l| (l, Some(r.product), None)));
+        }
+        Some((r, [r1, r2])) => {
+            plan.push((in1, Some(r1), None));
+            plan.push((in2, Some(r2), None));
+            plan.extend(labs.outputs.iter().map(|l| (l, None, Some(r.product))));
+        }
+        None => plan.extend(
+            labs.inputs
+                .iter()
+                .chain(&labs.outputs)
+                .map(|l| (l, None, None)),
+        ),
+    }
+
+    for (lab, load, collect) in plan {
+        let held = lab.store.as_ref().and_then(|s| s.mineral());
+        match (held, load) {
+            (Some((r, amount)), Some(load)) if r == load => {
+                let missing = LAB_MINERAL_CAPACITY - amount.min(LAB_MINERAL_CAPACITY);
+                if missing >= constants::REACTION_BATCH {
+                    let job = Job::Deliver(lab.clone(), load);
+                    logistics.demand(job, missing, 4, Some(load));
+                }
+            }
+            (None, Some(load)) => {
+                let job = Job::Deliver(lab.clone(), load);
+                logistics.demand(job, LAB_MINERAL_CAPACITY, 4, Some(load));
+            }
+            (Some((r, amount)), _) if Some(r) == collect => {
+                if amount >= constants::REACTION_BATCH {
+                    logistics.supply(Job::Withdraw(lab.clone(), r), amount, r);
+                }
+            }
+            (Some((r, amount)), _) => logistics.supply(Job::Withdraw(lab.clone(), r), amount, r),
+            (None, None) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{pos, FakeWorld, Intent, Store};
+    use screeps::{Position, RawObjectId};
+
+    fn lab(world: &FakeWorld, at: Position, mineral: Option<(ResourceType, u32)>) -> RawObjectId {
+        let mut store = Store::lab();
+        if let Some((resource, amount)) = mineral {
+            store = store.with(resource, amount);
+        }
+        world.add_structure(StructureType::Lab, at, Some(store))
+    }
+
+    fn stock(amounts: &[(ResourceType, u32)]) -> HashMap<ResourceType, u32> {
+        amounts.iter().copied().collect()
+    }
+
+    #[test]
+    fn inputs_are_in_range_of_the_most_labs() {
+        let world = FakeWorld::new();
+        lab(&world, pos(20, 20), None);
+        let first = lab(&world, pos(22, 21), None);
+        let second = lab(&world, pos(21, 22), None);
+        lab(&world, pos(23, 23), None);
+        lab(&world, pos(21, 20), None);
+        // Out of reach of the first
+        lab(&world, pos(19, 24), None);
+
+        let labs = Labs::find(&world, pos(0, 0).room_name()).unwrap();
+
+        assert_eq!(labs.inputs.map(|l| l.id), [first, second]);
+        assert_eq!(labs.outputs.len(), 3);
+    }
+
+    #[test]
+    fn reactions_make_what_runs_low() {
+        use ResourceType::*;
+        let stock = stock(&[
+            (Hydrogen, 5000),
+            (Oxygen, 5000),
+            (Utrium, 500),
+            (Hydroxide, 2000),
+            (UtriumHydride, 500),
+        ]);
+
+        // Not enough utrium for another batch of utrium hydride
+        let next = pick_reaction(&stock, None);
+        assert_eq!(
+            next,
+            Some(Reaction {
+                product: Hydroxide,
+                reverse: false
+            })
+        );
+
+        // But enough to keep going
+        let current = Reaction {
+            product: UtriumHydride,
+            reverse: false,
+        };
+        assert_eq!(pick_reaction(&stock, Some(current)), Some(current));
+    }
+
+    #[test]
+    fn surplus_compounds_get_split_when_reagents_run_low() {
+        use ResourceType::*;
+        let stock = stock(&[(Hydrogen, 100), (Oxygen, 5000), (Hydroxide, 5000)]);
+
+        let reverse = Reaction {
+            product: Hydroxide,
+            reverse: true,
+        };
+        assert_eq!(pick_reaction(&stock, None), Some(reverse));
+        // Down to the target
+        let stock = HashMap::from([(Hydrogen, 2100), (Hydroxide, 3000)]);
+        assert_eq!(pick_reaction(&stock, Some(reverse)), None);
+    }
+
+    #[test]
+    fn reactions_of_what_isnt_a_compound_get_dropped() {
+        use ResourceType::*;
+        let stock = stock(&[(Hydrogen, 5000), (Oxygen, 5000)]);
+        let broken = Reaction {
+            product: Hydrogen,
+            reverse: false,
+        };
+
+        let next = pick_reaction(&stock, Some(broken));
+
+        assert_eq!(
+            next,
+            Some(Reaction {
+                product: Hydroxide,
+                reverse: false
+            })
+        );
+    }
+
+    #[test]
+    fn outputs_react_once_cooled_down() {
+        use ResourceType::*;
+        let world = FakeWorld::new();
+        let first = lab(&world, pos(20, 20), Some((Oxygen, 10)));
+        let second = lab(&world, pos(21, 20), Some((Hydrogen, 500)));
+        let outputs: Vec<_> = (19..=22)
+            .map(|x| lab(&world, pos(x, 22), Some((Hydroxide, 100))))
+            .collect();
+        world.state_mut().structures.iter_mut().for_each(|s| {
+            if s.id == outputs[0] {
+                s.cooldown = 10;
+            }
+        });
+
+        let reaction = Reaction {
+            product: Hydroxide,
+            reverse: false,
+        };
+        let labs = Labs::find(&world, pos(0, 0).room_name()).unwrap();
+        run_labs(&world, &labs, Some(reaction));
+
+        // Only enough oxygen left for two
+        let reacting = |lab| Intent::RunReaction {
+            lab,
+            lab1: first,
+            lab2: second,
+        };
+        assert_eq!(
+            world.state().intents,
+            vec![reacting(outputs[1]), reacting(outputs[2])]
+        );
+    }
+
+    #[test]
+    fn haulers_load_reagents_and_collect_products() {
+        use ResourceType::*;
+        let world = FakeWorld::new();
+        let storage = Store::new(100_000).with(Oxygen, 5000).with(Hydrogen, 5000);
+        let storage = world.add_structure(StructureType::Storage, pos(25, 25), Some(storage));
+        let first = lab(&world, pos(20, 20), Some((Oxygen, 2500)));
+        let second = lab(&world, pos(21, 20), None);
+        let full = lab(&world, pos(20, 22), Some((Hydroxide, 2000)));
+        let small = lab(&world, pos(21, 22), Some((Hydroxide, 10)));
+        world.add_creep("hauler", pos(22, 22), Store::new(1000));
+        let hauler = world.creeps().pop().unwrap();
+
+        let reaction = Reaction {
+            product: Hydroxide,
+            reverse: false,
+        };
+        let room = pos(0, 0).room_name();
+        let mut logistics = Logistics::scan(&world, room);
+        let labs = Labs::find(&world, room).unwrap();
+        supply_labs(&labs, Some(reaction), &mut logistics);
+        let deliveries: Vec<_> = (0..6)
+            .filter_map(|_| logistics.assign(&hauler))
+            .map(|d| (d.pickup.unwrap().get_id(), d.dropoff.get_id()))
+            .collect();
+
+        // The first lab is full enough and the small batch of product can wait
+        assert_eq!(
+            deliveries[..3],
+            [(full, storage), (full, storage), (storage, second)]
+        );
+        assert!(deliveries
+            .iter()
+            .all(|&(from, to)| to != first && from != small));
+    }
+
+    #[test]
+    fn idle_labs_get_emptied() {
+        use ResourceType::*;
+        let world = FakeWorld::new();
+        let storage = world.add_structure(
+            StructureType::Storage,
+            pos(25, 25),
+            Some(Store::new(100_000)),
+        );
+        let first = lab(&world, pos(20, 20), Some((Oxygen, 10)));
+        lab(&world, pos(21, 20), None);
+        lab(&world, pos(20, 22), None);
+        world.add_creep("hauler", pos(22, 22), Store::new(1000));
+        let hauler = world.creeps().pop().unwrap();
+
+        let room = pos(0, 0).room_name();
+        let mut logistics = Logistics::scan(&world, room);
+        let labs = Labs::find(&world, room).unwrap();
+        supply_labs(&labs, None, &mut logistics);
+        let delivery = logistics.assign(&hauler).unwrap();
+
+        assert_eq!(delivery.pickup.unwrap().get_id(), first);
+        assert_eq!(delivery.dropoff.get_id(), storage);
+    }
+
+    #[test]
+    fn boost_labs_get_filled_with_their_compound() {
+        use ResourceType::*;
+        let world = FakeWorld::new();
+        let storage = Store::new(100_000)
+            .with(CatalyzedGhodiumAcid, 3000)
+            .with(CatalyzedUtriumAlkalide, 3000);
+        let storage = world.add_structure(StructureType::Storage, pos(25, 25), Some(storage));
+        lab(&world, pos(20, 20), None);
+        lab(&world, pos(21, 20), None);
+        let holding = lab(&world, pos(20, 22), Some((CatalyzedGhodiumAcid, 100)));
+        let empty = lab(&world, pos(21, 22), None);
+        world.add_creep("hauler", pos(22, 22), Store::new(1000));
+        let hauler = world.creeps().pop().unwrap();
+
+        let room = pos(0, 0).room_name();
+        let mut labs = Labs::find(&world, room).unwrap();
+        labs.reserve_boosts(&[CatalyzedUtriumAlkalide, CatalyzedGhodiumAcid]);
+        assert!(labs.outputs.is_empty());
+        assert_eq!(labs.boost_lab(CatalyzedGhodiumAcid).unwrap().id, holding);
+        assert_eq!(labs.boost_lab(CatalyzedUtriumAlkalide).unwrap().id, empty);
+
+        let mut logistics = Logistics::scan(&world, room);
+        supply_labs(&labs, None, &mut logistics);
+        let mut dropoffs: Vec<_> = (0..6)
+            .filter_map(|_| logistics.assign(&hauler))
+            .inspect(|d| assert_eq!(d.pickup.as_ref().unwrap().get_id(), storage))
+            .map(|d| d.dropoff.get_id())
+            .collect();
+        dropoffs.sort();
+        dropoffs.dedup();
+
+        assert_eq!(dropoffs, vec![holding, empty]);
+    }
+}
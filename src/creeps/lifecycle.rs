@@ -0,0 +1,134 @@
+use crate::core::{constants, CreepMemory};
+use crate::world::{CreepData, SpawnData};
+use screeps::{Part, CREEP_LIFE_TIME};
+use serde::{Deserialize, Serialize};
+
+/// What a creep does at a spawn instead of working, it's off the job market meanwhile.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Lifecycle {
+    Renew,
+    Recycle,
+}
+
+/// Decides what the creep should do next and at which spawn, `None` to keep working.
+///
+/// Big bodies get renewed at a nearby idle spawn when they grow old, bodies with less
+/// than half the main parts of what `energy` spawns now get recycled. Recycling is final.
+pub fn plan_lifecycle(
+    creep: &CreepData,
+    memory: &CreepMemory,
+    spawns: &[SpawnData],
+    energy: u32,
+) -> Option<(Lifecycle, SpawnData)> {
+    // Creeps from before roles can't be told apart from the ones still needed
+    let role = memory.role?;
+    let nearest = |spawns: &mut dyn Iterator<Item = &SpawnData>| {
+        spawns
+            .min_by_key(|s| s.pos.get_range_to(&creep.pos))
+            .cloned()
+    };
+
+    let parts = role.parts(&creep.body);
+    let undersized = role
+        .body(energy)
+        .is_some_and(|b| role.parts(&b) >= 2 * parts.max(1));
+    if memory.lifecycle == Some(Lifecycle::Recycle) || undersized {
+        return nearest(&mut spawns.iter()).map(|s| (Lifecycle::Recycle, s));
+    }
+
+    let ttl = creep.ticks_to_live?;
+    let cost = creep.body.iter().map(|p| p.cost()).sum::<u32>();
+    // Claim parts can't be renewed
+    if cost < constants::RENEW_MIN_COST || creep.body.contains(&Part::Claim) {
+        return None;
+    }
+
+    let renewing = memory.lifecycle == Some(Lifecycle::Renew);
+    // Every renewal adds 600 / parts ticks and can't go beyond the creep's life time
+    let full = ttl + 600 / creep.body.len() as u32 > CREEP_LIFE_TIME;
+    if (renewing && !full) || ttl < constants::RENEW_BELOW {
+        nearest(
+            &mut spawns
+                .iter()
+                .filter(|s| s.spawning.is_none())
+                .filter(|s| renewing || s.pos.get_range_to(&creep.pos) <= constants::RENEW_RANGE),
+        )
+        .map(|s| (Lifecycle::Renew, s))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::creeps::Role;
+    use crate::world::{pos, Store};
+    use screeps::RawObjectId;
+    use serde_json::Map;
+
+    fn spawns() -> Vec<SpawnData> {
+        vec![SpawnData {
+            id: RawObjectId::from([0, 0, 1]),
+            name: "Spawn1".to_string(),
+            pos: pos(25, 25),
+            spawning: None,
+        }]
+    }
+
+    fn creep(role: Role, energy: u32, ticks_to_live: u32) -> (CreepData, CreepMemory) {
+        let creep = CreepData {
+            body: role.body(energy).unwrap(),
+            fatigue: 0,
+            hits: 100,
+            hits_max: 100,
+            id: RawObjectId::from([0, 0, 2]),
+            name: "creep".to_string(),
+            pos: pos(30, 30),
+            store: Store::new(50),
+            ticks_to_live: Some(ticks_to_live),
+        };
+        let memory = CreepMemory {
+            role: Some(role),
+            extra: Map::new(),
+            ..CreepMemory::default()
+        };
+        (creep, memory)
+    }
+
+    fn plan(creep: &CreepData, memory: &CreepMemory, energy: u32) -> Option<Lifecycle> {
+        plan_lifecycle(creep, memory, &spawns(), energy).map(|(l, _)| l)
+    }
+
+    #[test]
+    fn old_big_creeps_get_renewed() {
+        let (creep, mut memory) = creep(Role::Upgrader, 2000, 1000);
+        assert_eq!(plan(&creep, &memory, 300), None);
+
+        let (creep, _) = self::creep(Role::Upgrader, 2000, 100);
+        assert_eq!(plan(&creep, &memory, 300), Some(Lifecycle::Renew));
+
+        // Keeps going until another renewal would be wasted
+        memory.lifecycle = Some(Lifecycle::Renew);
+        let (creep, _) = self::creep(Role::Upgrader, 2000, 1000);
+        assert_eq!(plan(&creep, &memory, 300), Some(Lifecycle::Renew));
+        let (creep, _) = self::creep(Role::Upgrader, 2000, 1480);
+        assert_eq!(plan(&creep, &memory, 300), None);
+    }
+
+    #[test]
+    fn small_creeps_die_of_old_age() {
+        let (creep, memory) = creep(Role::Upgrader, 300, 100);
+
+        assert_eq!(plan(&creep, &memory, 300), None);
+    }
+
+    #[test]
+    fn undersized_creeps_get_recycled_once_better_is_affordable() {
+        let (creep, memory) = creep(Role::Upgrader, 300, 1000);
+
+        assert_eq!(plan(&creep, &memory, 300), None);
+        assert_eq!(plan(&creep, &memory, 400), Some(Lifecycle::Recycle));
+    }
+}
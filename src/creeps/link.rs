@@ -0,0 +1,178 @@
+use crate::core::constants;
+use crate::world::{StructureData, World};
+use log::*;
+use screeps::{Position, RawObjectId, ResourceType, ReturnCode, RoomName, StructureType};
+
+/// What a link is for, told apart by what it's built next to
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum LinkKind {
+    /// Filled by a miner
+    Source,
+    /// Feeds the upgraders
+    Controller,
+    /// Feeds whoever refuels from it
+    Sink,
+    /// Filled and emptied by haulers
+    Storage,
+}
+
+pub fn link_kind(world: &dyn World, link: &StructureData) -> LinkKind {
+    let room = link.pos.room_name();
+    let near = |pos: Position| pos.in_range_to(&link.pos, constants::LINK_RANGE);
+
+    if world.sources(room).iter().any(|s| near(s.pos)) {
+        LinkKind::Source
+    } else if world
+        .room(room)
+        .and_then(|r| r.controller)
+        .is_some_and(|c| near(c.pos))
+    {
+        LinkKind::Controller
+    } else if world
+        .structures(room)
+        .iter()
+        .any(|s| s.structure_type == StructureType::Storage && near(s.pos))
+    {
+        LinkKind::Storage
+    } else {
+        LinkKind::Sink
+    }
+}
+
+fn energy(link: &StructureData) -> u32 {
+    link.store
+        .as_ref()
+        .map_or(0, |s| s.used_capacity(Some(ResourceType::Energy)))
+}
+
+fn free(link: &StructureData) -> u32 {
+    link.store
+        .as_ref()
+        .map_or(0, |s| s.free_capacity(Some(ResourceType::Energy)))
+}
+
+/// Sends energy on from the links it arrives at. Source links feed the controller first,
+/// then the sinks and what's left goes to the storage, which tops up the rest in turn.
+/// Links only send full batches, as every transfer loses a share and costs a cooldown.
+pub fn run_links(world: &dyn World, room: RoomName) {
+    let mut links: Vec<_> = world
+        .structures(room)
+        .into_iter()
+        .filter(|s| s.my && s.structure_type == StructureType::Link)
+        .map(|l| (link_kind(world, &l), l))
+        .collect();
+    links.sort_by_key(|(kind, _)| *kind);
+
+    let mut served: Vec<RawObjectId> = Vec::new();
+    for (kind, sender) in &links {
+        let sends = matches!(kind, LinkKind::Source | LinkKind::Storage);
+        if !sends || sender.cooldown > 0 || energy(sender) < constants::LINK_BATCH {
+            continue;
+        }
+
+        let receiver = links
+            .iter()
+            .filter(|(k, l)| match k {
+                LinkKind::Controller | LinkKind::Sink => energy(l) < constants::LINK_BATCH,
+                LinkKind::Storage => *kind == LinkKind::Source,
+                LinkKind::Source => false,
+            })
+            .filter(|(_, l)| free(l) > 0 && !served.contains(&l.id))
+            .min_by_key(|(k, l)| (*k, energy(l)));
+        if let Some((_, receiver)) = receiver {
+            let amount = energy(sender).min(free(receiver));
+            match world.link_transfer_energy(sender.id, receiver.id, amount) {
+                ReturnCode::Ok => served.push(receiver.id),
+                r => warn!("Link at {} couldn't send energy: {:?}", sender.pos, r),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{empty_room, pos, FakeWorld, Intent, Store};
+    use screeps::LINK_CAPACITY;
+
+    fn rcl5_room() -> FakeWorld {
+        let world = empty_room();
+        world.add_controller(pos(40, 40), 5);
+        world.add_source(pos(10, 10));
+        world.add_structure(StructureType::Storage, pos(25, 25), Some(Store::new(1000)));
+        world
+    }
+
+    fn link(world: &FakeWorld, at: Position, energy: u32) -> RawObjectId {
+        let store = Store::new(LINK_CAPACITY).with(ResourceType::Energy, energy);
+        world.add_structure(StructureType::Link, at, Some(store))
+    }
+
+    #[test]
+    fn links_are_told_apart_by_their_neighbours() {
+        let world = rcl5_room();
+        let kind = |at| {
+            let link = world.get_structure(link(&world, at, 0)).unwrap();
+            link_kind(&world, &link)
+        };
+
+        assert_eq!(kind(pos(12, 11)), LinkKind::Source);
+        assert_eq!(kind(pos(38, 40)), LinkKind::Controller);
+        assert_eq!(kind(pos(26, 24)), LinkKind::Storage);
+        assert_eq!(kind(pos(30, 30)), LinkKind::Sink);
+    }
+
+    #[test]
+    fn source_links_feed_the_controller_first() {
+        let world = rcl5_room();
+        let source = link(&world, pos(12, 11), 800);
+        let controller = link(&world, pos(38, 40), 100);
+        link(&world, pos(26, 24), 0);
+
+        run_links(&world, pos(0, 0).room_name());
+
+        assert_eq!(
+            world.state().intents,
+            vec![Intent::LinkTransfer {
+                link: source,
+                target: controller,
+                amount: 700
+            }]
+        );
+    }
+
+    #[test]
+    fn storage_links_top_up_what_sources_leave() {
+        let world = rcl5_room();
+        let source = link(&world, pos(12, 11), 800);
+        let storage = link(&world, pos(26, 24), 800);
+        link(&world, pos(38, 40), 800);
+        let first = link(&world, pos(30, 30), 0);
+        let second = link(&world, pos(30, 20), 200);
+        let cooling = link(&world, pos(10, 12), 800);
+        world.state_mut().structures.iter_mut().for_each(|s| {
+            if s.id == cooling {
+                s.cooldown = 3;
+            }
+        });
+
+        run_links(&world, pos(0, 0).room_name());
+
+        // The controller is full and the second source link is cooling down
+        assert_eq!(
+            world.state().intents,
+            vec![
+                Intent::LinkTransfer {
+                    link: source,
+                    target: first,
+                    amount: 800
+                },
+                Intent::LinkTransfer {
+                    link: storage,
+                    target: second,
+                    amount: 600
+                }
+            ]
+        );
+    }
+}
@@ -0,0 +1,491 @@
+use super::{
+    link_kind, mineral_container, room_stock, source_container, terminal_target, Job, LinkKind,
+};
+use crate::core::constants;
+use crate::world::{CreepData, Store, World};
+use screeps::{Position, ResourceType, RoomName, StructureType, LAB_ENERGY_CAPACITY};
+
+/// Something offering or wanting resources.
+#[derive(Clone)]
+pub struct Request {
+    pub amount: u32,
+    pub job: Job,
+    /// Lower gets served first, only used for demands
+    pub priority: u32,
+    /// `None` takes any resource, only used for demands
+    pub resource: Option<ResourceType>,
+}
+
+/// A hauler's next trip. Creeps already carrying something go straight to the dropoff.
+pub struct Delivery {
+    pub pickup: Option<Job>,
+    pub dropoff: Job,
+}
+
+/// Supply and demand of resources in a room, what's already on its way booked off.
+#[derive(Default)]
+pub struct Logistics {
+    demands: Vec<Request>,
+    /// Energy in stores that creeps can refuel from
+    stocks: Vec<Request>,
+    supplies: Vec<Request>,
+}
+
+impl Logistics {
+    pub fn scan(world: &dyn World, room: RoomName) -> Self {
+        let mut logistics = Self::default();
+
+        let mining_containers: Vec<_> = world
+            .sources(room)
+            .iter()
+            .filter_map(|s| source_container(world, s))
+            .chain(
+                world
+                    .minerals(room)
+                    .iter()
+                    .filter_map(|m| mineral_container(world, m)),
+            )
+            .map(|c| c.id)
+            .collect();
+        let controller = world.room(room).and_then(|r| r.controller).map(|c| c.pos);
+
+        for s in world.structures(room) {
+            let store = match &s.store {
+                Some(store) if s.my || s.structure_type == StructureType::Container => {
+                    store.clone()
+                }
+                _ => continue,
+            };
+            let free = store.free_capacity(Some(ResourceType::Energy));
+            let energy = store.used_capacity(Some(ResourceType::Energy));
+
+            if let StructureType::Container
+            | StructureType::Link
+            | StructureType::Storage
+            | StructureType::Terminal = s.structure_type
+            {
+                if energy != 0 {
+                    logistics.stocks.push(Request {
+                        amount: energy,
+                        job: Job::Refuel(s.clone()),
+                        priority: 0,
+                        resource: Some(ResourceType::Energy),
+                    });
+                }
+            }
+
+            match s.structure_type {
+                StructureType::Spawn | StructureType::Extension => {
+                    logistics.demand(Job::Maintain(s), free, 0, Some(ResourceType::Energy))
+                }
+                StructureType::Tower => {
+                    logistics.demand(Job::Maintain(s), free, 1, Some(ResourceType::Energy))
+                }
+                StructureType::Container if mining_containers.contains(&s.id) => {
+                    logistics.supply_store(Job::Withdraw(s, ResourceType::Energy), &store)
+                }
+                // Upgraders take their energy from here
+                StructureType::Container
+                    if controller.is_some_and(|c| {
+                        c.in_range_to(&s.pos, constants::RANGE_UPGRADE_CONTROLLER)
+                    }) =>
+                {
+                    let job = Job::Deliver(s, ResourceType::Energy);
+                    logistics.demand(job, free, 2, Some(ResourceType::Energy))
+                }
+                StructureType::Container => {
+                    logistics.supply_store(Job::Withdraw(s, ResourceType::Energy), &store)
+                }
+                StructureType::Storage => {
+                    logistics.supply_store(Job::Withdraw(s.clone(), ResourceType::Energy), &store);
+                    logistics.demand(Job::Deliver(s, ResourceType::Energy), free, 3, None);
+                }
+                StructureType::Lab => {
+                    let missing = LAB_ENERGY_CAPACITY.saturating_sub(energy);
+                    logistics.demand(Job::Maintain(s), missing, 4, Some(ResourceType::Energy))
+                }
+                // Half full, so it can both take what the sources send and feed the
+                // controller
+                StructureType::Link if link_kind(world, &s) == LinkKind::Storage => {
+                    let target = constants::LINK_BATCH;
+                    let excess = energy.saturating_sub(target);
+                    let missing = target.saturating_sub(energy).min(free);
+                    let job = Job::Withdraw(s.clone(), ResourceType::Energy);
+                    logistics.supply(job, excess, ResourceType::Energy);
+                    let job = Job::Deliver(s, ResourceType::Energy);
+                    logistics.demand(job, missing, 4, Some(ResourceType::Energy));
+                }
+                // Holds what the room can spare and enough energy to pay for sending it,
+                // anything else goes back to storage
+                StructureType::Terminal => {
+                    let mut stock = room_stock(world, room);
+                    stock.entry(ResourceType::Energy).or_insert(0);
+                    for &resource in stock.keys() {
+                        let target = terminal_target(&stock, resource);
+                        let held = store.used_capacity(Some(resource));
+                        let job = Job::Withdraw(s.clone(), resource);
+                        logistics.supply(job, held.saturating_sub(target), resource);
+                        let missing = target.saturating_sub(held).min(free);
+                        let job = Job::Deliver(s.clone(), resource);
+                        logistics.demand(job, missing, 4, Some(resource));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for r in world.dropped_resources(room) {
+            logistics.supply(Job::Pickup(r.clone()), r.amount, r.resource_type);
+        }
+        for t in world.tombstones(room) {
+            logistics.supply_store(Job::Loot(t.clone()), &t.store);
+        }
+        for r in world.ruins(room) {
+            logistics.supply_store(Job::Salvage(r.clone()), &r.store);
+        }
+
+        logistics
+    }
+
+    /// Pairs the creep with the most important demand it can serve, and the supply
+    /// closest to the way there.
+    pub fn assign(&mut self, creep: &CreepData) -> Option<Delivery> {
+        let pos = creep.pos;
+
+        if !creep.store.resources.is_empty() {
+            let (d, resource, amount) = creep
+                .store
+                .resources
+                .iter()
+                .filter_map(|(&r, &a)| self.best_demand(pos, |d| d.accepts(r)).map(|d| (d, r, a)))
+                .min_by_key(|(d, _, _)| {
+                    let d = &self.demands[*d];
+                    (d.priority, d.job.get_range_to(pos))
+                })?;
+            self.demands[d].amount = self.demands[d].amount.saturating_sub(amount);
+            return Some(Delivery {
+                pickup: None,
+                dropoff: self.demands[d].job.clone().with_resource(resource),
+            });
+        }
+
+        let capacity = creep.store.capacity;
+        let (s, d) = self
+            .demands
+            .iter()
+            .enumerate()
+            .filter(|(_, d)| d.amount != 0)
+            .filter_map(|(i, d)| {
+                let dropoff = d.job.get_id();
+                let to = d.job.get_range_to(pos);
+                self.supplies
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, s)| s.amount != 0 && s.job.get_id() != dropoff)
+                    .filter(|(_, s)| s.resource.is_some_and(|r| d.accepts(r)))
+                    .map(|(j, s)| (j, s.job.get_range_to(pos) + range(&s.job, &d.job)))
+                    .min_by_key(|(_, r)| *r)
+                    .map(|(j, r)| ((d.priority, to + r), j, i))
+            })
+            .min_by_key(|(key, _, _)| *key)
+            .map(|(_, s, d)| (s, d))?;
+
+        let amount = capacity
+            .min(self.supplies[s].amount)
+            .min(self.demands[d].amount);
+        self.supplies[s].amount -= amount;
+        self.demands[d].amount -= amount;
+        let pickup = self.supplies[s].job.clone();
+        Self::reserve(&mut self.stocks, &pickup, amount);
+        let resource = self.supplies[s].resource.unwrap();
+
+        Some(Delivery {
+            pickup: Some(pickup),
+            dropoff: self.demands[d].job.clone().with_resource(resource),
+        })
+    }
+
+    /// The closest store with energy left, preferring ones that have all of `amount`.
+    pub fn refuel(&self, pos: Position, amount: u32) -> Option<Job> {
+        self.stocks
+            .iter()
+            .filter(|s| s.amount != 0)
+            .min_by_key(|s| (s.amount < amount, s.job.get_range_to(pos)))
+            .map(|s| s.job.clone())
+    }
+
+    pub fn reserve_demand(&mut self, job: &Job, amount: u32) {
+        Self::reserve(&mut self.demands, job, amount)
+    }
+
+    /// Books the amount off the supplies and stocks of the job's target.
+    pub fn reserve_supply(&mut self, job: &Job, amount: u32) {
+        Self::reserve(&mut self.stocks, job, amount);
+        Self::reserve(&mut self.supplies, job, amount);
+    }
+
+    fn best_demand<F: Fn(&Request) -> bool>(&self, pos: Position, f: F) -> Option<usize> {
+        self.demands
+            .iter()
+            .enumerate()
+            .filter(|(_, d)| d.amount != 0 && f(d))
+            .min_by_key(|(_, d)| (d.priority, d.job.get_range_to(pos)))
+            .map(|(i, _)| i)
+    }
+
+    pub fn demand(&mut self, job: Job, amount: u32, priority: u32, resource: Option<ResourceType>) {
+        if amount != 0 {
+            self.demands.push(Request {
+                amount,
+                job,
+                priority,
+                resource,
+            });
+        }
+    }
+
+    fn reserve(requests: &mut [Request], job: &Job, mut amount: u32) {
+        let id = job.get_id();
+        let resource = job.resource();
+        for r in requests
+            .iter_mut()
+            .filter(|r| r.job.get_id() == id)
+            .filter(|r| resource.is_none_or(|resource| r.accepts(resource)))
+        {
+            let booked = amount.min(r.amount);
+            r.amount -= booked;
+            amount -= booked;
+        }
+    }
+
+    pub fn supply(&mut self, job: Job, amount: u32, resource: ResourceType) {
+        if amount != 0 {
+            self.supplies.push(Request {
+                amount,
+                job,
+                priority: 0,
+                resource: Some(resource),
+            });
+        }
+    }
+
+    /// Offers everything in the store.
+    fn supply_store(&mut self, job: Job, store: &Store) {
+        for (resource, amount) in &store.resources {
+            self.supply(job.clone().with_resource(*resource), *amount, *resource);
+        }
+    }
+}
+
+impl Request {
+    fn accepts(&self, resource: ResourceType) -> bool {
+        self.resource.is_none_or(|r| r == resource)
+    }
+}
+
+fn range(from: &Job, to: &Job) -> u32 {
+    match to {
+        Job::Deliver(s, _) | Job::Maintain(s) => from.get_range_to(s.pos),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{empty_room, pos, FakeWorld, Store};
+    use screeps::{RawObjectId, LINK_CAPACITY};
+
+    fn depots() -> FakeWorld {
+        let world = empty_room();
+        world.add_source(pos(10, 10));
+        world.add_structure(
+            StructureType::Container,
+            pos(11, 11),
+            Some(Store::new(2000).with(ResourceType::Energy, 1000)),
+        );
+        world.add_structure(
+            StructureType::Storage,
+            pos(30, 30),
+            Some(Store::new(10_000)),
+        );
+        world
+    }
+
+    fn hauler(world: &FakeWorld, name: &str, store: Store) -> CreepData {
+        world.add_creep(name, pos(20, 20), store);
+        world.creeps().into_iter().find(|c| c.name == name).unwrap()
+    }
+
+    fn ids(delivery: &Delivery) -> (Option<RawObjectId>, RawObjectId) {
+        (
+            delivery.pickup.as_ref().map(Job::get_id),
+            delivery.dropoff.get_id(),
+        )
+    }
+
+    #[test]
+    fn spawns_get_fed_before_storage() {
+        let world = depots();
+        let spawn = world.add_spawn("Spawn1", pos(25, 25));
+        world.state_mut().structures.last_mut().unwrap().store = Some(Store::new(300));
+        let container = world.structures(pos(0, 0).room_name())[0].id;
+        let storage = world.structures(pos(0, 0).room_name())[1].id;
+
+        let mut logistics = Logistics::scan(&world, pos(0, 0).room_name());
+        let first = logistics.assign(&hauler(&world, "first", Store::new(300)));
+        let second = logistics.assign(&hauler(&world, "second", Store::new(300)));
+
+        assert_eq!(ids(&first.unwrap()), (Some(container), spawn));
+        assert_eq!(ids(&second.unwrap()), (Some(container), storage));
+    }
+
+    #[test]
+    fn dropped_resources_get_picked_up() {
+        let world = depots();
+        world.state_mut().structures[0].store = Some(Store::new(2000));
+        world.add_resource(pos(21, 21), ResourceType::Hydrogen, 100);
+        let pile = world.dropped_resources(pos(0, 0).room_name())[0].id;
+        let storage = world.structures(pos(0, 0).room_name())[1].id;
+
+        let mut logistics = Logistics::scan(&world, pos(0, 0).room_name());
+        let delivery = logistics.assign(&hauler(&world, "hauler", Store::new(300)));
+
+        assert_eq!(ids(&delivery.unwrap()), (Some(pile), storage));
+        // All of it is on its way
+        assert!(logistics
+            .assign(&hauler(&world, "other", Store::new(300)))
+            .is_none());
+    }
+
+    #[test]
+    fn loaded_haulers_go_straight_to_the_dropoff() {
+        let world = depots();
+        let storage = world.structures(pos(0, 0).room_name())[1].id;
+
+        let mut logistics = Logistics::scan(&world, pos(0, 0).room_name());
+        let loaded = Store::new(300).with(ResourceType::Energy, 300);
+        let delivery = logistics.assign(&hauler(&world, "hauler", loaded));
+
+        assert_eq!(ids(&delivery.unwrap()), (None, storage));
+    }
+
+    #[test]
+    fn remains_get_emptied_into_storage() {
+        let world = depots();
+        world.state_mut().structures[0].store = Some(Store::new(2000));
+        let store = Store::new(1000).with(ResourceType::Hydrogen, 100);
+        let ruin = world.add_ruin(pos(21, 21), store, 500);
+        let storage = world.structures(pos(0, 0).room_name())[1].id;
+
+        let mut logistics = Logistics::scan(&world, pos(0, 0).room_name());
+        let delivery = logistics.assign(&hauler(&world, "hauler", Store::new(300)));
+
+        assert_eq!(ids(&delivery.unwrap()), (Some(ruin), storage));
+    }
+
+    #[test]
+    fn storage_links_get_emptied_down_to_a_batch() {
+        let world = depots();
+        world.state_mut().structures[0].store = Some(Store::new(2000));
+        let store = Store::new(LINK_CAPACITY).with(ResourceType::Energy, 800);
+        let link = world.add_structure(StructureType::Link, pos(31, 31), Some(store));
+        let storage = world.structures(pos(0, 0).room_name())[1].id;
+
+        let mut logistics = Logistics::scan(&world, pos(0, 0).room_name());
+        let first = logistics.assign(&hauler(&world, "first", Store::new(300)));
+        let second = logistics.assign(&hauler(&world, "second", Store::new(300)));
+
+        assert_eq!(ids(&first.unwrap()), (Some(link), storage));
+        // The rest stays for the controller
+        assert_eq!(ids(&second.unwrap()), (Some(link), storage));
+        assert!(logistics
+            .assign(&hauler(&world, "third", Store::new(300)))
+            .is_none());
+    }
+
+    #[test]
+    fn minerals_go_to_storage_and_energy_to_the_terminal() {
+        let world = FakeWorld::new();
+        let container = world.add_structure(
+            StructureType::Container,
+            pos(21, 21),
+            Some(Store::new(2000).with(ResourceType::Hydrogen, 500)),
+        );
+        let storage = world.add_structure(
+            StructureType::Storage,
+            pos(30, 30),
+            Some(Store::new(100_000).with(ResourceType::Energy, 50_000)),
+        );
+        let terminal = world.add_structure(
+            StructureType::Terminal,
+            pos(32, 30),
+            Some(Store::new(10_000)),
+        );
+
+        let mut logistics = Logistics::scan(&world, pos(0, 0).room_name());
+        let first = logistics
+            .assign(&hauler(&world, "first", Store::new(500)))
+            .unwrap();
+        let second = logistics
+            .assign(&hauler(&world, "second", Store::new(500)))
+            .unwrap();
+
+        assert_eq!(ids(&first), (Some(container), storage));
+        assert_eq!(first.dropoff.resource(), Some(ResourceType::Hydrogen));
+        assert_eq!(ids(&second), (Some(storage), terminal));
+        assert_eq!(second.dropoff.resource(), Some(ResourceType::Energy));
+    }
+
+    #[test]
+    fn terminals_hold_what_the_room_can_spare() {
+        let world = FakeWorld::new();
+        let storage = world.add_structure(
+            StructureType::Storage,
+            pos(30, 30),
+            Some(Store::new(100_000).with(ResourceType::Hydrogen, 8000)),
+        );
+        let terminal = world.add_structure(
+            StructureType::Terminal,
+            pos(32, 30),
+            Some(
+                Store::new(100_000)
+                    .with(ResourceType::Energy, 10_000)
+                    .with(ResourceType::Oxygen, 500),
+            ),
+        );
+
+        let mut logistics = Logistics::scan(&world, pos(0, 0).room_name());
+        let first = logistics
+            .assign(&hauler(&world, "first", Store::new(500)))
+            .unwrap();
+        let second = logistics
+            .assign(&hauler(&world, "second", Store::new(5000)))
+            .unwrap();
+
+        // Oxygen the room is short of goes back, the spare hydrogen gets ready to send
+        assert_eq!(ids(&first), (Some(terminal), storage));
+        assert_eq!(first.dropoff.resource(), Some(ResourceType::Oxygen));
+        assert_eq!(ids(&second), (Some(storage), terminal));
+        assert_eq!(second.dropoff.resource(), Some(ResourceType::Hydrogen));
+        assert!(logistics
+            .assign(&hauler(&world, "third", Store::new(500)))
+            .is_none());
+    }
+
+    #[test]
+    fn carried_resources_go_where_they_are_needed_most() {
+        let world = depots();
+        let spawn = world.add_spawn("Spawn1", pos(25, 25));
+        world.state_mut().structures.last_mut().unwrap().store = Some(Store::new(300));
+
+        let mut logistics = Logistics::scan(&world, pos(0, 0).room_name());
+        let loaded = Store::new(300)
+            .with(ResourceType::Hydrogen, 250)
+            .with(ResourceType::Energy, 50);
+        let delivery = logistics.assign(&hauler(&world, "hauler", loaded)).unwrap();
+
+        assert_eq!(ids(&delivery), (None, spawn));
+        assert_eq!(delivery.dropoff.resource(), Some(ResourceType::Energy));
+    }
+}
@@ -0,0 +1,487 @@
+use super::{room_stock, stock_target};
+use crate::core::{constants, transfer_cost, MarketSettings, Memory};
+use crate::world::{OrderData, StructureData, World};
+use log::*;
+use screeps::{
+    game::market::OrderType, ResourceType, ReturnCode, RoomName, StructureType, MARKET_FEE,
+};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// The average price over the order history, weighed by the volume traded
+fn average_price(world: &dyn World, resource: ResourceType) -> Option<f64> {
+    let history = world.price_history(resource);
+    let volume = history.iter().map(|h| h.volume).sum::<u32>();
+    if volume == 0 {
+        return None;
+    }
+    let total = history
+        .iter()
+        .map(|h| h.avg_price * h.volume as f64)
+        .sum::<f64>();
+    Some(total / volume as f64)
+}
+
+fn fee(price: f64, amount: u32) -> f64 {
+    price * amount as f64 * MARKET_FEE as f64
+}
+
+struct Trader<'a> {
+    world: &'a dyn World,
+    settings: &'a MarketSettings,
+    /// Credits left to spend this scan
+    budget: f64,
+    /// What the energy a transfer costs is worth
+    energy_price: f64,
+    my_orders: Vec<OrderData>,
+}
+
+impl Trader<'_> {
+    /// The other player's order that pays the most or asks the least, the energy for the
+    /// transfer included, as long as that's within bounds of the average price
+    fn best_order(
+        &self,
+        room: RoomName,
+        side: OrderType,
+        resource: ResourceType,
+        price: f64,
+    ) -> Option<OrderData> {
+        let net = |o: &OrderData| {
+            // The cost grows in proportion with the amount
+            let to = o.room.unwrap_or(room);
+            let energy = transfer_cost(1000, room, to) as f64 / 1000.0 * self.energy_price;
+            match side {
+                OrderType::Sell => o.price - energy,
+                OrderType::Buy => -(o.price + energy),
+            }
+        };
+        let (wanted, bound) = match side {
+            OrderType::Sell => (OrderType::Buy, price * self.settings.price_floor),
+            OrderType::Buy => (OrderType::Sell, -price * self.settings.price_ceiling),
+        };
+
+        self.world
+            .market_orders(resource)
+            .into_iter()
+            .filter(|o| o.order_type == wanted && o.amount != 0 && o.room.is_some())
+            .filter(|o| !self.my_orders.iter().any(|m| m.id == o.id))
+            .filter(|o| net(o) >= bound)
+            .max_by(|a, b| net(a).partial_cmp(&net(b)).unwrap_or(Ordering::Equal))
+    }
+
+    /// Fills as much of the order as the terminal and the budget allow
+    fn deal(&mut self, terminal: &StructureData, side: OrderType, order: &OrderData, amount: u32) {
+        let room = terminal.pos.room_name();
+        let mut amount = amount.min(order.amount);
+        if side == OrderType::Buy {
+            amount = amount.min((self.budget / order.price) as u32);
+        }
+        let energy = terminal
+            .store
+            .as_ref()
+            .map_or(0, |s| s.used_capacity(Some(ResourceType::Energy)));
+        if amount == 0 || transfer_cost(amount, room, order.room.unwrap_or(room)) > energy {
+            debug!("Can't afford dealing {:?} in {}", order.resource_type, room);
+            return;
+        }
+
+        match self.world.market_deal(&order.id, amount, room) {
+            ReturnCode::Ok => {
+                let verb = match side {
+                    OrderType::Sell => "Sold",
+                    OrderType::Buy => {
+                        self.budget -= order.price * amount as f64;
+                        "Bought"
+                    }
+                };
+                info!(
+                    "{} {} {:?} in {} at {:.3} through order {}",
+                    verb, amount, order.resource_type, room, order.price, order.id
+                );
+            }
+            r => warn!(
+                "Couldn't deal {:?} in {}: {:?}",
+                order.resource_type, room, r
+            ),
+        }
+    }
+
+    fn within_bounds(&self, side: OrderType, order_price: f64, price: f64) -> bool {
+        match side {
+            OrderType::Sell => order_price >= price * self.settings.price_floor,
+            OrderType::Buy => order_price <= price * self.settings.price_ceiling,
+        }
+    }
+
+    fn cancel(&mut self, order: &OrderData, why: &str) {
+        match self.world.market_cancel_order(&order.id) {
+            ReturnCode::Ok => {
+                info!(
+                    "Cancelled {:?} order {} for {:?} in {:?}, {}",
+                    order.order_type, order.id, order.resource_type, order.room, why
+                );
+                self.my_orders.retain(|o| o.id != order.id);
+            }
+            r => warn!("Couldn't cancel order {}: {:?}", order.id, r),
+        }
+    }
+
+    /// Puts up an order of our own at the average price, renewing it once it's filled and
+    /// cancelling it once the going rate leaves its price out of bounds
+    fn place_order(
+        &mut self,
+        room: RoomName,
+        side: OrderType,
+        resource: ResourceType,
+        amount: u32,
+        price: f64,
+    ) {
+        let world = self.world;
+        let order = self
+            .my_orders
+            .iter()
+            .find(|o| o.order_type == side && o.resource_type == resource && o.room == Some(room))
+            .cloned();
+        let order = match order {
+            Some(o) => o,
+            None => {
+                if fee(price, amount) > self.budget {
+                    return;
+                }
+                match world.market_create_order(side, resource, price, amount, room) {
+                    ReturnCode::Ok => {
+                        self.budget -= fee(price, amount);
+                        info!(
+                            "Placed {:?} order for {} {:?} in {} at {:.3}",
+                            side, amount, resource, room, price
+                        );
+                    }
+                    r => warn!(
+                        "Couldn't place order for {:?} in {}: {:?}",
+                        resource, room, r
+                    ),
+                }
+                return;
+            }
+        };
+
+        if !self.within_bounds(side, order.price, price) {
+            self.cancel(&order, "its price is out of bounds");
+            return;
+        }
+
+        let raise = fee((price - order.price).max(0.0), order.amount);
+        if (order.price - price).abs() > price * constants::MARKET_REPRICE && raise <= self.budget {
+            match world.market_change_price(&order.id, price) {
+                ReturnCode::Ok => {
+                    self.budget -= raise;
+                    info!(
+                        "Repriced order {} for {:?} from {:.3} to {:.3}",
+                        order.id, resource, order.price, price
+                    );
+                }
+                r => warn!("Couldn't reprice order {}: {:?}", order.id, r),
+            }
+        }
+
+        let add = amount.saturating_sub(order.amount);
+        let renew = fee(price.max(order.price), add);
+        if order.amount < constants::TERMINAL_MIN_SEND && add != 0 && renew <= self.budget {
+            match world.market_extend_order(&order.id, add) {
+                ReturnCode::Ok => {
+                    self.budget -= renew;
+                    info!("Renewed order {} for {} {:?}", order.id, add, resource);
+                }
+                r => warn!("Couldn't renew order {}: {:?}", order.id, r),
+            }
+        }
+    }
+}
+
+/// Sells what the colony can spare and buys the minerals it's short of. Other players'
+/// orders are taken when their price, the energy for the transfer included, is within
+/// bounds of the average price, otherwise we put up orders of our own.
+pub fn trade(world: &dyn World, memory: &Memory) {
+    let settings = &memory.settings.market;
+    let mut trader = Trader {
+        budget: settings
+            .budget
+            .min(world.credits() - settings.credit_reserve)
+            .max(0.0),
+        energy_price: average_price(world, ResourceType::Energy).unwrap_or(0.0),
+        my_orders: world.my_orders(),
+        settings,
+        world,
+    };
+
+    let rooms: Vec<_> = world
+        .rooms()
+        .into_iter()
+        .filter_map(|room| {
+            let terminal = world
+                .structures(room)
+                .into_iter()
+                .find(|s| s.my && s.structure_type == StructureType::Terminal)?;
+            Some((terminal, room_stock(world, room)))
+        })
+        .collect();
+    let amount = |stock: &HashMap<ResourceType, u32>, r| stock.get(&r).copied().unwrap_or(0);
+    // Rooms share among themselves before anything gets traded
+    let short = |r| rooms.iter().any(|(_, s)| amount(s, r) < stock_target(r));
+    let spare = |r| rooms.iter().any(|(_, s)| amount(s, r) > stock_target(r));
+
+    let mut plans = Vec::new();
+    for (terminal, stock) in &rooms {
+        let held = |r| {
+            terminal
+                .store
+                .as_ref()
+                .map_or(0, |s| s.used_capacity(Some(r)))
+        };
+        let sales = stock
+            .iter()
+            .filter(|(&r, _)| r != ResourceType::Energy && !short(r))
+            .map(|(&r, &a)| {
+                (
+                    OrderType::Sell,
+                    r,
+                    a.saturating_sub(stock_target(r)).min(held(r)),
+                )
+            });
+        let purchases = constants::MINERALS
+            .iter()
+            .filter(|&&r| !spare(r))
+            .map(|&r| {
+                let missing = stock_target(r).saturating_sub(amount(stock, r));
+                (OrderType::Buy, r, missing.min(constants::TERMINAL_BATCH))
+            });
+        let trades: Vec<_> = sales
+            .chain(purchases)
+            .filter(|&(_, _, a)| a >= constants::TERMINAL_MIN_SEND)
+            .collect();
+        plans.push((terminal, trades));
+    }
+
+    // Orders for what's no longer in surplus or short go before any new ones
+    let stale: Vec<_> = trader
+        .my_orders
+        .iter()
+        .filter(|o| {
+            !plans.iter().any(|(t, trades)| {
+                o.room == Some(t.pos.room_name())
+                    && trades
+                        .iter()
+                        .any(|&(side, r, _)| side == o.order_type && r == o.resource_type)
+            })
+        })
+        .cloned()
+        .collect();
+    for order in &stale {
+        trader.cancel(order, "it's no longer needed");
+    }
+
+    for (terminal, trades) in plans {
+        let room = terminal.pos.room_name();
+        // A terminal deals once per tick
+        let mut ready = terminal.cooldown == 0;
+        for (side, resource, amount) in trades {
+            let price = match average_price(world, resource) {
+                Some(p) => p,
+                None => continue,
+            };
+            match trader.best_order(room, side, resource, price) {
+                Some(order) if ready => {
+                    trader.deal(terminal, side, &order, amount);
+                    ready = false;
+                }
+                Some(_) => {}
+                None => trader.place_order(room, side, resource, amount, price),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{empty_room, pos, FakeWorld, Intent, Store};
+    use screeps::TERMINAL_CAPACITY;
+
+    fn world(resources: &[(ResourceType, u32)]) -> FakeWorld {
+        let world = empty_room();
+        let store = resources
+            .iter()
+            .fold(Store::new(TERMINAL_CAPACITY), |s, &(r, a)| s.with(r, a));
+        world.add_structure(StructureType::Terminal, pos(25, 25), Some(store));
+        world.state_mut().credits = 100_000.0;
+        world.set_price(ResourceType::Energy, 1.0);
+        world
+    }
+
+    fn stocked() -> Vec<(ResourceType, u32)> {
+        use ResourceType::*;
+        let mut stock: Vec<_> = constants::MINERALS.iter().map(|&r| (r, 5000)).collect();
+        stock.push((Energy, 10_000));
+        stock
+    }
+
+    #[test]
+    fn surplus_goes_to_the_best_offer_after_transfer_costs() {
+        use ResourceType::*;
+        let mut stock = stocked();
+        stock[0] = (Hydrogen, 8000);
+        let world = world(&stock);
+        world.set_price(Hydrogen, 2.0);
+        // Pays the most, but it's too far to be worth it
+        world.add_order(
+            OrderType::Buy,
+            Hydrogen,
+            2.05,
+            5000,
+            RoomName::new("E20S20").unwrap(),
+        );
+        let near = world.add_order(
+            OrderType::Buy,
+            Hydrogen,
+            1.95,
+            5000,
+            RoomName::new("W2N1").unwrap(),
+        );
+        world.add_order(
+            OrderType::Buy,
+            Hydrogen,
+            1.5,
+            5000,
+            RoomName::new("W1N2").unwrap(),
+        );
+
+        trade(&world, &Memory::default());
+
+        assert_eq!(
+            world.state().intents,
+            vec![Intent::Deal {
+                order: near,
+                amount: 3000,
+                room: pos(25, 25).room_name()
+            }]
+        );
+    }
+
+    #[test]
+    fn missing_minerals_get_ordered_when_sellers_ask_too_much() {
+        use ResourceType::*;
+        let mut stock = stocked();
+        stock[1] = (Oxygen, 1000);
+        let world = world(&stock);
+        world.set_price(Oxygen, 1.0);
+        world.add_order(
+            OrderType::Sell,
+            Oxygen,
+            1.5,
+            5000,
+            RoomName::new("W2N1").unwrap(),
+        );
+
+        trade(&world, &Memory::default());
+
+        assert_eq!(
+            world.state().intents,
+            vec![Intent::CreateOrder {
+                order_type: OrderType::Buy,
+                resource: Oxygen,
+                price: 1.0,
+                amount: 4000,
+                room: pos(25, 25).room_name()
+            }]
+        );
+    }
+
+    #[test]
+    fn own_orders_get_repriced_and_renewed() {
+        use ResourceType::*;
+        let mut stock = stocked();
+        stock[2] = (Utrium, 1000);
+        let world = world(&stock);
+        world.set_price(Utrium, 1.0);
+        world.state_mut().my_orders.push(OrderData {
+            amount: 0,
+            id: "mine".to_string(),
+            order_type: OrderType::Buy,
+            price: 0.5,
+            resource_type: Utrium,
+            room: Some(pos(25, 25).room_name()),
+        });
+
+        trade(&world, &Memory::default());
+
+        assert_eq!(
+            world.state().intents,
+            vec![
+                Intent::ChangeOrderPrice {
+                    order: "mine".to_string(),
+                    price: 1.0
+                },
+                Intent::ExtendOrder {
+                    order: "mine".to_string(),
+                    amount: 4000
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn own_orders_get_cancelled_once_unneeded_or_out_of_bounds() {
+        use ResourceType::*;
+        let mut stock = stocked();
+        stock[2] = (Utrium, 1000);
+        let world = world(&stock);
+        world.set_price(Utrium, 1.0);
+        let order = |id: &str, resource, price| OrderData {
+            amount: 4000,
+            id: id.to_string(),
+            order_type: OrderType::Buy,
+            price,
+            resource_type: resource,
+            room: Some(pos(25, 25).room_name()),
+        };
+        world.state_mut().my_orders = vec![
+            order("stocked", Oxygen, 1.0),
+            order("overpriced", Utrium, 2.0),
+        ];
+
+        trade(&world, &Memory::default());
+
+        assert_eq!(
+            world.state().intents,
+            vec![
+                Intent::CancelOrder {
+                    order: "stocked".to_string()
+                },
+                Intent::CancelOrder {
+                    order: "overpriced".to_string()
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn nothing_gets_bought_below_the_credit_reserve() {
+        use ResourceType::*;
+        let mut stock = stocked();
+        stock[1] = (Oxygen, 1000);
+        let world = world(&stock);
+        world.set_price(Oxygen, 1.0);
+        world.add_order(
+            OrderType::Sell,
+            Oxygen,
+            1.0,
+            5000,
+            RoomName::new("W2N1").unwrap(),
+        );
+        world.state_mut().credits = 10_000.0;
+
+        trade(&world, &Memory::default());
+
+        assert!(world.state().intents.is_empty());
+    }
+}
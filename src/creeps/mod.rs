@@ -1,13 +1,27 @@
 pub mod job;
 pub mod job_offer;
+pub mod lab;
+pub mod lifecycle;
+pub mod link;
+pub mod logistics;
+pub mod market;
 pub mod regulator;
+pub mod role;
 pub mod spawn;
+pub mod terminal;
 pub mod tower;
 pub mod work;
 
 pub use job::*;
 pub use job_offer::*;
+pub use lab::*;
+pub use lifecycle::*;
+pub use link::*;
+pub use logistics::*;
+pub use market::*;
 pub use regulator::*;
+pub use role::*;
 pub use spawn::*;
+pub use terminal::*;
 pub use tower::*;
 pub use work::*;
@@ -1,11 +1,19 @@
-use super::{Creep, Job, JobOffer, Tower};
-use crate::core::{constants, NumHelper};
+use super::{
+    boosted_parts, mineral_container, plan_lifecycle, population, room_stock, run_labs, run_links,
+    source_container, spawn_creep, supply_labs, Creep, Demand, Job, JobOffer, Labs, Lifecycle,
+    Logistics, Role, SpawnQueue, SpawnRequest, Tower,
+};
+use crate::core::{constants, CreepMemory, Memory, NumHelper};
+use crate::planner::hits_target;
+use crate::world::{CreepData, RemainsData, RoomTerrain, World};
 use log::*;
 use screeps::{
-    constants::StructureType, find, prelude::*, Attackable, LookResult, Position, ResourceType,
-    Room, Structure, StructureTower, Terrain,
+    constants::StructureType, Part, Position, ResourceType, ReturnCode, RoomName, Terrain,
+    ATTACK_POWER, BUILD_POWER, CARRY_CAPACITY, CONTROLLER_MAX_UPGRADE_PER_TICK, CREEP_LIFE_TIME,
+    ENERGY_DECAY, ENERGY_REGEN_TIME, HARVEST_POWER, HEAL_POWER, LAB_BOOST_MINERAL,
 };
-use std::{collections::HashMap, convert::TryInto};
+use serde_json::Map;
+use std::collections::HashMap;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -14,161 +22,623 @@ pub enum Error {
     Creep(#[from] super::work::Error),
     #[error("Couldn't make tower do action `{0:?}`")]
     Tower(#[from] super::tower::Error),
+    #[error("Couldn't spawn: `{0:?}`")]
+    Spawn(ReturnCode),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Requester of the spawns that keep the room's population up.
+const DEMAND_REQUESTER: &str = "demand";
+
+/// What will be left of `amount` once a creep gets there. Remains hold on to their
+/// contents until they decay, resources on the ground shrink every tick.
+fn salvageable(amount: u32, ticks_to_decay: u32) -> u32 {
+    let exposed = constants::SALVAGE_TRAVEL_TICKS.saturating_sub(ticks_to_decay);
+    amount.saturating_sub(amount.div_ceil(ENERGY_DECAY) * exposed)
+}
+
 pub struct Regulator {
     creeps: HashMap<String, Creep>,
     towers: HashMap<String, Tower>,
     jobs: Vec<JobOffer>,
-    room: Room,
+    room: RoomName,
+    spawn_queue: SpawnQueue,
+    terrain: RoomTerrain,
 }
 
 impl Regulator {
-    pub fn distribute_jobs(&mut self, respawned: bool) -> Result<()> {
-        let creeps = screeps::game::creeps::values();
+    pub fn adopt_creep(&mut self, memory: &mut Memory, mut creep: Creep) {
+        debug!("{} adopting creep {}", self.room, creep.get_name());
 
-        if respawned {
-            // Remove dead creeps
-            self.creeps = self
-                .creeps
-                .drain()
-                .filter(|(name, _)| creeps.iter().any(|c| &c.name() == name))
-                .collect();
+        // Jobs are offered per room, so whatever it did for the old one is void now
+        creep.drop_job();
+        let creep_memory = memory.creeps.entry(creep.get_name()).or_default();
+        creep_memory.home = Some(self.room);
+        creep_memory.job = None;
+        self.creeps.insert(creep.get_name(), creep);
+    }
+
+    pub fn creep_names(&self) -> Vec<String> {
+        self.creeps.keys().cloned().collect()
+    }
+
+    pub fn distribute_jobs(&mut self, world: &dyn World, memory: &mut Memory) -> Result<()> {
+        let creeps = self.own_creeps(world, memory);
+
+        // Remove dead creeps every tick, so their memory isn't written back
+        self.creeps
+            .retain(|name, _| creeps.iter().any(|c| &c.name == name));
+
+        let room = self.room;
+        let time = world.time();
+        let energy = world.room(room).map(|r| r.energy_available).unwrap_or(0);
+        let spawns: Vec<_> = world
+            .spawns()
+            .into_iter()
+            .filter(|s| s.pos.room_name() == room)
+            .collect();
+        let mut recycling = creeps.iter().any(|c| {
+            let m = memory.creeps.get(&c.name);
+            m.is_some_and(|m| m.lifecycle == Some(Lifecycle::Recycle))
+        });
+
+        let reaction = memory.rooms.get(&room).and_then(|r| r.reaction);
+        let labs = self.labs(world, memory);
+        let mut logistics = Logistics::scan(world, room);
+        if let Some(labs) = &labs {
+            supply_labs(labs, reaction, &mut logistics);
+        }
+        // Only the creeps alive this tick book what they're on their way to
+        for creep in creeps.iter().filter_map(|c| self.creeps.get(&c.name)) {
+            creep.reserve(&mut logistics);
         }
 
         for s_creep in creeps {
-            if let Some(creep) = self.creeps.get_mut(&s_creep.name()) {
-                creep.set_creep(s_creep);
-                creep.select_job(&mut self.jobs)?
-            } else {
-                let mut creep = Creep::from_creep(s_creep);
-                creep.select_job(&mut self.jobs)?;
-                self.creeps.insert(creep.get_name(), creep);
+            let creep_memory = memory.creeps.entry(s_creep.name.clone()).or_default();
+            let mut plan = plan_lifecycle(&s_creep, creep_memory, &spawns, energy);
+
+            // One creep at a time, the rest keep working until its replacement is there
+            if matches!(plan, Some((Lifecycle::Recycle, _)))
+                && creep_memory.lifecycle != Some(Lifecycle::Recycle)
+            {
+                if recycling {
+                    plan = None;
+                }
+                recycling = true;
+            }
+
+            let creep = self
+                .creeps
+                .entry(s_creep.name.clone())
+                .or_insert_with(|| Creep::from_creep(s_creep.clone()));
+            creep.set_role(creep_memory.role);
+            if creep.get_pos() != s_creep.pos && s_creep.pos.room_name() == room {
+                let room_memory = memory.rooms.entry(room).or_default();
+                room_memory.traffic.record(time, s_creep.pos);
+            }
+            let fresh = s_creep
+                .ticks_to_live
+                .is_none_or(|t| t + constants::BOOST_WAIT >= CREEP_LIFE_TIME);
+            if plan.is_none() && creep_memory.role == Some(Role::Hauler) && creep.is_idle() {
+                if let Some(delivery) = logistics.assign(&s_creep) {
+                    creep.haul(delivery);
+                }
+            }
+            creep.set_creep(s_creep);
+
+            // Fresh creeps get their boosts before they start working, unless the labs
+            // take too long to get them ready
+            if let Some(&compound) = creep_memory.boosts.first() {
+                match labs.as_ref().and_then(|l| l.boost_lab(compound)) {
+                    Some(lab) if fresh => {
+                        creep.drop_job();
+                        match creep.get_boosted(world, lab) {
+                            Ok(false) => {}
+                            Ok(true) => {
+                                creep_memory.boosts.remove(0);
+                            }
+                            Err(e) => {
+                                warn!("{}", e);
+                                creep_memory.boosts.remove(0);
+                            }
+                        }
+                        continue;
+                    }
+                    _ => {
+                        debug!("{} going without {:?}", creep.get_name(), compound);
+                        creep_memory.boosts.remove(0);
+                    }
+                }
+            }
+
+            creep_memory.lifecycle = None;
+            if let Some((lifecycle, spawn)) = plan {
+                creep.drop_job();
+                match creep.run_lifecycle(world, lifecycle, &spawn) {
+                    Ok(true) => creep_memory.lifecycle = Some(lifecycle),
+                    Ok(false) => {}
+                    Err(e) => warn!("{}", e),
+                }
+            }
+            if creep_memory.lifecycle.is_none() {
+                creep.select_job(world, &mut self.jobs, &mut logistics)?;
             }
         }
 
-        let towers = self
-            .room
-            .find(screeps::constants::find::MY_STRUCTURES)
+        let towers = world
+            .structures(self.room)
             .into_iter()
-            .filter_map(|s| match s.as_structure() {
-                Structure::Tower(t) => Some(t),
-                _ => None,
-            })
-            .collect::<Vec<StructureTower>>();
+            .filter(|s| s.my && s.structure_type == StructureType::Tower)
+            .collect::<Vec<_>>();
 
         // Filter out removed towers
         self.towers = self
             .towers
             .drain()
-            .filter(|(id, _)| towers.iter().any(|t| &t.id().to_string() == id))
+            .filter(|(id, _)| towers.iter().any(|t| &t.id.to_string() == id))
             .collect();
 
         for s_tower in towers {
-            if let Some(tower) = self.towers.get_mut(&s_tower.id().to_string()) {
+            if let Some(tower) = self.towers.get_mut(&s_tower.id.to_string()) {
                 tower.set_tower(s_tower);
-                tower.select_job(&mut self.jobs)?;
+                tower.select_job(world, &mut self.jobs)?;
             } else {
                 let mut tower = Tower::from_tower(s_tower);
-                tower.select_job(&mut self.jobs)?;
+                tower.select_job(world, &mut self.jobs)?;
                 self.towers.insert(tower.get_id(), tower);
             }
         }
 
+        run_links(world, room);
+        if let Some(labs) = &labs {
+            run_labs(world, labs, reaction);
+        }
+
+        self.save(memory);
+
         Ok(())
     }
-    pub fn new(room: Room) -> Self {
-        Self {
-            creeps: screeps::game::creeps::values()
-                .into_iter()
-                .map(|c| (c.name(), Creep::from_creep(c)))
-                .collect(),
-            towers: room
-                .find(screeps::constants::find::MY_STRUCTURES)
+
+    pub fn new(world: &dyn World, memory: &mut Memory, room: RoomName) -> Self {
+        let room_memory = memory.rooms.get(&room).cloned().unwrap_or_default();
+
+        let mut regulator = Self {
+            creeps: HashMap::new(),
+            towers: world
+                .structures(room)
                 .into_iter()
-                .filter_map(|s| match s.as_structure() {
-                    Structure::Tower(t) => Some((t.id().to_string(), Tower::from_tower(t))),
-                    _ => None,
+                .filter(|s| s.my && s.structure_type == StructureType::Tower)
+                .map(|t| {
+                    let mut tower = Tower::from_tower(t);
+                    if let Some(job) = room_memory.towers.get(&tower.get_id()) {
+                        tower.restore_job(world, job);
+                    }
+                    (tower.get_id(), tower)
                 })
                 .collect(),
             jobs: Vec::new(),
             room,
+            spawn_queue: SpawnQueue::default(),
+            terrain: world.terrain(room),
+        };
+
+        regulator.creeps = regulator
+            .own_creeps(world, memory)
+            .into_iter()
+            .map(|c| {
+                let mut creep = Creep::from_creep(c);
+                if let Some(creep_memory) = memory.creeps.get(&creep.get_name()) {
+                    if let Some(job) = &creep_memory.job {
+                        creep.restore_job(world, job);
+                    }
+                    if let Some(job) = &creep_memory.next_job {
+                        creep.restore_next_job(world, job);
+                    }
+                }
+                (creep.get_name(), creep)
+            })
+            .collect();
+        regulator
+    }
+
+    /// Stops managing the creep, so it can be handed to another regulator with
+    /// `adopt_creep`.
+    pub fn release_creep(&mut self, name: &str) -> Option<Creep> {
+        self.creeps.remove(name)
+    }
+
+    pub fn request_spawn(&mut self, request: SpawnRequest) {
+        self.spawn_queue.push(request);
+    }
+
+    /// Queues the creeps the room is short of, then lets every idle spawn of the room
+    /// work off the front of the queue.
+    pub fn spawn_creeps(&mut self, world: &dyn World, memory: &mut Memory) -> Result<bool> {
+        self.plan_spawns(world, memory);
+
+        let mut energy = match world.room(self.room) {
+            Some(r) => r.energy_available,
+            None => return Ok(false),
+        };
+        let mut spawned = false;
+        let room = self.room;
+
+        for spawn in world
+            .spawns()
+            .into_iter()
+            .filter(|s| s.pos.room_name() == room && s.spawning.is_none())
+        {
+            let request = match self.spawn_queue.peek() {
+                Some(r) => r,
+                None => break,
+            };
+
+            let body = if request.cost() <= energy {
+                request.body.clone()
+            } else if let Some(body) = request.role.body(energy).filter(|_| request.emergency) {
+                warn!("Emergency spawn of {:?} in {}", request.role, self.room);
+                body
+            } else {
+                debug!("Waiting for energy to spawn {:?}", request.role);
+                break;
+            };
+
+            let request = self.spawn_queue.pop().unwrap();
+            let r = spawn_creep(world, memory, &spawn, &body, request.memory.clone());
+            if r != ReturnCode::Ok {
+                self.spawn_queue.push(request);
+                return Err(Error::Spawn(r));
+            }
+
+            energy -= body.iter().map(|p| p.cost()).sum::<u32>();
+            spawned = true;
         }
+
+        Ok(spawned)
     }
 
-    pub fn scan(&mut self) {
+    pub fn scan(&mut self, world: &dyn World) {
         self.jobs.clear();
 
-        self.scan_attack_jobs();
-        self.scan_build_jobs();
-        self.scan_harvest_jobs();
-        self.scan_heal_jobs();
-        self.scan_maintain_jobs();
-        self.scan_repair_jobs();
-        self.scan_upgrade_jobs();
+        self.scan_attack_jobs(world);
+        self.scan_build_jobs(world);
+        self.scan_extract_jobs(world);
+        self.scan_harvest_jobs(world);
+        self.scan_heal_jobs(world);
+        self.scan_maintain_jobs(world);
+        self.scan_repair_jobs(world);
+        self.scan_salvage_jobs(world);
+        self.scan_upgrade_jobs(world);
+    }
+
+    fn own_creeps(&self, world: &dyn World, memory: &mut Memory) -> Vec<CreepData> {
+        world
+            .creeps()
+            .into_iter()
+            .filter(|c| {
+                let creep_memory = memory.creeps.entry(c.name.clone()).or_default();
+                match creep_memory.home {
+                    Some(home) => home == self.room,
+                    None if c.pos.room_name() == self.room => {
+                        // Creeps spawned before homes were recorded settle where they are
+                        creep_memory.home = Some(self.room);
+                        true
+                    }
+                    None => false,
+                }
+            })
+            .collect()
+    }
+
+    /// Writes the current assignments to memory, so they survive a global reset.
+    fn save(&self, memory: &mut Memory) {
+        for (name, creep) in &self.creeps {
+            let creep_memory = memory.creeps.entry(name.clone()).or_default();
+            creep_memory.job = creep.job_memory();
+            creep_memory.next_job = creep.next_job_memory();
+        }
+
+        memory.rooms.entry(self.room).or_default().towers = self
+            .towers
+            .iter()
+            .filter_map(|(id, t)| t.job_memory().map(|j| (id.clone(), j)))
+            .collect();
     }
 
-    pub fn set_room(&mut self, room: Room) {
-        self.room = room;
+    /// The room's labs, with the boosts its creeps wait for set aside. Queued creeps
+    /// count too, so the labs are filled by the time they spawn.
+    fn labs(&self, world: &dyn World, memory: &Memory) -> Option<Labs> {
+        let mut labs = Labs::find(world, self.room)?;
+        let waiting: Vec<_> = memory
+            .creeps
+            .values()
+            .filter(|m| m.home == Some(self.room))
+            .flat_map(|m| m.boosts.iter().copied())
+            .chain(self.spawn_queue.boosts())
+            .collect();
+        labs.reserve_boosts(&waiting);
+        Some(labs)
+    }
+
+    fn plan_spawns(&mut self, world: &dyn World, memory: &Memory) {
+        self.spawn_queue.withdraw(DEMAND_REQUESTER);
+
+        let capacity = world
+            .room(self.room)
+            .map(|r| r.energy_capacity_available)
+            .unwrap_or(0);
+        let population = population(world, memory, self.room, capacity);
+        let bodies = |role| population.get(&role).map(Vec::as_slice).unwrap_or(&[]);
+        let labs = Labs::find(world, self.room).is_some();
+        let stock = room_stock(world, self.room);
+        let harvesters = [Role::Miner, Role::Upgrader, Role::Builder]
+            .iter()
+            .map(|r| bodies(*r).len())
+            .sum::<usize>();
+
+        for (priority, &role) in Role::ALL.iter().enumerate() {
+            let body: Vec<Part> = match role.body(capacity) {
+                Some(b) => b,
+                None => continue,
+            };
+
+            // Only what there's enough of for the whole body
+            let boosts: Vec<_> = role
+                .boosts()
+                .iter()
+                .copied()
+                .filter(|&c| {
+                    let parts = boosted_parts(&body, c);
+                    let enough = stock
+                        .get(&c)
+                        .is_some_and(|&a| a >= parts * LAB_BOOST_MINERAL);
+                    labs && parts != 0 && enough
+                })
+                .collect();
+
+            let demand = self.demand(world, role);
+            let mut creeps = bodies(role).len() + self.spawn_queue.count(role);
+            let mut parts = bodies(role).iter().map(|b| role.parts(b)).sum::<u32>()
+                + self.spawn_queue.parts(role);
+
+            while parts < demand.parts && creeps < demand.max_creeps {
+                self.request_spawn(SpawnRequest {
+                    body: body.clone(),
+                    // Without anyone harvesting the room would never fill up again
+                    emergency: role == Role::Miner && harvesters == 0 && creeps == 0,
+                    memory: CreepMemory {
+                        boosts: boosts.clone(),
+                        home: Some(self.room),
+                        job: None,
+                        lifecycle: None,
+                        next_job: None,
+                        role: Some(role),
+                        extra: Map::new(),
+                    },
+                    // Every wanted role gets one creep before any gets a second
+                    priority: if creeps == 0 {
+                        priority
+                    } else {
+                        priority + Role::ALL.len()
+                    } as u32,
+                    requester: DEMAND_REQUESTER.to_string(),
+                    role,
+                });
+                creeps += 1;
+                parts += role.parts(&body);
+            }
+        }
+    }
+
+    /// What the room can keep busy of the role, judged by the room's sources and job
+    /// offers.
+    fn demand(&self, world: &dyn World, role: Role) -> Demand {
+        let sources = world.sources(self.room);
+        // Energy per tick the sources give when they're harvested completely
+        let income = sources
+            .iter()
+            .map(|s| s.energy_capacity / ENERGY_REGEN_TIME)
+            .sum::<u32>();
+        let offers = |f: fn(&Job) -> bool| self.jobs.iter().filter(move |o| f(&o.job));
+
+        match role {
+            Role::Miner => Demand {
+                parts: sources
+                    .iter()
+                    .map(|s| {
+                        let per_tick = s.energy_capacity / ENERGY_REGEN_TIME;
+                        per_tick.div_ceil(HARVEST_POWER)
+                    })
+                    .sum(),
+                // A single miner parks on the container of a source
+                max_creeps: sources
+                    .iter()
+                    .map(|s| match source_container(world, s) {
+                        Some(_) => 1,
+                        None => self.get_free_spots(s.pos, constants::RANGE_HARVEST) as usize,
+                    })
+                    .sum(),
+            },
+            Role::Hauler => {
+                let depots: Vec<_> = world
+                    .structures(self.room)
+                    .into_iter()
+                    .filter(|s| {
+                        s.structure_type == StructureType::Spawn
+                            || s.structure_type == StructureType::Storage
+                    })
+                    .collect();
+                let containers: Vec<_> = sources
+                    .iter()
+                    .filter_map(|s| source_container(world, s).map(|c| (s, c)))
+                    .collect();
+
+                // Enough to carry the income of the containers both ways to the nearest depot
+                let carried = containers
+                    .iter()
+                    .filter_map(|(s, c)| {
+                        let range = depots.iter().map(|d| c.pos.get_range_to(&d.pos)).min()?;
+                        Some(s.energy_capacity / ENERGY_REGEN_TIME * 2 * range)
+                    })
+                    .sum::<u32>();
+                Demand {
+                    parts: carried.div_ceil(CARRY_CAPACITY),
+                    max_creeps: containers.len(),
+                }
+            }
+            Role::Upgrader => match offers(|j| matches!(j, Job::Upgrade(_))).next() {
+                Some(offer) => {
+                    // Half the income, the rest goes to spawning and building
+                    let mut parts = (income / 2).max(1);
+                    if let Job::Upgrade(c) = &offer.job {
+                        if c.level == 8 {
+                            parts = parts.min(CONTROLLER_MAX_UPGRADE_PER_TICK);
+                        }
+                    }
+                    Demand {
+                        parts,
+                        max_creeps: offer.available_places as usize,
+                    }
+                }
+                None => Demand::default(),
+            },
+            Role::Builder => {
+                let sites = offers(|j| matches!(j, Job::Build(_)));
+                let backlog = sites
+                    .clone()
+                    .map(|o| match &o.job {
+                        Job::Build(s) => s.progress_total - s.progress,
+                        _ => 0,
+                    })
+                    .sum::<u32>();
+                // Done within half a creep's life, as fast as the income allows
+                let per_part = BUILD_POWER * CREEP_LIFE_TIME / 2;
+                Demand {
+                    parts: backlog
+                        .div_ceil(per_part)
+                        .min((income / BUILD_POWER).max(1)),
+                    max_creeps: sites.map(|o| o.available_places as usize).sum(),
+                }
+            }
+            Role::Defender => {
+                let hostiles = offers(|j| matches!(j, Job::Attack(_)));
+                let hits = hostiles
+                    .clone()
+                    .map(|o| match &o.job {
+                        Job::Attack(c) => c.hits,
+                        _ => 0,
+                    })
+                    .sum::<u32>();
+                // Enough to kill them within a few dozen ticks
+                let per_part = ATTACK_POWER * 50;
+                Demand {
+                    parts: hits.div_ceil(per_part),
+                    max_creeps: hostiles.count().min(3),
+                }
+            }
+            Role::Healer => {
+                if offers(|j| matches!(j, Job::Attack(_))).next().is_none() {
+                    return Demand::default();
+                }
+                let missing = offers(|j| matches!(j, Job::Heal(_)))
+                    .map(|o| match &o.job {
+                        Job::Heal(c) => c.hits_max - c.hits,
+                        _ => 0,
+                    })
+                    .sum::<u32>();
+                let per_part = HEAL_POWER * 50;
+                Demand {
+                    parts: missing.div_ceil(per_part).max(1),
+                    max_creeps: 1,
+                }
+            }
+            Role::MineralMiner => {
+                let minerals = offers(|j| matches!(j, Job::Extract(..))).count();
+                Demand {
+                    parts: minerals as u32,
+                    max_creeps: minerals,
+                }
+            }
+            // Nothing sends creeps out of their rooms yet
+            Role::Claimer | Role::Scout => Demand::default(),
+        }
     }
 
     fn get_free_spots(&self, pos: Position, range: u32) -> u32 {
-        let c = self
-            .room
-            .look_at_area(
-                (pos.y() - range).limit_min(0),
-                (pos.x() - range).limit_min(0),
-                (pos.y() + range).limit_max(constants::ROOM_Y - 1),
-                (pos.x() + range).limit_max(constants::ROOM_X - 1),
-            )
-            .into_iter()
-            .filter(|res| match res.look_result {
-                LookResult::Terrain(t) => t != Terrain::Wall,
-                _ => false,
-            })
-            .count();
+        let mut c = 0;
+
+        for y in pos.y().saturating_sub(range)..=(pos.y() + range).limit_max(constants::ROOM_Y - 1)
+        {
+            for x in
+                pos.x().saturating_sub(range)..=(pos.x() + range).limit_max(constants::ROOM_X - 1)
+            {
+                if self.terrain.get(x, y) != Terrain::Wall {
+                    c += 1;
+                }
+            }
+        }
 
         debug!("{} free spots for job", c);
 
-        c.try_into().unwrap()
+        c
     }
 
-    fn scan_attack_jobs(&mut self) {
+    fn scan_attack_jobs(&mut self, world: &dyn World) {
         self.jobs.append(
-            &mut self
-                .room
-                .find(screeps::constants::find::HOSTILE_CREEPS)
+            &mut world
+                .hostile_creeps(self.room)
                 .into_iter()
                 .map(|c| JobOffer::new(Job::Attack(c), 5))
                 .collect(),
         )
     }
 
-    fn scan_build_jobs(&mut self) {
+    fn scan_build_jobs(&mut self, world: &dyn World) {
         self.jobs.append(
-            &mut self
-                .room
-                .find(screeps::constants::find::CONSTRUCTION_SITES)
+            &mut world
+                .construction_sites(self.room)
                 .into_iter()
                 .map(|c| {
-                    let spots = self.get_free_spots(c.pos(), constants::RANGE_BUILD);
+                    let spots = self.get_free_spots(c.pos, constants::RANGE_BUILD);
                     JobOffer::new(Job::Build(c), spots)
                 })
                 .collect(),
         )
     }
 
-    fn scan_harvest_jobs(&mut self) {
+    /// Offers mining the minerals that have an extractor and a container, until they're
+    /// depleted and regenerate.
+    fn scan_extract_jobs(&mut self, world: &dyn World) {
+        let structures = world.structures(self.room);
+        self.jobs.extend(
+            world
+                .minerals(self.room)
+                .into_iter()
+                .filter(|m| m.amount != 0)
+                .filter(|m| {
+                    structures.iter().any(|s| {
+                        s.my && s.pos == m.pos && s.structure_type == StructureType::Extractor
+                    })
+                })
+                .filter_map(|m| {
+                    let container = mineral_container(world, &m)?;
+                    Some(JobOffer::new(Job::Extract(m, container), 1))
+                }),
+        )
+    }
+
+    fn scan_harvest_jobs(&mut self, world: &dyn World) {
         self.jobs.append(
-            &mut self
-                .room
-                .find(screeps::constants::find::SOURCES)
+            &mut world
+                .sources(self.room)
                 .into_iter()
                 .filter_map(|c| {
-                    if c.energy() != 0 {
-                        let spots = self.get_free_spots(c.pos(), constants::RANGE_HARVEST);
+                    if let Some(container) = source_container(world, &c) {
+                        // Only the static miner harvests here, everyone else withdraws
+                        Some(JobOffer::new(Job::Mine(c, container), 1))
+                    } else if c.energy != 0 {
+                        let spots = self.get_free_spots(c.pos, constants::RANGE_HARVEST);
                         Some(JobOffer::new(Job::Harvest(c), spots))
                     } else {
                         None
@@ -178,14 +648,13 @@ impl Regulator {
         )
     }
 
-    fn scan_heal_jobs(&mut self) {
+    fn scan_heal_jobs(&mut self, world: &dyn World) {
         self.jobs.append(
-            &mut self
-                .room
-                .find(screeps::constants::find::MY_CREEPS)
+            &mut world
+                .my_creeps(self.room)
                 .into_iter()
                 .filter_map(|c| {
-                    if c.hits() == c.hits_max() {
+                    if c.hits == c.hits_max {
                         None
                     } else {
                         Some(JobOffer::new(Job::Heal(c), 1))
@@ -195,23 +664,23 @@ impl Regulator {
         )
     }
 
-    fn scan_maintain_jobs(&mut self) {
+    fn scan_maintain_jobs(&mut self, world: &dyn World) {
         self.jobs.append(
-            &mut self
-                .room
-                .find(screeps::constants::find::STRUCTURES)
+            &mut world
+                .structures(self.room)
                 .into_iter()
                 .filter_map(|s| {
-                    let typ = s.structure_type();
+                    let typ = s.structure_type;
                     if (typ == StructureType::Extension
                         || typ == StructureType::Spawn
                         || typ == StructureType::Tower)
-                        && s.as_has_store()
+                        && s.store
+                            .as_ref()
                             .unwrap()
-                            .store_free_capacity(Some(ResourceType::Energy))
+                            .free_capacity(Some(ResourceType::Energy))
                             != 0
                     {
-                        let spots = self.get_free_spots(s.pos(), constants::RANGE_TRANSFER);
+                        let spots = self.get_free_spots(s.pos, constants::RANGE_TRANSFER);
                         Some(JobOffer::new(Job::Maintain(s), spots))
                     } else {
                         None
@@ -221,32 +690,570 @@ impl Regulator {
         )
     }
 
-    fn scan_repair_jobs(&mut self) {
+    fn scan_repair_jobs(&mut self, world: &dyn World) {
+        let energy_capacity_available = world
+            .room(self.room)
+            .map(|r| r.energy_capacity_available)
+            .unwrap_or(0);
+
         self.jobs.append(
-            &mut self
-                .room
-                .find(find::STRUCTURES)
+            &mut world
+                .structures(self.room)
                 .into_iter()
                 .filter_map(|s| {
-                    if let Some(a) = s.as_attackable() {
-                        let hits = a.hits();
-                        if hits != 0
-                            && hits < self.room.energy_capacity_available()
-                            && hits < a.hits_max()
-                        {
-                            return Some(JobOffer::new(Job::Repair(s), 1));
-                        }
+                    // Defenses get built up to their target, the rest only patched up
+                    let defense = matches!(
+                        s.structure_type,
+                        StructureType::Rampart | StructureType::Wall
+                    );
+                    let damaged = defense || s.hits < energy_capacity_available;
+                    if s.hits != 0 && damaged && s.hits < hits_target(world, &s) {
+                        Some(JobOffer::new(Job::Repair(s), 1))
+                    } else {
+                        None
                     }
-                    None
                 })
                 .collect(),
         )
     }
 
-    fn scan_upgrade_jobs(&mut self) {
-        if let Some(c) = self.room.controller() {
-            let spots = self.get_free_spots(c.pos(), constants::RANGE_UPGRADE_CONTROLLER);
+    /// Offers the energy lying around, a place for every creep it takes to collect it.
+    fn scan_salvage_jobs(&mut self, world: &dyn World) {
+        let energy = |r: &RemainsData| r.store.used_capacity(Some(ResourceType::Energy));
+        let piles = world
+            .dropped_resources(self.room)
+            .into_iter()
+            .filter(|r| r.resource_type == ResourceType::Energy)
+            .map(|r| (salvageable(r.amount, 0), r.pos, Job::Pickup(r)));
+        let tombstones = world.tombstones(self.room).into_iter().map(|t| {
+            (
+                salvageable(energy(&t), t.ticks_to_decay),
+                t.pos,
+                Job::Loot(t),
+            )
+        });
+        let ruins = world.ruins(self.room).into_iter().map(|r| {
+            (
+                salvageable(energy(&r), r.ticks_to_decay),
+                r.pos,
+                Job::Salvage(r),
+            )
+        });
+        let found: Vec<_> = piles
+            .chain(tombstones)
+            .chain(ruins)
+            .filter(|(amount, _, _)| *amount >= constants::SALVAGE_MIN_AMOUNT)
+            .collect();
+
+        for (amount, pos, job) in found {
+            let spots = self.get_free_spots(pos, constants::RANGE_PICKUP);
+            let places = amount.div_ceil(constants::SALVAGE_PER_CREEP).min(spots);
+            self.jobs.push(JobOffer::new(job, places));
+        }
+    }
+
+    fn scan_upgrade_jobs(&mut self, world: &dyn World) {
+        if let Some(c) = world.room(self.room).and_then(|r| r.controller) {
+            let spots = self.get_free_spots(c.pos, constants::RANGE_UPGRADE_CONTROLLER);
             self.jobs.push(JobOffer::new(Job::Upgrade(c), spots));
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{cleanup_memory, load_memory, save_memory};
+    use crate::world::{pos, world, FakeWorld, Intent, Store};
+    use screeps::SPAWN_ENERGY_CAPACITY;
+
+    #[test]
+    fn scan_offers_a_job_per_target() {
+        let world = world();
+        let mut memory = Memory::default();
+        world.add_source(pos(10, 10));
+        world.add_site(StructureType::Extension, pos(30, 30));
+        world.add_structure(StructureType::Extension, pos(26, 25), Some(Store::new(50)));
+
+        let mut regulator = Regulator::new(&world, &mut memory, pos(0, 0).room_name());
+        regulator.scan(&world);
+
+        let types: Vec<_> = regulator.jobs.iter().map(|o| o.job.get_type()).collect();
+        // The spawn is full, so only the extension needs maintaining
+        assert_eq!(types, vec!["build", "harvest", "maintain", "upgrade"]);
+    }
+
+    #[test]
+    fn salvage_offers_follow_amount_and_decay() {
+        let world = world();
+        let mut memory = Memory::default();
+        let energy = |amount| Store::new(amount).with(ResourceType::Energy, amount);
+        world.add_resource(pos(10, 10), ResourceType::Energy, 1000);
+        world.add_resource(pos(20, 10), ResourceType::Energy, 60);
+        world.add_resource(pos(30, 10), ResourceType::Hydrogen, 1000);
+        world.add_tombstone(pos(10, 20), energy(250), 5);
+        world.add_ruin(pos(10, 30), energy(60), 500);
+
+        let mut regulator = Regulator::new(&world, &mut memory, pos(0, 0).room_name());
+        regulator.scan(&world);
+
+        let offers: Vec<_> = regulator
+            .jobs
+            .iter()
+            .filter(|o| o.job.gathers())
+            .map(|o| (o.job.get_type(), o.available_places))
+            .collect();
+        // The small pile decays below the minimum on the way, the ruin doesn't decay
+        assert_eq!(offers, vec![("pickup", 9), ("loot", 3), ("salvage", 1)]);
+        assert_eq!(salvageable(2000, 5), 1960);
+    }
+
+    #[test]
+    fn free_spots_skip_walls() {
+        let world = world();
+        let mut memory = Memory::default();
+        world.add_source(pos(10, 10));
+        {
+            let mut state = world.state_mut();
+            let terrain = &mut state.rooms.get_mut(&pos(0, 0).room_name()).unwrap().terrain;
+            for x in 9..=11 {
+                terrain.set(x, 9, Terrain::Wall);
+            }
+        }
+
+        let mut regulator = Regulator::new(&world, &mut memory, pos(0, 0).room_name());
+        regulator.scan(&world);
+
+        let harvest = regulator
+            .jobs
+            .iter()
+            .find(|o| matches!(o.job, Job::Harvest(_)))
+            .unwrap();
+        assert_eq!(harvest.available_places, 6);
+    }
+
+    #[test]
+    fn demand_follows_sources_and_backlog() {
+        let world = world();
+        world.add_source(pos(10, 10));
+        let mut memory = Memory::default();
+        let mut regulator = Regulator::new(&world, &mut memory, pos(0, 0).room_name());
+        regulator.scan(&world);
+
+        // A 3000 energy source regenerates 10 energy a tick, five WORK parts harvest it
+        assert_eq!(regulator.demand(&world, Role::Miner).parts, 5);
+        assert_eq!(regulator.demand(&world, Role::Upgrader).parts, 5);
+        assert_eq!(regulator.demand(&world, Role::Builder).parts, 0);
+        assert_eq!(regulator.demand(&world, Role::Hauler).parts, 0);
+
+        world.add_site(StructureType::Extension, pos(30, 30));
+        regulator.scan(&world);
+        assert_eq!(regulator.demand(&world, Role::Builder).parts, 1);
+    }
+
+    #[test]
+    fn small_bodies_are_made_up_for_in_numbers() {
+        let world = world();
+        world.add_source(pos(10, 10));
+        let mut memory = Memory::default();
+        let mut regulator = Regulator::new(&world, &mut memory, pos(0, 0).room_name());
+        regulator.scan(&world);
+
+        assert!(regulator.spawn_creeps(&world, &mut memory).unwrap());
+
+        // One WORK part per body at 300 energy, four more miners to go
+        assert_eq!(regulator.spawn_queue.count(Role::Miner), 4);
+        assert_eq!(regulator.spawn_queue.parts(Role::Miner), 4);
+    }
+
+    #[test]
+    fn spawn_requests_ask_for_boosts_in_stock() {
+        let world = world();
+        world.add_source(pos(10, 10));
+        let storage = Store::new(100_000).with(ResourceType::CatalyzedUtriumAlkalide, 3000);
+        world.add_structure(StructureType::Storage, pos(30, 30), Some(storage));
+        for at in &[pos(20, 20), pos(21, 20), pos(20, 22)] {
+            world.add_structure(StructureType::Lab, *at, Some(Store::lab()));
+        }
+        let mut memory = Memory::default();
+        let mut regulator = Regulator::new(&world, &mut memory, pos(0, 0).room_name());
+        regulator.scan(&world);
+
+        assert!(regulator.spawn_creeps(&world, &mut memory).unwrap());
+
+        // Miners get theirs, there's nothing in stock for the upgraders
+        let boosts: Vec<_> = regulator.spawn_queue.boosts().collect();
+        assert_eq!(boosts, vec![ResourceType::CatalyzedUtriumAlkalide; 4]);
+    }
+
+    #[test]
+    fn fresh_creeps_get_boosted_before_working() {
+        let world = world();
+        world.add_source(pos(10, 10));
+        world.add_structure(StructureType::Lab, pos(20, 20), Some(Store::lab()));
+        world.add_structure(StructureType::Lab, pos(21, 20), Some(Store::lab()));
+        let store = Store::lab()
+            .with(ResourceType::CatalyzedGhodiumAcid, 30)
+            .with(ResourceType::Energy, 20);
+        let lab = world.add_structure(StructureType::Lab, pos(20, 22), Some(store));
+        world.add_creep("upgrader", pos(21, 23), Store::new(50));
+        let mut memory = Memory::default();
+        memory.creeps.insert(
+            "upgrader".to_string(),
+            CreepMemory {
+                boosts: vec![ResourceType::CatalyzedGhodiumAcid],
+                home: Some(pos(0, 0).room_name()),
+                job: None,
+                lifecycle: None,
+                next_job: None,
+                role: Some(Role::Upgrader),
+                extra: Map::new(),
+            },
+        );
+
+        let mut regulator = Regulator::new(&world, &mut memory, pos(0, 0).room_name());
+        regulator.scan(&world);
+        regulator.distribute_jobs(&world, &mut memory).unwrap();
+
+        assert!(world.state().intents.contains(&Intent::Boost {
+            lab,
+            creep: "upgrader".to_string()
+        }));
+        assert!(memory.creeps["upgrader"].boosts.is_empty());
+        assert!(memory.creeps["upgrader"].job.is_none());
+    }
+
+    #[test]
+    fn ramparts_get_repaired_up_to_their_target() {
+        let world = world();
+        world.state_mut().rooms.values_mut().for_each(|r| {
+            r.controller.as_mut().unwrap().level = 3;
+        });
+        let weak = world.add_structure(StructureType::Rampart, pos(20, 20), None);
+        let strong = world.add_structure(StructureType::Rampart, pos(21, 20), None);
+        world
+            .state_mut()
+            .structures
+            .iter_mut()
+            .find(|s| s.id == strong)
+            .unwrap()
+            .hits = constants::DEFENSE_HITS[3];
+        let mut memory = Memory::default();
+        let mut regulator = Regulator::new(&world, &mut memory, pos(0, 0).room_name());
+        regulator.scan(&world);
+
+        let repairs: Vec<_> = regulator
+            .jobs
+            .iter()
+            .filter_map(|o| match &o.job {
+                Job::Repair(s) => Some(s.id),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(repairs, vec![weak]);
+    }
+
+    #[test]
+    fn extractors_offer_mineral_mining() {
+        let world = world();
+        world.add_mineral(pos(10, 10), ResourceType::Hydrogen);
+        world.add_structure(StructureType::Extractor, pos(10, 10), None);
+        world.add_structure(
+            StructureType::Container,
+            pos(11, 11),
+            Some(Store::new(2000)),
+        );
+        let mut memory = Memory::default();
+        let mut regulator = Regulator::new(&world, &mut memory, pos(0, 0).room_name());
+        regulator.scan(&world);
+
+        let offers: Vec<_> = regulator
+            .jobs
+            .iter()
+            .map(|o| (o.job.get_type(), o.available_places))
+            .filter(|(t, _)| *t == "extract")
+            .collect();
+        assert_eq!(offers, vec![("extract", 1)]);
+        assert_eq!(regulator.demand(&world, Role::MineralMiner).max_creeps, 1);
+    }
+
+    #[test]
+    fn containers_turn_harvesting_into_static_mining() {
+        let world = world();
+        world.add_source(pos(10, 10));
+        world.add_structure(
+            StructureType::Container,
+            pos(11, 11),
+            Some(Store::new(2000).with(ResourceType::Energy, 500)),
+        );
+        let mut memory = Memory::default();
+        let mut regulator = Regulator::new(&world, &mut memory, pos(0, 0).room_name());
+        regulator.scan(&world);
+
+        let offers: Vec<_> = regulator
+            .jobs
+            .iter()
+            .map(|o| (o.job.get_type(), o.available_places))
+            .filter(|(t, _)| *t != "upgrade")
+            .collect();
+        assert_eq!(offers, vec![("mine", 1)]);
+        assert_eq!(regulator.demand(&world, Role::Miner).max_creeps, 1);
+        // 10 energy a tick over 14 tiles and back
+        assert_eq!(regulator.demand(&world, Role::Hauler).parts, 6);
+    }
+
+    #[test]
+    fn towers_attack_hostiles() {
+        let world = world();
+        let mut memory = Memory::default();
+        let tower = world.add_structure(
+            StructureType::Tower,
+            pos(24, 24),
+            Some(Store::new(1000).with(ResourceType::Energy, 1000)),
+        );
+        let hostile = world.add_hostile(pos(20, 20));
+
+        let mut regulator = Regulator::new(&world, &mut memory, pos(0, 0).room_name());
+        regulator.scan(&world);
+        regulator.distribute_jobs(&world, &mut memory).unwrap();
+
+        assert_eq!(
+            world.state().intents,
+            vec![Intent::TowerAttack {
+                tower,
+                target: hostile
+            }]
+        );
+    }
+
+    fn acting_creeps(world: &FakeWorld) -> Vec<String> {
+        world
+            .state()
+            .intents
+            .iter()
+            .filter_map(|i| match i {
+                Intent::Harvest { creep, .. } | Intent::Move { creep, .. } => Some(creep.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn two_rooms() -> (FakeWorld, RoomName, RoomName) {
+        let world = world();
+        let other = RoomName::new("W2N1").unwrap();
+        world.add_room(other);
+        world.add_source(pos(10, 10));
+        world.add_source(Position::new(10, 10, other));
+        world.add_creep("local", pos(20, 20), Store::new(50));
+        world.add_creep("remote", Position::new(20, 20, other), Store::new(50));
+        (world, pos(0, 0).room_name(), other)
+    }
+
+    #[test]
+    fn undersized_creeps_get_recycled_one_at_a_time() {
+        let world = world();
+        world.add_source(pos(10, 10));
+        world.add_structure(
+            StructureType::Extension,
+            pos(24, 24),
+            Some(Store::new(100).with(ResourceType::Energy, 100)),
+        );
+        let mut memory = Memory::default();
+        for (name, pos) in &[("old", pos(26, 26)), ("older", pos(24, 26))] {
+            world.add_creep(name, *pos, Store::new(50));
+            memory.creeps.insert(
+                name.to_string(),
+                CreepMemory {
+                    boosts: Vec::new(),
+                    home: Some(pos.room_name()),
+                    job: None,
+                    lifecycle: None,
+                    next_job: None,
+                    role: Some(Role::Upgrader),
+                    extra: Map::new(),
+                },
+            );
+        }
+
+        let mut regulator = Regulator::new(&world, &mut memory, pos(0, 0).room_name());
+        regulator.scan(&world);
+        regulator.distribute_jobs(&world, &mut memory).unwrap();
+
+        assert_eq!(
+            world.state().intents[0],
+            Intent::Recycle {
+                spawn: "Spawn1".to_string(),
+                creep: "old".to_string()
+            }
+        );
+        assert_eq!(memory.creeps["old"].lifecycle, Some(Lifecycle::Recycle));
+        assert_eq!(memory.creeps["older"].lifecycle, None);
+        assert!(memory.creeps["older"].job.is_some());
+
+        // Still one at a time while the first is on its way
+        world.state_mut().intents.clear();
+        regulator.distribute_jobs(&world, &mut memory).unwrap();
+
+        assert_eq!(memory.creeps["older"].lifecycle, None);
+        assert!(!world.state().intents.iter().any(|i| matches!(
+            i,
+            Intent::Recycle { creep, .. } if creep == "older"
+        )));
+    }
+
+    #[test]
+    fn dead_creeps_stay_forgotten() {
+        let world = world();
+        world.add_source(pos(10, 10));
+        world.add_creep("dead", pos(20, 20), Store::new(50));
+        let mut memory = Memory::default();
+        memory.creeps.entry("dead".to_string()).or_default().home = Some(pos(0, 0).room_name());
+        let mut regulator = Regulator::new(&world, &mut memory, pos(0, 0).room_name());
+        regulator.scan(&world);
+        regulator.distribute_jobs(&world, &mut memory).unwrap();
+
+        world.state_mut().creeps.clear();
+        cleanup_memory(&world, &mut memory);
+        regulator.distribute_jobs(&world, &mut memory).unwrap();
+
+        assert!(regulator.creep_names().is_empty());
+        assert!(!memory.creeps.contains_key("dead"));
+    }
+
+    #[test]
+    fn dead_haulers_dont_hold_on_to_deliveries() {
+        let world = world();
+        let spawn = world.spawns()[0].id;
+        world
+            .state_mut()
+            .structures
+            .iter_mut()
+            .filter(|s| s.id == spawn)
+            .for_each(|s| s.store = Some(Store::new(SPAWN_ENERGY_CAPACITY)));
+        let storage = Store::new(10_000).with(ResourceType::Energy, 10_000);
+        world.add_structure(StructureType::Storage, pos(30, 30), Some(storage));
+        let full =
+            Store::new(SPAWN_ENERGY_CAPACITY).with(ResourceType::Energy, SPAWN_ENERGY_CAPACITY);
+        world.add_creep("dead", pos(24, 24), full);
+        world.add_creep("live", pos(30, 31), Store::new(50));
+        let mut memory = Memory::default();
+        for name in &["dead", "live"] {
+            let creep_memory = memory.creeps.entry(name.to_string()).or_default();
+            creep_memory.home = Some(pos(0, 0).room_name());
+            creep_memory.role = Some(Role::Hauler);
+        }
+        let maintain = Job::Maintain(world.get_structure(spawn).unwrap()).to_memory();
+        memory.creeps.get_mut("dead").unwrap().job = Some(maintain.clone());
+        let mut regulator = Regulator::new(&world, &mut memory, pos(0, 0).room_name());
+        regulator.scan(&world);
+
+        world.state_mut().creeps.remove("dead");
+        regulator.distribute_jobs(&world, &mut memory).unwrap();
+
+        assert_eq!(memory.creeps["live"].job, Some(maintain));
+    }
+
+    #[test]
+    fn only_directs_own_creeps() {
+        let (world, room, _) = two_rooms();
+        let mut memory = Memory::default();
+
+        let mut regulator = Regulator::new(&world, &mut memory, room);
+        regulator.scan(&world);
+        regulator.distribute_jobs(&world, &mut memory).unwrap();
+
+        assert_eq!(regulator.creep_names(), vec!["local".to_string()]);
+        assert_eq!(acting_creeps(&world), vec!["local".to_string()]);
+    }
+
+    #[test]
+    fn homeless_creeps_settle_in_their_room() {
+        let (world, room, _) = two_rooms();
+        let mut memory = Memory::default();
+
+        let regulator = Regulator::new(&world, &mut memory, room);
+
+        assert_eq!(regulator.creep_names(), vec!["local".to_string()]);
+        assert_eq!(memory.creeps["local"].home, Some(room));
+        assert_eq!(memory.creeps["remote"].home, None);
+    }
+
+    #[test]
+    fn handed_over_creeps_follow_their_new_regulator() {
+        let (world, room, other) = two_rooms();
+        let mut memory = Memory::default();
+        let mut from = Regulator::new(&world, &mut memory, room);
+        let mut to = Regulator::new(&world, &mut memory, other);
+
+        let creep = from.release_creep("local").unwrap();
+        to.adopt_creep(&mut memory, creep);
+        assert_eq!(memory.creeps["local"].home, Some(other));
+
+        for r in [&mut from, &mut to].iter_mut() {
+            r.scan(&world);
+            r.distribute_jobs(&world, &mut memory).unwrap();
+        }
+
+        assert!(from.creep_names().is_empty());
+        let mut names = to.creep_names();
+        names.sort();
+        assert_eq!(names, vec!["local".to_string(), "remote".to_string()]);
+        // Each creep acts exactly once per tick
+        let mut acting = acting_creeps(&world);
+        acting.sort();
+        assert_eq!(acting, vec!["local".to_string(), "remote".to_string()]);
+    }
+
+    #[test]
+    fn jobs_survive_a_global_reset() {
+        let (world, room, _) = two_rooms();
+        let mut memory = Memory::default();
+        let mut regulator = Regulator::new(&world, &mut memory, room);
+        regulator.scan(&world);
+        regulator.distribute_jobs(&world, &mut memory).unwrap();
+        world.state_mut().intents.clear();
+        save_memory(&world, &memory);
+        let mut memory = load_memory(&world);
+
+        // A fresh regulator hasn't scanned yet, so it has no jobs to hand out
+        let mut regulator = Regulator::new(&world, &mut memory, room);
+        regulator.distribute_jobs(&world, &mut memory).unwrap();
+
+        assert_eq!(
+            memory.creeps["local"].job.as_ref().map(|j| j.kind.as_str()),
+            Some("harvest")
+        );
+        assert_eq!(acting_creeps(&world), vec!["local".to_string()]);
+    }
+
+    #[test]
+    fn tower_jobs_survive_a_global_reset() {
+        let world = world();
+        let mut memory = Memory::default();
+        let tower = world.add_structure(
+            StructureType::Tower,
+            pos(24, 24),
+            Some(Store::new(1000).with(ResourceType::Energy, 1000)),
+        );
+        let hostile = world.add_hostile(pos(20, 20));
+        let room = pos(0, 0).room_name();
+
+        let mut regulator = Regulator::new(&world, &mut memory, room);
+        regulator.scan(&world);
+        regulator.distribute_jobs(&world, &mut memory).unwrap();
+        world.state_mut().intents.clear();
+        save_memory(&world, &memory);
+        let mut memory = load_memory(&world);
+
+        let mut regulator = Regulator::new(&world, &mut memory, room);
+        regulator.distribute_jobs(&world, &mut memory).unwrap();
+
+        assert_eq!(
+            world.state().intents,
+            vec![Intent::TowerAttack {
+                tower,
+                target: hostile
+            }]
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use super::Job;
+use screeps::{Part, ResourceType, MAX_CREEP_SIZE};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Miner,
+    Hauler,
+    Upgrader,
+    Builder,
+    Defender,
+    Healer,
+    Claimer,
+    Scout,
+    MineralMiner,
+}
+
+pub struct BodyTemplate {
+    /// The smallest useful body
+    pub minimum: &'static [Part],
+    /// Added on top of the minimum as often as energy and `max_parts` allow
+    pub pattern: &'static [Part],
+    pub max_parts: usize,
+}
+
+impl Role {
+    /// In order of importance, the first roles get spawned first when they're wanted.
+    pub const ALL: [Role; 9] = [
+        Role::Miner,
+        Role::Defender,
+        Role::Healer,
+        Role::Hauler,
+        Role::Upgrader,
+        Role::Builder,
+        Role::MineralMiner,
+        Role::Claimer,
+        Role::Scout,
+    ];
+
+    /// What the role's creeps get boosted with when there's enough of it.
+    pub fn boosts(self) -> &'static [ResourceType] {
+        use ResourceType::*;
+
+        match self {
+            Role::Miner => &[CatalyzedUtriumAlkalide],
+            Role::Upgrader => &[CatalyzedGhodiumAcid],
+            Role::Defender => &[CatalyzedUtriumAcid, CatalyzedGhodiumAlkalide],
+            Role::Healer => &[CatalyzedLemergiumAlkalide],
+            _ => &[],
+        }
+    }
+
+    /// The part doing the role's work, demand is measured in it.
+    pub fn main_part(self) -> Part {
+        match self {
+            Role::Miner | Role::Upgrader | Role::Builder | Role::MineralMiner => Part::Work,
+            Role::Hauler => Part::Carry,
+            Role::Defender => Part::Attack,
+            Role::Healer => Part::Heal,
+            Role::Claimer => Part::Claim,
+            Role::Scout => Part::Move,
+        }
+    }
+
+    pub fn parts(self, body: &[Part]) -> u32 {
+        let main = self.main_part();
+        body.iter().filter(|p| **p == main).count() as u32
+    }
+
+    pub fn template(self) -> BodyTemplate {
+        use Part::*;
+
+        match self {
+            Role::Miner => BodyTemplate {
+                minimum: &[Work, Carry, Move],
+                pattern: &[Work, Work, Move],
+                max_parts: 9,
+            },
+            Role::Hauler => BodyTemplate {
+                minimum: &[Carry, Carry, Move],
+                pattern: &[Carry, Carry, Move],
+                max_parts: 30,
+            },
+            Role::Upgrader | Role::Builder => BodyTemplate {
+                minimum: &[Work, Carry, Move],
+                pattern: &[Work, Carry, Move],
+                max_parts: 24,
+            },
+            Role::Defender => BodyTemplate {
+                minimum: &[Attack, Move],
+                pattern: &[Tough, Attack, Move, Move],
+                max_parts: 20,
+            },
+            Role::Healer => BodyTemplate {
+                minimum: &[Heal, Move],
+                pattern: &[Heal, Move],
+                max_parts: 20,
+            },
+            Role::Claimer => BodyTemplate {
+                minimum: &[Claim, Move],
+                pattern: &[Claim, Move],
+                max_parts: 10,
+            },
+            Role::Scout => BodyTemplate {
+                minimum: &[Move],
+                pattern: &[],
+                max_parts: 1,
+            },
+            // Its output drops into the container it stands on
+            Role::MineralMiner => BodyTemplate {
+                minimum: &[Work, Work, Move],
+                pattern: &[Work, Work, Move],
+                max_parts: 30,
+            },
+        }
+    }
+
+    /// The biggest body the energy pays for, `None` if not even the minimum fits.
+    pub fn body(self, energy: u32) -> Option<Vec<Part>> {
+        let template = self.template();
+        let max_parts = template.max_parts.min(MAX_CREEP_SIZE as usize);
+        let cost = |parts: &[Part]| parts.iter().map(|p| p.cost()).sum::<u32>();
+
+        let mut body = template.minimum.to_vec();
+        if cost(&body) > energy {
+            return None;
+        }
+
+        if !template.pattern.is_empty() {
+            while body.len() + template.pattern.len() <= max_parts
+                && cost(&body) + cost(template.pattern) <= energy
+            {
+                body.extend_from_slice(template.pattern);
+            }
+        }
+
+        // Armor goes first, so it soaks up damage before anything else
+        body.sort_by_key(|p| *p != Part::Tough);
+        Some(body)
+    }
+
+    pub fn accepts(self, job: &Job) -> bool {
+        match self {
+            // Until there are containers, miners bring their energy home themselves
+            Role::Miner => matches!(job, Job::Harvest(_) | Job::Maintain(_) | Job::Mine(..)),
+            Role::Hauler => matches!(
+                job,
+                Job::Deliver(..)
+                    | Job::Loot(_)
+                    | Job::Maintain(_)
+                    | Job::Pickup(_)
+                    | Job::Salvage(_)
+                    | Job::Withdraw(..)
+            ),
+            Role::Upgrader => job.gathers() || matches!(job, Job::Upgrade(_)),
+            Role::Builder => {
+                job.gathers() || matches!(job, Job::Build(_) | Job::Repair(_) | Job::Maintain(_))
+            }
+            Role::Defender => matches!(job, Job::Attack(_)),
+            Role::Healer => matches!(job, Job::Heal(_)),
+            Role::MineralMiner => matches!(job, Job::Extract(..)),
+            Role::Claimer | Role::Scout => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn body_grows_with_energy() {
+        assert_eq!(Role::Upgrader.body(199), None);
+        assert_eq!(
+            Role::Upgrader.body(300),
+            Some(vec![Part::Work, Part::Carry, Part::Move])
+        );
+        assert_eq!(Role::Upgrader.body(400).unwrap().len(), 6);
+    }
+
+    #[test]
+    fn body_stays_within_max_parts() {
+        assert_eq!(Role::Miner.body(10_000).unwrap().len(), 9);
+        assert_eq!(Role::Scout.body(10_000), Some(vec![Part::Move]));
+    }
+
+    #[test]
+    fn tough_parts_go_first() {
+        let body = Role::Defender.body(1000).unwrap();
+
+        assert_eq!(body[0], Part::Tough);
+        assert_eq!(body.iter().filter(|p| **p == Part::Tough).count(), 4);
+    }
+}
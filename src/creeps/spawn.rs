@@ -1,100 +1,418 @@
-use crate::core::constants;
+use super::{Lifecycle, Role};
+use crate::core::{CreepMemory, Memory};
+use crate::world::{CreepData, SpawnData, World};
 use log::*;
-use screeps::{prelude::*, Part, ReturnCode};
+use screeps::{Part, Position, ResourceType, ReturnCode, RoomName, CREEP_SPAWN_TIME};
+use std::collections::HashMap;
 
-struct BodyParts {
-    mode: u8,
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpawnRequest {
+    pub body: Vec<Part>,
+    /// Spawn with whatever energy there is instead of waiting for the full body
+    pub emergency: bool,
+    pub memory: CreepMemory,
+    /// Lower goes first
+    pub priority: u32,
+    /// Who asked for the creep, so it can withdraw its requests again
+    pub requester: String,
+    pub role: Role,
 }
 
-impl BodyParts {
-    pub fn new() -> Self {
-        Self { mode: 0 }
+impl SpawnRequest {
+    pub fn cost(&self) -> u32 {
+        self.body.iter().map(|p| p.cost()).sum()
     }
 }
 
-impl Iterator for BodyParts {
-    type Item = Part;
+/// Spawn requests ordered by priority, first come first served within a priority.
+#[derive(Default)]
+pub struct SpawnQueue {
+    requests: Vec<SpawnRequest>,
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        match self.mode {
-            3 => {
-                self.mode += 1;
-                Some(Part::Carry)
-            }
-            4 => {
-                self.mode = 0;
-                Some(Part::Move)
-            }
-            _ => {
-                self.mode += 1;
-                Some(Part::Work)
-            }
+impl SpawnQueue {
+    pub fn count(&self, role: Role) -> usize {
+        self.requests.iter().filter(|r| r.role == role).count()
+    }
+
+    /// Main parts of the queued creeps of the role
+    pub fn parts(&self, role: Role) -> u32 {
+        self.requests
+            .iter()
+            .filter(|r| r.role == role)
+            .map(|r| role.parts(&r.body))
+            .sum()
+    }
+
+    /// Compounds the queued creeps get boosted with
+    pub fn boosts(&self) -> impl Iterator<Item = ResourceType> + '_ {
+        self.requests
+            .iter()
+            .flat_map(|r| r.memory.boosts.iter().copied())
+    }
+
+    pub fn peek(&self) -> Option<&SpawnRequest> {
+        self.requests.first()
+    }
+
+    pub fn pop(&mut self) -> Option<SpawnRequest> {
+        if self.requests.is_empty() {
+            None
+        } else {
+            Some(self.requests.remove(0))
         }
     }
-}
 
-pub fn replenish_creeps() -> Result<bool, ReturnCode> {
-    debug!("running spawns");
+    pub fn push(&mut self, request: SpawnRequest) {
+        let i = self
+            .requests
+            .iter()
+            .position(|r| r.priority > request.priority)
+            .unwrap_or(self.requests.len());
+        self.requests.insert(i, request);
+    }
 
-    if screeps::game::creeps::keys().len() >= constants::MAX_CREEPS {
-        debug!("Enough creeps spawned, not spawning more");
-        return Ok(false);
+    pub fn withdraw(&mut self, requester: &str) {
+        self.requests.retain(|r| r.requester != requester);
     }
+}
 
-    for spawn in screeps::game::spawns::values() {
-        debug!("running spawn {}", spawn.name());
+/// How much of a role a room can keep busy, in parts of the role's main part.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Demand {
+    pub parts: u32,
+    /// Usually how many creeps fit around the work
+    pub max_creeps: usize,
+}
+
+/// Ticks from queueing a replacement until it's where the creep at `pos` is now.
+pub fn replacement_time(body: &[Part], spawn: Position, pos: Position) -> u32 {
+    body.len() as u32 * CREEP_SPAWN_TIME + spawn.get_range_to(&pos)
+}
 
-        let room = spawn.room().expect("room isn't visible");
+/// Bodies of the living and spawning creeps of the room per role. Creeps still in the
+/// spawn are assumed to have the biggest body `capacity` pays for. Creeps dying before
+/// a replacement could take over don't count, so the replacement gets spawned in time,
+/// neither do creeps on their way to be recycled. Renewing creeps stay.
+pub fn population(
+    world: &dyn World,
+    memory: &Memory,
+    room: RoomName,
+    capacity: u32,
+) -> HashMap<Role, Vec<Vec<Part>>> {
+    let spawns = world.spawns();
+    let spawns: Vec<_> = spawns
+        .iter()
+        .filter(|s| s.pos.room_name() == room)
+        .collect();
+    let replaced = |c: &CreepData| {
+        let lifecycle = memory.creeps.get(&c.name).and_then(|m| m.lifecycle);
+        let ttl = match (lifecycle, c.ticks_to_live) {
+            (Some(Lifecycle::Recycle), _) => return true,
+            (Some(Lifecycle::Renew), _) | (_, None) => return false,
+            (None, Some(ttl)) => ttl,
+        };
+        spawns
+            .iter()
+            .map(|s| replacement_time(&c.body, s.pos, c.pos))
+            .min()
+            .is_some_and(|t| ttl <= t)
+    };
 
-        debug!(
-            "room available energy: {}, capacity: {}",
-            room.energy_available(),
-            room.energy_capacity_available()
+    let bodies = world
+        .creeps()
+        .into_iter()
+        .filter(|c| !replaced(c))
+        .map(|c| (c.name, Some(c.body)))
+        .chain(
+            spawns
+                .iter()
+                .filter_map(|s| s.spawning.clone())
+                .map(|name| (name, None)),
         );
 
-        if room.energy_available() < room.energy_capacity_available() {
-            debug!("Waiting for spawn to be full to spawn big mob");
-            return Ok(false);
+    let mut population: HashMap<Role, Vec<Vec<Part>>> = HashMap::new();
+    for (name, body) in bodies {
+        if let Some(CreepMemory {
+            home: Some(home),
+            role: Some(role),
+            ..
+        }) = memory.creeps.get(&name)
+        {
+            if *home == room {
+                let body = body.or_else(|| role.body(capacity)).unwrap_or_default();
+                population.entry(*role).or_default().push(body);
+            }
+        }
+    }
+
+    population
+}
+
+/// Spawns the body under a fresh name and records the creep's memory.
+pub fn spawn_creep(
+    world: &dyn World,
+    memory: &mut Memory,
+    spawn: &SpawnData,
+    body: &[Part],
+    creep_memory: CreepMemory,
+) -> ReturnCode {
+    // create a unique name, spawn.
+    let name_base = world.time();
+    let mut additional = 0;
+    let (name, res) = loop {
+        let name = format!("{}-{}", name_base, additional);
+        let res = world.spawn_creep(&spawn.name, body, &name);
+
+        if res == ReturnCode::NameExists {
+            additional += 1;
+        } else {
+            break (name, res);
         }
+    };
+
+    if res == ReturnCode::Ok {
+        info!(
+            "Spawning {:?} {} at {}",
+            creep_memory.role, name, spawn.name
+        );
+        memory.creeps.insert(name, creep_memory);
+    }
+
+    res
+}
 
-        let mut body = vec![Part::Move, Part::Move, Part::Carry, Part::Work];
-        let energy = room.energy_available();
-        let mut sum = body.iter().map(|p| p.cost()).sum();
-        let mut iter = BodyParts::new();
-        let mut next = iter.next().unwrap();
-        let mut num_bodyparts = 0;
-
-        while energy >= (sum + next.cost()) && num_bodyparts <= constants::MAX_CREEPS_BODY_PARTS {
-            body.push(next);
-            sum = body.iter().map(|p| p.cost()).sum();
-            next = iter.next().unwrap();
-            num_bodyparts += 1;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::creeps::Regulator;
+    use crate::world::{empty_room, pos, world, FakeWorld, Intent, Store};
+    use screeps::{ResourceType, StructureType};
+    use serde_json::Map;
+
+    fn request(role: Role, priority: u32) -> SpawnRequest {
+        SpawnRequest {
+            body: role.body(300).unwrap(),
+            emergency: false,
+            memory: CreepMemory::default(),
+            priority,
+            requester: format!("{:?}", role),
+            role,
         }
+    }
+
+    #[test]
+    fn queue_orders_by_priority_then_age() {
+        let mut queue = SpawnQueue::default();
+        queue.push(request(Role::Upgrader, 4));
+        queue.push(request(Role::Builder, 5));
+        queue.push(request(Role::Hauler, 4));
+        queue.push(request(Role::Miner, 0));
+
+        let roles: Vec<_> = std::iter::from_fn(|| queue.pop()).map(|r| r.role).collect();
+
+        assert_eq!(
+            roles,
+            vec![Role::Miner, Role::Upgrader, Role::Hauler, Role::Builder]
+        );
+    }
+
+    #[test]
+    fn requesters_can_withdraw() {
+        let mut queue = SpawnQueue::default();
+        queue.push(request(Role::Upgrader, 4));
+        queue.push(request(Role::Builder, 5));
 
-        if energy >= sum {
-            // create a unique name, spawn.
-            let name_base = screeps::game::time();
-            let mut additional = 0;
-            let res = loop {
-                let name = format!("{}-{}", name_base, additional);
-                let res = spawn.spawn_creep(&body, &name);
-
-                if res == ReturnCode::NameExists {
-                    additional += 1;
-                } else {
-                    break res;
+        queue.withdraw("Upgrader");
+
+        assert_eq!(queue.count(Role::Upgrader), 0);
+        assert_eq!(queue.peek().map(|r| r.role), Some(Role::Builder));
+    }
+
+    fn add_creep(world: &FakeWorld, memory: &mut Memory, name: &str, role: Role) {
+        world.add_creep(name, pos(10, 20), Store::new(50));
+        world.state_mut().creeps.get_mut(name).unwrap().body = role.body(10_000).unwrap();
+        memory.creeps.insert(
+            name.to_string(),
+            CreepMemory {
+                boosts: Vec::new(),
+                home: Some(pos(0, 0).room_name()),
+                job: None,
+                lifecycle: None,
+                next_job: None,
+                role: Some(role),
+                extra: Map::new(),
+            },
+        );
+    }
+
+    fn spawn(world: &FakeWorld, memory: &mut Memory) -> Result<bool, String> {
+        let mut regulator = Regulator::new(world, memory, pos(0, 0).room_name());
+        regulator.scan(world);
+        regulator
+            .spawn_creeps(world, memory)
+            .map_err(|e| e.to_string())
+    }
+
+    fn spawned(world: &FakeWorld, memory: &Memory) -> Vec<(String, Role)> {
+        world
+            .state()
+            .intents
+            .iter()
+            .filter_map(|i| match i {
+                Intent::Spawn { spawn, name, .. } => {
+                    Some((spawn.clone(), memory.creeps[name].role.unwrap()))
                 }
-            };
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn spawns_biggest_body_on_full_energy() {
+        let world = world();
+        world.add_source(pos(10, 10));
+
+        let mut memory = Memory::default();
+        assert_eq!(spawn(&world, &mut memory), Ok(true));
 
-            if res == ReturnCode::Ok {
-                return Ok(true);
-            } else {
-                return Err(res);
+        let intents = &world.state().intents;
+        assert_eq!(intents.len(), 1);
+        match &intents[0] {
+            Intent::Spawn { spawn, body, name } => {
+                assert_eq!(spawn, "Spawn1");
+                assert_eq!(body, &Role::Miner.body(300).unwrap());
+                assert_eq!(memory.creeps[name].home, Some(pos(0, 0).room_name()));
+                assert_eq!(memory.creeps[name].role, Some(Role::Miner));
             }
-        } else {
-            warn!("Not enough energy!");
+            i => panic!("unexpected intent {:?}", i),
         }
     }
 
-    Ok(false)
+    #[test]
+    fn waits_for_full_energy() {
+        let world = world();
+        world.add_controller(pos(40, 40), 2);
+        world.add_structure(StructureType::Extension, pos(26, 25), Some(Store::new(100)));
+        let mut memory = Memory::default();
+        add_creep(&world, &mut memory, "miner", Role::Miner);
+
+        assert_eq!(spawn(&world, &mut memory), Ok(false));
+        assert!(world.state().intents.is_empty());
+    }
+
+    #[test]
+    fn emergencies_spawn_with_what_is_there() {
+        let world = world();
+        world.add_source(pos(10, 10));
+        world.add_structure(StructureType::Extension, pos(26, 25), Some(Store::new(250)));
+
+        let mut memory = Memory::default();
+        assert_eq!(spawn(&world, &mut memory), Ok(true));
+
+        let intent = world.state().intents[0].clone();
+        match intent {
+            Intent::Spawn { body, .. } => assert_eq!(body, Role::Miner.body(300).unwrap()),
+            i => panic!("unexpected intent {:?}", i),
+        }
+    }
+
+    #[test]
+    fn every_spawn_of_the_room_takes_requests() {
+        let world = empty_room();
+        world.add_spawn("Spawn1", pos(25, 25));
+        world.add_spawn("Spawn2", pos(30, 25));
+        world.add_source(pos(10, 10));
+        world.add_source(pos(40, 10));
+        world.add_structure(
+            StructureType::Extension,
+            pos(26, 25),
+            Some(Store::new(800).with(ResourceType::Energy, 800)),
+        );
+
+        let mut memory = Memory::default();
+        assert_eq!(spawn(&world, &mut memory), Ok(true));
+
+        assert_eq!(
+            spawned(&world, &memory),
+            vec![
+                ("Spawn1".to_string(), Role::Miner),
+                ("Spawn2".to_string(), Role::Miner)
+            ]
+        );
+    }
+
+    #[test]
+    fn stops_once_demand_is_met() {
+        let world = world();
+        world.add_source(pos(10, 10));
+        let mut memory = Memory::default();
+        add_creep(&world, &mut memory, "miner", Role::Miner);
+        add_creep(&world, &mut memory, "upgrader", Role::Upgrader);
+
+        assert_eq!(spawn(&world, &mut memory), Ok(false));
+        assert!(world.state().intents.is_empty());
+    }
+
+    #[test]
+    fn replaces_creeps_before_they_die() {
+        let world = world();
+        world.add_source(pos(10, 10));
+        let mut memory = Memory::default();
+        add_creep(&world, &mut memory, "miner", Role::Miner);
+        add_creep(&world, &mut memory, "upgrader", Role::Upgrader);
+
+        // 9 parts take 27 ticks to spawn, the miner is 15 tiles away from the spawn
+        world
+            .state_mut()
+            .creeps
+            .get_mut("miner")
+            .unwrap()
+            .ticks_to_live = Some(43);
+        assert_eq!(spawn(&world, &mut memory), Ok(false));
+
+        world
+            .state_mut()
+            .creeps
+            .get_mut("miner")
+            .unwrap()
+            .ticks_to_live = Some(42);
+        assert_eq!(spawn(&world, &mut memory), Ok(true));
+        assert_eq!(
+            spawned(&world, &memory),
+            vec![("Spawn1".to_string(), Role::Miner)]
+        );
+    }
+
+    #[test]
+    fn picks_the_most_wanted_role() {
+        let world = world();
+        world.add_source(pos(10, 10));
+        let mut memory = Memory::default();
+        add_creep(&world, &mut memory, "miner", Role::Miner);
+
+        assert_eq!(spawn(&world, &mut memory), Ok(true));
+        assert_eq!(
+            spawned(&world, &memory),
+            vec![("Spawn1".to_string(), Role::Upgrader)]
+        );
+
+        world.state_mut().intents.clear();
+        world.add_hostile(pos(20, 20));
+
+        assert_eq!(spawn(&world, &mut memory), Ok(true));
+        assert_eq!(
+            spawned(&world, &memory),
+            vec![("Spawn1".to_string(), Role::Defender)]
+        );
+    }
+
+    #[test]
+    fn spawns_nothing_without_demand() {
+        let world = empty_room();
+        world.add_spawn("Spawn1", pos(25, 25));
+
+        assert_eq!(spawn(&world, &mut Memory::default()), Ok(false));
+        assert!(world.state().intents.is_empty());
+    }
 }
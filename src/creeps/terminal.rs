@@ -0,0 +1,153 @@
+use super::room_stock;
+use crate::core::{constants, transfer_cost};
+use crate::world::World;
+use log::*;
+use screeps::{ResourceType, ReturnCode, StructureType};
+use std::collections::HashMap;
+
+/// How much of a resource a room keeps for itself
+pub fn stock_target(resource: ResourceType) -> u32 {
+    match resource {
+        ResourceType::Energy => constants::STOCK_ENERGY,
+        _ => constants::STOCK_RESOURCE,
+    }
+}
+
+/// How much of a resource the terminal should hold: a batch of the room's surplus ready
+/// to be sent, and for energy enough on top to pay for sending
+pub fn terminal_target(stock: &HashMap<ResourceType, u32>, resource: ResourceType) -> u32 {
+    let amount = stock.get(&resource).copied().unwrap_or(0);
+    let surplus = amount.saturating_sub(stock_target(resource));
+    let batch = surplus.min(constants::TERMINAL_BATCH);
+    match resource {
+        ResourceType::Energy => batch + constants::TERMINAL_ENERGY,
+        _ => batch,
+    }
+}
+
+/// Sends every terminal that has cooled down's surplus to the room furthest below its
+/// target for it, as long as the terminal has it at hand and the energy to pay for it.
+pub fn balance_terminals(world: &dyn World) {
+    let mut rooms: Vec<_> = world
+        .rooms()
+        .into_iter()
+        .filter_map(|room| {
+            let terminal = world
+                .structures(room)
+                .into_iter()
+                .find(|s| s.my && s.structure_type == StructureType::Terminal)?;
+            Some((terminal, room_stock(world, room)))
+        })
+        .collect();
+
+    for i in 0..rooms.len() {
+        let (terminal, stock) = &rooms[i];
+        if terminal.cooldown > 0 {
+            continue;
+        }
+        let held = |r| {
+            terminal
+                .store
+                .as_ref()
+                .map_or(0, |s| s.used_capacity(Some(r)))
+        };
+
+        let best = stock
+            .iter()
+            .flat_map(|(&resource, &amount)| {
+                let surplus = amount.saturating_sub(stock_target(resource));
+                let at_hand = match resource {
+                    ResourceType::Energy => {
+                        held(resource).saturating_sub(constants::TERMINAL_ENERGY)
+                    }
+                    _ => held(resource),
+                };
+                rooms.iter().enumerate().filter(move |&(j, _)| j != i).map(
+                    move |(j, (_, other))| {
+                        let other = other.get(&resource).copied().unwrap_or(0);
+                        let deficit = stock_target(resource).saturating_sub(other);
+                        (deficit.min(surplus).min(at_hand), resource, j)
+                    },
+                )
+            })
+            .max_by_key(|&(amount, _, _)| amount);
+        let (amount, resource, j) = match best {
+            Some(b) if b.0 >= constants::TERMINAL_MIN_SEND => b,
+            _ => continue,
+        };
+
+        let from = terminal.pos.room_name();
+        let to = rooms[j].0.pos.room_name();
+        let cost = transfer_cost(amount, from, to);
+        if resource != ResourceType::Energy && held(ResourceType::Energy) < cost {
+            debug!(
+                "Terminal in {} is short of energy to send {:?}",
+                from, resource
+            );
+            continue;
+        }
+        match world.terminal_send(terminal.id, resource, amount, to) {
+            ReturnCode::Ok => {
+                info!("Sending {} {:?} from {} to {}", amount, resource, from, to);
+                *rooms[i].1.entry(resource).or_insert(0) -= amount;
+                *rooms[j].1.entry(resource).or_insert(0) += amount;
+            }
+            r => warn!("Terminal in {} couldn't send {:?}: {:?}", from, resource, r),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{FakeWorld, Intent, Store};
+    use screeps::{Position, RawObjectId, RoomName, TERMINAL_CAPACITY};
+
+    fn terminal(world: &FakeWorld, room: &str, resources: &[(ResourceType, u32)]) -> RawObjectId {
+        let pos = Position::new(25, 25, RoomName::new(room).unwrap());
+        world.add_room(pos.room_name());
+        let store = resources
+            .iter()
+            .fold(Store::new(TERMINAL_CAPACITY), |s, &(r, a)| s.with(r, a));
+        world.add_structure(StructureType::Terminal, pos, Some(store))
+    }
+
+    #[test]
+    fn surpluses_go_to_the_room_furthest_short() {
+        use ResourceType::*;
+        let world = FakeWorld::new();
+        let from = terminal(&world, "W1N1", &[(Hydrogen, 8000), (Energy, 10_000)]);
+        terminal(&world, "W2N1", &[(Hydrogen, 4000)]);
+        terminal(&world, "W3N1", &[(Hydrogen, 1000)]);
+
+        balance_terminals(&world);
+
+        assert_eq!(
+            world.state().intents,
+            vec![Intent::TerminalSend {
+                terminal: from,
+                resource: Hydrogen,
+                amount: 3000,
+                destination: RoomName::new("W3N1").unwrap(),
+            }]
+        );
+    }
+
+    #[test]
+    fn terminals_wait_for_cooldown_and_energy() {
+        use ResourceType::*;
+        let world = FakeWorld::new();
+        let cooling = terminal(&world, "W1N1", &[(Hydrogen, 8000), (Energy, 10_000)]);
+        terminal(&world, "W2N1", &[(Oxygen, 8000)]);
+        terminal(&world, "W3N1", &[]);
+        world.state_mut().structures.iter_mut().for_each(|s| {
+            if s.id == cooling {
+                s.cooldown = 5;
+            }
+        });
+
+        balance_terminals(&world);
+
+        assert!(world.state().intents.is_empty());
+    }
+}
@@ -1,7 +1,9 @@
 use super::{Job, JobOffer};
-use crate::constants;
+use crate::core::{constants, JobMemory};
+use crate::planner::hits_target;
+use crate::world::{StructureData, World};
 use log::*;
-use screeps::{prelude::*, Attackable, ResourceType, ReturnCode, StructureTower};
+use screeps::ReturnCode;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -18,50 +20,60 @@ type Result<T> = std::result::Result<T, crate::creeps::tower::Error>;
 
 pub struct Tower {
     current_job: Option<Job>,
-    inner: StructureTower,
+    inner: StructureData,
 }
 
 impl Tower {
-    pub fn execute_job(&self, job: &Job) -> Result<bool> {
+    pub fn execute_job(&self, world: &dyn World, job: &Job) -> Result<bool> {
         // FIXME: Allow Health
         Ok(match job {
-            Job::Attack(_) => self.attack(&job)?,
-            Job::Heal(_) => self.heal(&job)?,
-            Job::Repair(_) => self.repair(&job)?,
+            Job::Attack(_) => self.attack(world, job)?,
+            Job::Heal(_) => self.heal(world, job)?,
+            Job::Repair(_) => self.repair(world, job)?,
             _ => unimplemented!(),
         })
     }
 
     pub fn get_id(&self) -> String {
-        self.inner.id().to_string()
+        self.inner.id.to_string()
     }
 
-    pub fn from_tower(inner: StructureTower) -> Self {
+    pub fn from_tower(inner: StructureData) -> Self {
         Self {
             current_job: None,
             inner,
         }
     }
 
-    pub fn set_tower(&mut self, tower: StructureTower) {
+    pub fn job_memory(&self) -> Option<JobMemory> {
+        self.current_job.as_ref().map(Job::to_memory)
+    }
+
+    /// Jobs a tower can't do are dropped, so it picks a new one
+    pub fn restore_job(&mut self, world: &dyn World, memory: &JobMemory) {
+        self.current_job = Job::from_memory(world, memory)
+            .filter(|j| matches!(j, Job::Attack(_) | Job::Heal(_) | Job::Repair(_)));
+    }
+
+    pub fn set_tower(&mut self, tower: StructureData) {
         self.inner = tower;
     }
 
-    pub fn select_job(&mut self, jobs: &mut [JobOffer]) -> Result<()> {
+    pub fn select_job(&mut self, world: &dyn World, jobs: &mut [JobOffer]) -> Result<()> {
         debug!(
             "tower {} has {} jobs to choose from",
-            self.inner.id(),
+            self.inner.id,
             jobs.len()
         );
 
         if let Some(job) = &self.current_job {
             debug!("Keeping job");
-            if !self.execute_job(job)? {
+            if !self.execute_job(world, job)? {
                 self.current_job = None;
             }
         } else {
             debug!("Changing job");
-            let pos = self.inner.pos();
+            let pos = self.inner.pos;
 
             if let Some(offer) = jobs
                 .iter_mut()
@@ -71,13 +83,10 @@ impl Tower {
                     Job::Repair(c) => {
                         debug!(
                             "Repair: {} hits vs {} capacity",
-                            c.as_attackable().unwrap().hits(),
-                            self.inner.store_capacity(Some(ResourceType::Energy))
-                                * constants::MAX_REPAIR_MULTIPLIER
+                            c.hits,
+                            self.energy_capacity() * constants::MAX_REPAIR_MULTIPLIER
                         );
-                        c.as_attackable().unwrap().hits()
-                            < self.inner.store_capacity(Some(ResourceType::Energy))
-                                * constants::MAX_REPAIR_MULTIPLIER
+                        c.hits < self.energy_capacity() * constants::MAX_REPAIR_MULTIPLIER
                     }
                     _ => false,
                 })
@@ -88,25 +97,29 @@ impl Tower {
             {
                 offer.available_places -= 1;
 
-                if self.execute_job(&offer.job)? {
+                if self.execute_job(world, &offer.job)? {
                     self.current_job = Some(offer.job.clone());
                 }
             } else {
-                debug!("No job available for tower {}", self.inner.id());
+                debug!("No job available for tower {}", self.inner.id);
             }
         }
 
         Ok(())
     }
 
-    fn attack(&self, job: &Job) -> Result<bool> {
+    fn energy_capacity(&self) -> u32 {
+        self.inner.store.as_ref().map(|s| s.capacity).unwrap_or(0)
+    }
+
+    fn attack(&self, world: &dyn World, job: &Job) -> Result<bool> {
         debug!("Running attack");
 
-        if let Some(creep) = job.get_creep() {
-            let r = self.inner.attack(&creep);
+        if let Some(creep) = job.get_creep(world) {
+            let r = world.tower_attack(self.inner.id, creep.id);
             match r {
                 ReturnCode::Ok => {
-                    if job.get_creep().map(|c| c.hits()).unwrap_or(0) == 0 {
+                    if job.get_creep(world).map(|c| c.hits).unwrap_or(0) == 0 {
                         info!("Killed enemy, abandoning job!");
                         Ok(false)
                     } else {
@@ -120,16 +133,16 @@ impl Tower {
         }
     }
 
-    fn heal(&self, job: &Job) -> Result<bool> {
+    fn heal(&self, world: &dyn World, job: &Job) -> Result<bool> {
         debug!("Running heal");
 
-        if let Some(creep) = job.get_creep() {
-            let r = self.inner.heal(&creep);
+        if let Some(creep) = job.get_creep(world) {
+            let r = world.tower_heal(self.inner.id, creep.id);
             match r {
                 ReturnCode::Ok => {
                     if job
-                        .get_creep()
-                        .map(|c| c.hits() == c.hits_max())
+                        .get_creep(world)
+                        .map(|c| c.hits == c.hits_max)
                         .unwrap_or(false)
                     {
                         info!("Healed creep, abandoning job!");
@@ -145,20 +158,17 @@ impl Tower {
         }
     }
 
-    fn repair(&self, job: &Job) -> Result<bool> {
+    fn repair(&self, world: &dyn World, job: &Job) -> Result<bool> {
         debug!("Running repair");
 
-        if let Some(target) = job.get_structure() {
-            let attackable = target.as_attackable().unwrap();
-            if attackable.hits() == attackable.hits_max()
-                || attackable.hits()
-                    > self.inner.store_capacity(Some(ResourceType::Energy))
-                        * constants::MAX_REPAIR_MULTIPLIER
+        if let Some(target) = job.get_structure(world) {
+            if target.hits >= hits_target(world, &target)
+                || target.hits > self.energy_capacity() * constants::MAX_REPAIR_MULTIPLIER
             {
                 return Ok(false);
             }
 
-            let r = self.inner.repair(&target);
+            let r = world.tower_repair(self.inner.id, target.id);
             match r {
                 ReturnCode::Ok => Ok(true),
                 ReturnCode::NotEnough => Ok(true),
@@ -169,3 +179,32 @@ impl Tower {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{pos, world, Store};
+    use screeps::{StructureType, TOWER_CAPACITY};
+
+    #[test]
+    fn towers_drop_jobs_they_cant_do() {
+        let world = world();
+        let id = world.add_structure(
+            StructureType::Tower,
+            pos(30, 30),
+            Some(Store::new(TOWER_CAPACITY)),
+        );
+        let mut tower = Tower::from_tower(world.get_structure(id).unwrap());
+        let spawn = world
+            .structures(pos(0, 0).room_name())
+            .into_iter()
+            .find(|s| s.structure_type == StructureType::Spawn)
+            .unwrap();
+        let maintain = Job::Maintain(spawn).to_memory();
+
+        tower.restore_job(&world, &maintain);
+        tower.select_job(&world, &mut []).unwrap();
+
+        assert!(tower.job_memory().is_none());
+    }
+}
@@ -1,107 +1,274 @@
-use super::{Job, JobOffer};
-use crate::core::constants;
+use super::{boosted_parts, Delivery, Job, JobOffer, Lifecycle, Logistics, Role};
+use crate::core::{constants, JobMemory};
+use crate::planner::hits_target;
+use crate::world::{CreepData, SpawnData, StructureData, World};
 use log::*;
-use screeps::{prelude::*, LineDrawStyle, MoveToOptions, PolyStyle, ResourceType, ReturnCode};
+use screeps::{
+    Position, ResourceType, ReturnCode, StructureType, LAB_BOOST_ENERGY, LAB_BOOST_MINERAL,
+};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum Error {
+    #[error("Couldn't attack: `{0:?}`")]
+    Attack(ReturnCode),
+    #[error("Couldn't get boosted: `{0:?}`")]
+    Boost(ReturnCode),
     #[error("Couldn't build: `{0:?}`")]
     Build(ReturnCode),
+    #[error("Couldn't deliver: `{0:?}`")]
+    Deliver(ReturnCode),
     #[error("Couldn't harvest: `{0:?}`")]
     Harvest(ReturnCode),
+    #[error("Couldn't heal: `{0:?}`")]
+    Heal(ReturnCode),
     #[error("Couldn't maintain: `{0:?}`")]
     Maintain(ReturnCode),
     #[error("Couldn't move: `{0:?}`")]
     Move(ReturnCode),
+    #[error("Couldn't pick up: `{0:?}`")]
+    Pickup(ReturnCode),
+    #[error("Couldn't recycle: `{0:?}`")]
+    Recycle(ReturnCode),
+    #[error("Couldn't renew: `{0:?}`")]
+    Renew(ReturnCode),
     #[error("Couldn't repair: `{0:?}`")]
     Repair(ReturnCode),
     #[error("Couldn't upgrade: `{0:?}`")]
     Upgrade(ReturnCode),
+    #[error("Couldn't withdraw: `{0:?}`")]
+    Withdraw(ReturnCode),
 }
 
 pub struct Creep {
     current_job: Option<Job>,
-    inner: screeps::Creep,
+    inner: CreepData,
+    /// Taken up once the current job is done
+    next_job: Option<Job>,
+    role: Option<Role>,
 }
 
 type Result<T> = std::result::Result<T, crate::creeps::work::Error>;
 
 impl Creep {
-    pub fn execute_job(&self, job: &Job) -> Result<bool> {
+    pub fn execute_job(&self, world: &dyn World, job: &Job) -> Result<bool> {
         Ok(match job {
-            Job::Attack(_) | Job::Heal(_) => unimplemented!(),
-            Job::Build(_) => self.build(&job)?,
-            Job::Harvest(_) => self.harvest(&job)?,
-            Job::Maintain(_) => self.maintain(&job)?,
-            Job::Repair(_) => self.repair(&job)?,
-            Job::Upgrade(_) => self.upgrade(&job)?,
+            Job::Attack(_) => self.attack(world, job)?,
+            Job::Build(_) => self.build(world, job)?,
+            Job::Deliver(..) => self.deliver(world, job)?,
+            Job::Extract(..) => self.extract(world, job)?,
+            Job::Harvest(_) => self.harvest(world, job)?,
+            Job::Heal(_) => self.heal(world, job)?,
+            Job::Loot(_) | Job::Salvage(_) => self.loot(world, job)?,
+            Job::Maintain(_) => self.maintain(world, job)?,
+            Job::Mine(..) => self.mine(world, job)?,
+            Job::Pickup(_) => self.pickup(world, job)?,
+            Job::Refuel(_) | Job::Withdraw(..) => self.withdraw(world, job)?,
+            Job::Repair(_) => self.repair(world, job)?,
+            Job::Upgrade(_) => self.upgrade(world, job)?,
         })
     }
 
-    pub fn from_creep(inner: screeps::Creep) -> Self {
+    pub fn drop_job(&mut self) {
+        self.current_job = None;
+        self.next_job = None;
+    }
+
+    pub fn from_creep(inner: CreepData) -> Self {
         Self {
             current_job: None,
             inner,
+            next_job: None,
+            role: None,
         }
     }
 
     pub fn get_name(&self) -> String {
-        self.inner.name()
+        self.inner.name.clone()
+    }
+
+    pub fn get_pos(&self) -> Position {
+        self.inner.pos
+    }
+
+    /// Takes the pickup (if any) and then the dropoff of the delivery.
+    pub fn haul(&mut self, delivery: Delivery) {
+        match delivery.pickup {
+            Some(pickup) => {
+                self.current_job = Some(pickup);
+                self.next_job = Some(delivery.dropoff);
+            }
+            None => {
+                self.current_job = Some(delivery.dropoff);
+                self.next_job = None;
+            }
+        }
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.current_job.is_none() && self.next_job.is_none()
+    }
+
+    pub fn job_memory(&self) -> Option<JobMemory> {
+        self.current_job.as_ref().map(Job::to_memory)
+    }
+
+    pub fn next_job_memory(&self) -> Option<JobMemory> {
+        self.next_job.as_ref().map(Job::to_memory)
+    }
+
+    /// Books what the creep is on its way to pick up and drop off.
+    pub fn reserve(&self, logistics: &mut Logistics) {
+        let store = &self.inner.store;
+        for job in self.current_job.iter().chain(self.next_job.iter()) {
+            match job {
+                Job::Loot(_)
+                | Job::Pickup(_)
+                | Job::Refuel(_)
+                | Job::Salvage(_)
+                | Job::Withdraw(..) => logistics.reserve_supply(job, store.free_capacity(None)),
+                Job::Deliver(..) | Job::Maintain(_) if self.next_job.is_some() => {
+                    logistics.reserve_demand(job, store.capacity)
+                }
+                Job::Deliver(..) | Job::Maintain(_) => {
+                    logistics.reserve_demand(job, store.used_capacity(job.resource()))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Walks to the spawn to be renewed or recycled there, `false` once the spawn can't
+    /// renew it any further.
+    pub fn run_lifecycle(
+        &self,
+        world: &dyn World,
+        lifecycle: Lifecycle,
+        spawn: &SpawnData,
+    ) -> Result<bool> {
+        if !self.inner.pos.is_near_to(&spawn.pos) {
+            self.move_to(world, spawn.pos)?;
+            return Ok(true);
+        }
+
+        let name = &self.inner.name;
+        match lifecycle {
+            Lifecycle::Renew => match world.spawn_renew_creep(&spawn.name, name) {
+                ReturnCode::Ok => Ok(true),
+                ReturnCode::Busy | ReturnCode::Full | ReturnCode::NotEnough => Ok(false),
+                r => Err(Error::Renew(r)),
+            },
+            Lifecycle::Recycle => match world.spawn_recycle_creep(&spawn.name, name) {
+                ReturnCode::Ok => Ok(true),
+                r => Err(Error::Recycle(r)),
+            },
+        }
+    }
+
+    /// Walks up to the lab and has it boost the creep once it holds enough for all the
+    /// parts, `true` once boosted.
+    pub fn get_boosted(&self, world: &dyn World, lab: &StructureData) -> Result<bool> {
+        // Still spawning
+        if self.inner.ticks_to_live.is_none() {
+            return Ok(false);
+        }
+        if !self.inner.pos.in_range_to(&lab.pos, constants::RANGE_BOOST) {
+            self.move_to(world, lab.pos)?;
+            return Ok(false);
+        }
+
+        let store = lab.store.clone().unwrap_or_default();
+        let (compound, amount) = match store.mineral() {
+            Some(m) => m,
+            None => return Ok(false),
+        };
+        let parts = boosted_parts(&self.inner.body, compound);
+        if amount < parts * LAB_BOOST_MINERAL
+            || store.used_capacity(Some(ResourceType::Energy)) < parts * LAB_BOOST_ENERGY
+        {
+            return Ok(false);
+        }
+        match world.lab_boost_creep(lab.id, &self.inner.name) {
+            ReturnCode::Ok => Ok(true),
+            r => Err(Error::Boost(r)),
+        }
     }
 
-    pub fn set_creep(&mut self, creep: screeps::Creep) {
+    pub fn restore_job(&mut self, world: &dyn World, memory: &JobMemory) {
+        self.current_job = Job::from_memory(world, memory);
+    }
+
+    pub fn restore_next_job(&mut self, world: &dyn World, memory: &JobMemory) {
+        self.next_job = Job::from_memory(world, memory);
+    }
+
+    pub fn set_creep(&mut self, creep: CreepData) {
         self.inner = creep;
     }
 
-    pub fn select_job(&mut self, jobs: &mut [JobOffer]) -> Result<()> {
+    pub fn set_role(&mut self, role: Option<Role>) {
+        self.role = role;
+    }
+
+    pub fn select_job(
+        &mut self,
+        world: &dyn World,
+        jobs: &mut [JobOffer],
+        logistics: &mut Logistics,
+    ) -> Result<()> {
         debug!(
             "creep {} has {} jobs to choose from",
-            self.inner.name(),
+            self.inner.name,
             jobs.len()
         );
 
         if let Some(job) = &self.current_job {
             debug!("Keeping job");
-            if !self.execute_job(job)? {
-                self.current_job = None;
+            if !self.execute_job(world, job)? {
+                self.current_job = self.next_job.take();
+            }
+        } else if let Some(job) = self.refuel(logistics) {
+            debug!("Refueling");
+            world.creep_say(&self.inner.name, "refueling");
+            if self.execute_job(world, &job)? {
+                self.current_job = Some(job);
             }
         } else {
             debug!("Changing job");
-            let pos = self.inner.pos();
+            let pos = self.inner.pos;
 
             if let Some(offer) = jobs
                 .iter_mut()
-                .filter(|a| {
-                    if matches!(a.job, Job::Attack(_)) {
-                        false
-                    } else if matches!(a.job, Job::Heal(_)) {
-                        false
-                    } else {
-                        true
-                    }
-                })
+                .filter(|a| self.accepts(&a.job))
                 .filter(|a| a.available_places != 0)
                 .filter(|a| {
-                    if let Job::Repair(c) = &a.job {
+                    if matches!(
+                        a.job,
+                        Job::Attack(_) | Job::Extract(..) | Job::Heal(_) | Job::Mine(..)
+                    ) {
+                        // Fighting and mining don't need energy
+                        return true;
+                    } else if let Job::Repair(c) = &a.job {
                         debug!(
                             "Repair: {} hits vs {} capacity",
-                            c.as_attackable().unwrap().hits(),
-                            self.inner.store_capacity(Some(ResourceType::Energy))
-                                * constants::MAX_REPAIR_MULTIPLIER
+                            c.hits,
+                            self.inner.store.capacity * constants::MAX_REPAIR_MULTIPLIER
                         );
-                        return c.as_attackable().unwrap().hits()
-                            < self.inner.store_capacity(Some(ResourceType::Energy))
-                                * constants::MAX_REPAIR_MULTIPLIER;
-                    } else if self.inner.store_free_capacity(Some(ResourceType::Energy)) == 0
-                        || self.inner.ticks_to_live().unwrap_or(0) < 50
+                        // Defenses are only offered below their target
+                        return matches!(
+                            c.structure_type,
+                            StructureType::Rampart | StructureType::Wall
+                        ) || c.hits
+                            < self.inner.store.capacity * constants::MAX_REPAIR_MULTIPLIER;
+                    } else if self.inner.store.free_capacity(None) == 0
+                        || self.inner.ticks_to_live.unwrap_or(0) < 50
                     {
-                        if let Job::Harvest(_) = a.job {
+                        if a.job.gathers() {
                             debug!("Rejecting job because harvest and no free energy storage");
                             return false;
                         }
-                    } else if self.inner.store_used_capacity(Some(ResourceType::Energy)) == 0 {
-                        if let Job::Harvest(_) = a.job {
+                    } else if self.inner.store.used_capacity(Some(ResourceType::Energy)) == 0 {
+                        if a.job.gathers() {
                             return true;
                         } else {
                             debug!("Rejecting job because no energy and not harvest job");
@@ -118,61 +285,113 @@ impl Creep {
             {
                 offer.available_places -= 1;
 
+                let name = &self.inner.name;
                 match &offer.job {
-                    Job::Attack(_) | Job::Heal(_) => unimplemented!(),
+                    Job::Attack(_) => world.creep_say(name, "attacking"),
+                    Job::Heal(_) => world.creep_say(name, "healing"),
                     // FIXME: Check if one creep is enough for building
-                    Job::Build(_) => self.inner.say("building", false),
-                    Job::Harvest(_) => self.inner.say("harvesting", false),
+                    Job::Build(_) => world.creep_say(name, "building"),
+                    Job::Deliver(..) => world.creep_say(name, "delivering"),
+                    Job::Extract(..) => world.creep_say(name, "extracting"),
+                    Job::Harvest(_) => world.creep_say(name, "harvesting"),
+                    Job::Loot(_) => world.creep_say(name, "looting"),
                     Job::Maintain(target) => {
                         // Don't move multiple creeps to maintainance when one creep can fill the spot
                         if offer.available_places != 0
-                            && i64::from(
-                                target
-                                    .as_has_store()
-                                    .unwrap()
-                                    .store_free_capacity(Some(ResourceType::Energy)),
-                            ) <= i64::from(
-                                self.inner.store_used_capacity(Some(ResourceType::Energy)),
-                            )
+                            && target
+                                .store
+                                .as_ref()
+                                .unwrap()
+                                .free_capacity(Some(ResourceType::Energy))
+                                <= self.inner.store.used_capacity(Some(ResourceType::Energy))
                         {
                             offer.available_places = 0;
                         }
-                        self.inner.say("maintaining", false)
+                        world.creep_say(name, "maintaining")
                     }
                     // FIXME: Check if one creep is enough for repairing
-                    Job::Repair(_) => self.inner.say("repairing", false),
-                    Job::Upgrade(_) => self.inner.say("upgrading", false),
+                    Job::Mine(..) => world.creep_say(name, "mining"),
+                    Job::Pickup(_) => world.creep_say(name, "picking up"),
+                    Job::Refuel(_) => world.creep_say(name, "refueling"),
+                    Job::Repair(_) => world.creep_say(name, "repairing"),
+                    Job::Salvage(_) => world.creep_say(name, "salvaging"),
+                    Job::Upgrade(_) => world.creep_say(name, "upgrading"),
+                    Job::Withdraw(..) => world.creep_say(name, "withdrawing"),
                 };
 
-                if self.execute_job(&offer.job)? {
+                if self.execute_job(world, &offer.job)? {
                     self.current_job = Some(offer.job.clone());
                 }
             } else {
-                warn!("No job available for creep {}", self.inner.name());
+                warn!("No job available for creep {}", self.inner.name);
             }
         }
 
         Ok(())
     }
 
-    fn build(&self, job: &Job) -> Result<bool> {
+    fn accepts(&self, job: &Job) -> bool {
+        match self.role {
+            Some(role) => role.accepts(job),
+            // Creeps from before roles are generic workers
+            None => !matches!(
+                job,
+                Job::Attack(_) | Job::Extract(..) | Job::Heal(_) | Job::Mine(..)
+            ),
+        }
+    }
+
+    /// The closest stocked energy, if the creep has run out and takes energy from stores.
+    fn refuel(&self, logistics: &mut Logistics) -> Option<Job> {
+        let store = &self.inner.store;
+        if store.used_capacity(Some(ResourceType::Energy)) != 0
+            || self.inner.ticks_to_live.unwrap_or(0) < 50
+        {
+            return None;
+        }
+
+        let wanted = store.free_capacity(Some(ResourceType::Energy));
+        let job = logistics
+            .refuel(self.inner.pos, wanted)
+            .filter(|j| self.accepts(j))?;
+        logistics.reserve_supply(&job, wanted);
+        Some(job)
+    }
+
+    fn attack(&self, world: &dyn World, job: &Job) -> Result<bool> {
+        debug!("Running attack");
+
+        if let Some(target) = job.get_creep(world) {
+            let r = world.creep_attack(&self.inner.name, target.id);
+            match r {
+                ReturnCode::NotInRange => Ok(!self.move_to(world, target.pos)?),
+                ReturnCode::Ok => Ok(true),
+                _ => Err(Error::Attack(r)),
+            }
+        } else {
+            debug!("Target is gone, abandoning attack job!");
+            Ok(false)
+        }
+    }
+
+    fn build(&self, world: &dyn World, job: &Job) -> Result<bool> {
         debug!("Running build");
 
-        if self.inner.store_used_capacity(Some(ResourceType::Energy)) == 0 {
+        if self.inner.store.used_capacity(Some(ResourceType::Energy)) == 0 {
             debug!("No energy left, abandoning build job!");
             return Ok(false);
         }
 
-        if let Some(site) = job.get_construction_site() {
-            if site.progress() == site.progress_total() {
+        if let Some(site) = job.get_construction_site(world) {
+            if site.progress == site.progress_total {
                 debug!("Building done, abandoning build job!");
                 return Ok(false);
             }
 
-            let r = self.inner.build(&site);
+            let r = world.creep_build(&self.inner.name, site.id);
 
             if r == ReturnCode::NotInRange {
-                self.move_to(&site)?;
+                self.move_to(world, site.pos)?;
             } else if r != ReturnCode::Ok {
                 return Err(Error::Build(r));
             }
@@ -183,22 +402,71 @@ impl Creep {
         }
     }
 
-    fn harvest(&self, job: &Job) -> Result<bool> {
+    fn deliver(&self, world: &dyn World, job: &Job) -> Result<bool> {
+        debug!("Running deliver");
+
+        let resource = job.resource().unwrap();
+        if self.inner.store.used_capacity(Some(resource)) == 0 {
+            debug!("Nothing left to deliver, abandoning deliver job!");
+            return Ok(false);
+        }
+
+        if let Some(target) = job.get_structure(world) {
+            let r = world.creep_transfer_all(&self.inner.name, target.id, resource);
+            match r {
+                ReturnCode::NotInRange => Ok(!self.move_to(world, target.pos)?),
+                ReturnCode::Ok | ReturnCode::Full => Ok(false),
+                _ => Err(Error::Deliver(r)),
+            }
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn extract(&self, world: &dyn World, job: &Job) -> Result<bool> {
+        debug!("Running extract");
+
+        let (mineral, container) = match (job.get_mineral(world), job.get_structure(world)) {
+            (Some(m), Some(c)) => (m, c),
+            _ => {
+                debug!("Mineral or container is gone, abandoning extract job!");
+                return Ok(false);
+            }
+        };
+
+        if self.inner.pos != container.pos {
+            self.move_to(world, container.pos)?;
+            return Ok(true);
+        }
+
+        let r = world.creep_harvest(&self.inner.name, mineral.id);
+        match r {
+            // Tired while the extractor cools down
+            ReturnCode::Ok | ReturnCode::Tired => Ok(true),
+            ReturnCode::NotEnough => {
+                debug!("Mineral is depleted, abandoning extract job!");
+                Ok(false)
+            }
+            _ => Err(Error::Harvest(r)),
+        }
+    }
+
+    fn harvest(&self, world: &dyn World, job: &Job) -> Result<bool> {
         debug!("Running harvest");
 
-        debug!("{} free capacity", self.inner.store_free_capacity(None));
+        debug!("{} free capacity", self.inner.store.free_capacity(None));
 
-        if self.inner.store_free_capacity(Some(ResourceType::Energy)) == 0 {
+        if self.inner.store.free_capacity(Some(ResourceType::Energy)) == 0 {
             debug!("Energy storage full, abandoning harvest job!");
             return Ok(false);
         }
 
-        if let Some(source) = job.get_source() {
-            let r = self.inner.harvest(&source);
+        if let Some(source) = job.get_source(world) {
+            let r = world.creep_harvest(&self.inner.name, source.id);
             match r {
                 ReturnCode::NotInRange => {
                     debug!("Not in range for harvest, moving");
-                    Ok(!self.move_to(&source)?)
+                    Ok(!self.move_to(world, source.pos)?)
                 }
                 ReturnCode::Ok => {
                     debug!("harvesting...");
@@ -215,12 +483,57 @@ impl Creep {
         }
     }
 
-    fn move_to<T: ?Sized + HasPosition>(&self, target: &T) -> Result<bool> {
-        let r = self.inner.move_to_with_options(
-            target,
-            MoveToOptions::new()
-                .visualize_path_style(PolyStyle::default().line_style(LineDrawStyle::Dashed)),
-        );
+    fn heal(&self, world: &dyn World, job: &Job) -> Result<bool> {
+        debug!("Running heal");
+
+        match job.get_creep(world) {
+            Some(target) if target.hits < target.hits_max => {
+                let r = world.creep_heal(&self.inner.name, target.id);
+                match r {
+                    ReturnCode::NotInRange => Ok(!self.move_to(world, target.pos)?),
+                    ReturnCode::Ok => Ok(true),
+                    _ => Err(Error::Heal(r)),
+                }
+            }
+            _ => {
+                debug!("Nothing left to heal, abandoning heal job!");
+                Ok(false)
+            }
+        }
+    }
+
+    fn loot(&self, world: &dyn World, job: &Job) -> Result<bool> {
+        debug!("Running loot");
+
+        if self.inner.store.free_capacity(None) == 0 {
+            debug!("Storage full, abandoning loot job!");
+            return Ok(false);
+        }
+
+        let remains = match job.get_remains(world) {
+            Some(r) => r,
+            None => {
+                debug!("Remains are gone, abandoning loot job!");
+                return Ok(false);
+            }
+        };
+
+        // Haulers take what their dropoff wants, workers only want the energy
+        let resource = match &self.next_job {
+            Some(dropoff) => dropoff.resource().unwrap_or(ResourceType::Energy),
+            None => ResourceType::Energy,
+        };
+
+        let r = world.creep_withdraw_all(&self.inner.name, remains.id, resource);
+        match r {
+            ReturnCode::NotInRange => Ok(!self.move_to(world, remains.pos)?),
+            ReturnCode::Ok | ReturnCode::NotEnough | ReturnCode::Full => Ok(false),
+            _ => Err(Error::Withdraw(r)),
+        }
+    }
+
+    fn move_to(&self, world: &dyn World, target: Position) -> Result<bool> {
+        let r = world.creep_move_to(&self.inner.name, target);
         match r {
             ReturnCode::Ok => {
                 debug!("Ok, moved");
@@ -238,34 +551,33 @@ impl Creep {
         }
     }
 
-    fn maintain(&self, job: &Job) -> Result<bool> {
+    fn maintain(&self, world: &dyn World, job: &Job) -> Result<bool> {
         debug!("Running maintaince");
 
-        if self.inner.store_used_capacity(Some(ResourceType::Energy)) == 0 {
+        if self.inner.store.used_capacity(Some(ResourceType::Energy)) == 0 {
             debug!("No energy left, abandoning maintain job!");
             return Ok(false);
         }
 
-        if let Some(target) = job.get_structure() {
+        if let Some(target) = job.get_structure(world) {
             if target
-                .as_has_store()
+                .store
+                .as_ref()
                 .unwrap()
-                .store_free_capacity(Some(ResourceType::Energy))
+                .free_capacity(Some(ResourceType::Energy))
                 == 0
             {
                 debug!("Store is full, abandoning maintain job");
                 return Ok(false);
             }
 
-            if self.inner.pos().is_near_to(&target) {
+            if self.inner.pos.is_near_to(&target.pos) {
                 debug!("Transferring");
-                let r = self
-                    .inner
-                    .transfer_all(target.as_transferable().unwrap(), ResourceType::Energy);
+                let r = world.creep_transfer_all(&self.inner.name, target.id, ResourceType::Energy);
                 match r {
                     ReturnCode::NotInRange => {
                         debug!("Not in range");
-                        Ok(!self.move_to(&target)?)
+                        Ok(!self.move_to(world, target.pos)?)
                     }
                     ReturnCode::Ok => {
                         debug!("Transferred");
@@ -278,7 +590,7 @@ impl Creep {
                     _ => Err(Error::Maintain(r)),
                 }
             } else {
-                self.inner.move_to(&target);
+                world.creep_move_to(&self.inner.name, target.pos);
                 Ok(true)
             }
         } else {
@@ -286,24 +598,80 @@ impl Creep {
         }
     }
 
-    fn repair(&self, job: &Job) -> Result<bool> {
+    fn mine(&self, world: &dyn World, job: &Job) -> Result<bool> {
+        debug!("Running mine");
+
+        let (source, container) = match (job.get_source(world), job.get_structure(world)) {
+            (Some(s), Some(c)) => (s, c),
+            _ => {
+                debug!("Source or container is gone, abandoning mine job!");
+                return Ok(false);
+            }
+        };
+
+        if self.inner.pos != container.pos {
+            self.move_to(world, container.pos)?;
+            return Ok(true);
+        }
+
+        // Whatever doesn't fit into the creep drops into the container
+        let r = world.creep_harvest(&self.inner.name, source.id);
+        if !matches!(r, ReturnCode::Ok | ReturnCode::NotEnough) {
+            return Err(Error::Harvest(r));
+        }
+
+        // Unless there's a link to send it on with
+        let link = world
+            .structures(container.pos.room_name())
+            .into_iter()
+            .find(|s| {
+                s.my && s.structure_type == StructureType::Link
+                    && s.pos.in_range_to(&container.pos, constants::RANGE_TRANSFER)
+            });
+        if let Some(link) = link {
+            if self.inner.store.used_capacity(Some(ResourceType::Energy)) > 0 {
+                let r = world.creep_transfer_all(&self.inner.name, link.id, ResourceType::Energy);
+                if !matches!(r, ReturnCode::Ok | ReturnCode::Full) {
+                    return Err(Error::Deliver(r));
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    fn pickup(&self, world: &dyn World, job: &Job) -> Result<bool> {
+        debug!("Running pickup");
+
+        if let Some(resource) = job.get_resource(world) {
+            let r = world.creep_pickup(&self.inner.name, resource.id);
+            match r {
+                ReturnCode::NotInRange => Ok(!self.move_to(world, resource.pos)?),
+                ReturnCode::Ok | ReturnCode::Full => Ok(false),
+                _ => Err(Error::Pickup(r)),
+            }
+        } else {
+            debug!("Resource is gone, abandoning pickup job!");
+            Ok(false)
+        }
+    }
+
+    fn repair(&self, world: &dyn World, job: &Job) -> Result<bool> {
         debug!("Running repair");
 
-        if self.inner.store_used_capacity(Some(ResourceType::Energy)) == 0 {
+        if self.inner.store.used_capacity(Some(ResourceType::Energy)) == 0 {
             debug!("No energy left, abandoning repair job!");
             return Ok(false);
         }
 
-        if let Some(target) = job.get_structure() {
-            let attackable = target.as_attackable().unwrap();
-            if attackable.hits() == attackable.hits_max() {
+        if let Some(target) = job.get_structure(world) {
+            if target.hits >= hits_target(world, &target) {
                 return Ok(false);
             }
 
-            let r = self.inner.repair(&target);
+            let r = world.creep_repair(&self.inner.name, target.id);
             match r {
                 // FIXME: Handle not being able to reach it
-                ReturnCode::NotInRange => self.move_to(&target),
+                ReturnCode::NotInRange => self.move_to(world, target.pos),
                 ReturnCode::Ok => Ok(false),
                 _ => Err(Error::Repair(r)),
             }?;
@@ -314,18 +682,21 @@ impl Creep {
         }
     }
 
-    fn upgrade(&self, job: &Job) -> Result<bool> {
+    fn upgrade(&self, world: &dyn World, job: &Job) -> Result<bool> {
         debug!("Running upgrade");
 
-        if self.inner.store_used_capacity(Some(ResourceType::Energy)) == 0 {
+        if self.inner.store.used_capacity(Some(ResourceType::Energy)) == 0 {
             debug!("No energy left, abandoning upgrade job!");
             return Ok(false);
         }
 
-        let c = job.get_structure_controller();
-        let r = self.inner.upgrade_controller(&c);
+        let c = match job.get_structure_controller(world) {
+            Some(c) => c,
+            None => return Ok(false),
+        };
+        let r = world.creep_upgrade_controller(&self.inner.name, c.id);
         match r {
-            ReturnCode::NotInRange => self.move_to(&c),
+            ReturnCode::NotInRange => self.move_to(world, c.pos),
             ReturnCode::NotEnough => Ok(false),
             ReturnCode::Ok => Ok(false),
             _ => Err(Error::Upgrade(r)),
@@ -333,4 +704,297 @@ impl Creep {
 
         Ok(true)
     }
+
+    fn withdraw(&self, world: &dyn World, job: &Job) -> Result<bool> {
+        debug!("Running withdraw");
+
+        let resource = job.resource().unwrap();
+        if self.inner.store.free_capacity(Some(resource)) == 0 {
+            debug!("Storage full, abandoning withdraw job!");
+            return Ok(false);
+        }
+
+        if let Some(target) = job.get_structure(world) {
+            let r = world.creep_withdraw_all(&self.inner.name, target.id, resource);
+            match r {
+                ReturnCode::NotInRange => Ok(!self.move_to(world, target.pos)?),
+                ReturnCode::Ok | ReturnCode::NotEnough | ReturnCode::Full => Ok(false),
+                _ => Err(Error::Withdraw(r)),
+            }
+        } else {
+            Ok(false)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{empty_room, pos, FakeWorld, Intent, Store};
+    use screeps::{StructureType, LAB_MINERAL_CAPACITY};
+
+    fn creep(world: &FakeWorld, energy: u32) -> Creep {
+        world.add_creep(
+            "worker",
+            pos(20, 20),
+            Store::new(50).with(ResourceType::Energy, energy),
+        );
+        Creep::from_creep(world.creeps().pop().unwrap())
+    }
+
+    #[test]
+    fn empty_creep_goes_harvesting() {
+        let world = FakeWorld::new();
+        let source = world.add_source(pos(10, 10));
+        let extension =
+            world.add_structure(StructureType::Extension, pos(21, 21), Some(Store::new(50)));
+        let mut jobs = vec![
+            JobOffer::new(Job::Harvest(world.get_source(source).unwrap()), 1),
+            JobOffer::new(Job::Maintain(world.get_structure(extension).unwrap()), 1),
+        ];
+
+        let mut creep = creep(&world, 0);
+        creep
+            .select_job(&world, &mut jobs, &mut Logistics::default())
+            .unwrap();
+
+        assert!(matches!(creep.current_job, Some(Job::Harvest(_))));
+        assert_eq!(jobs[0].available_places, 0);
+        assert_eq!(
+            world.state().intents,
+            vec![Intent::Move {
+                creep: "worker".to_string(),
+                target: pos(10, 10)
+            }]
+        );
+    }
+
+    #[test]
+    fn full_creep_fills_nearby_extension() {
+        let world = FakeWorld::new();
+        let source = world.add_source(pos(10, 10));
+        let extension =
+            world.add_structure(StructureType::Extension, pos(21, 21), Some(Store::new(50)));
+        let mut jobs = vec![
+            JobOffer::new(Job::Harvest(world.get_source(source).unwrap()), 1),
+            JobOffer::new(Job::Maintain(world.get_structure(extension).unwrap()), 2),
+        ];
+
+        let mut creep = creep(&world, 50);
+        creep
+            .select_job(&world, &mut jobs, &mut Logistics::default())
+            .unwrap();
+
+        // The creep can fill the extension on its own, so nobody else is sent there
+        assert_eq!(jobs[1].available_places, 0);
+        assert_eq!(
+            world.state().intents,
+            vec![Intent::Transfer {
+                creep: "worker".to_string(),
+                target: extension,
+                resource: ResourceType::Energy
+            }]
+        );
+    }
+
+    #[test]
+    fn labs_full_of_mineral_still_take_energy() {
+        let world = FakeWorld::new();
+        let store = Store::lab().with(ResourceType::Hydrogen, LAB_MINERAL_CAPACITY);
+        let lab = world.add_structure(StructureType::Lab, pos(21, 21), Some(store));
+
+        let creep = creep(&world, 50);
+
+        assert!(!creep
+            .execute_job(&world, &Job::Maintain(world.get_structure(lab).unwrap()))
+            .unwrap());
+        assert_eq!(
+            world.state().intents,
+            vec![Intent::Transfer {
+                creep: "worker".to_string(),
+                target: lab,
+                resource: ResourceType::Energy
+            }]
+        );
+    }
+
+    #[test]
+    fn upgrading_stops_when_the_controller_is_out_of_sight() {
+        let world = FakeWorld::new();
+        let id = world.add_controller(pos(40, 40), 1);
+        let controller = world.get_controller(id).unwrap();
+        world.state_mut().rooms.clear();
+
+        let creep = creep(&world, 50);
+
+        assert!(!creep
+            .execute_job(&world, &Job::Upgrade(controller))
+            .unwrap());
+        assert!(world.state().intents.is_empty());
+    }
+
+    #[test]
+    fn roles_only_take_their_jobs() {
+        let world = FakeWorld::new();
+        let source = world.add_source(pos(10, 10));
+        let hostile = world.add_hostile(pos(21, 21));
+        let mut jobs = vec![
+            JobOffer::new(Job::Harvest(world.get_source(source).unwrap()), 1),
+            JobOffer::new(Job::Attack(world.get_creep(hostile).unwrap()), 1),
+        ];
+
+        let mut creep = creep(&world, 0);
+        creep.set_role(Some(Role::Defender));
+        creep
+            .select_job(&world, &mut jobs, &mut Logistics::default())
+            .unwrap();
+
+        assert!(matches!(creep.current_job, Some(Job::Attack(_))));
+        assert_eq!(
+            world.state().intents,
+            vec![Intent::Attack {
+                creep: "worker".to_string(),
+                target: hostile
+            }]
+        );
+    }
+
+    #[test]
+    fn workers_only_loot_energy() {
+        let world = FakeWorld::new();
+        let store = Store::new(1000)
+            .with(ResourceType::Energy, 20)
+            .with(ResourceType::Hydrogen, 500);
+        let tombstone = world.add_tombstone(pos(21, 21), store, 100);
+        let mut jobs = vec![JobOffer::new(
+            Job::Loot(world.get_tombstone(tombstone).unwrap()),
+            1,
+        )];
+
+        let mut creep = creep(&world, 0);
+        creep.set_role(Some(Role::Upgrader));
+        creep
+            .select_job(&world, &mut jobs, &mut Logistics::default())
+            .unwrap();
+
+        assert_eq!(
+            world.state().intents,
+            vec![Intent::Withdraw {
+                creep: "worker".to_string(),
+                target: tombstone,
+                resource: ResourceType::Energy,
+            }]
+        );
+    }
+
+    #[test]
+    fn empty_creeps_refuel_until_stores_run_dry() {
+        let world = FakeWorld::new();
+        let source = world.add_source(pos(10, 10));
+        let storage = world.add_structure(
+            StructureType::Storage,
+            pos(30, 30),
+            Some(Store::new(1000).with(ResourceType::Energy, 50)),
+        );
+        world.add_creep("other", pos(20, 20), Store::new(50));
+        let mut jobs = vec![JobOffer::new(
+            Job::Harvest(world.get_source(source).unwrap()),
+            2,
+        )];
+        let mut logistics = Logistics::scan(&world, pos(0, 0).room_name());
+
+        let mut first = creep(&world, 0);
+        first.select_job(&world, &mut jobs, &mut logistics).unwrap();
+        let mut second = Creep::from_creep(world.creeps()[0].clone());
+        second
+            .select_job(&world, &mut jobs, &mut logistics)
+            .unwrap();
+
+        // The storage only had enough for one of them
+        assert!(matches!(&first.current_job, Some(Job::Refuel(s)) if s.id == storage));
+        assert!(matches!(second.current_job, Some(Job::Harvest(_))));
+    }
+
+    #[test]
+    fn miners_park_on_the_container() {
+        let world = empty_room();
+        let source = world.get_source(world.add_source(pos(20, 22))).unwrap();
+        let container = world.add_structure(
+            StructureType::Container,
+            pos(20, 21),
+            Some(Store::new(2000)),
+        );
+        let mut jobs = vec![JobOffer::new(
+            Job::Mine(source.clone(), world.get_structure(container).unwrap()),
+            1,
+        )];
+
+        // Full, yet it keeps harvesting
+        let mut creep = creep(&world, 50);
+        creep.set_role(Some(Role::Miner));
+        creep
+            .select_job(&world, &mut jobs, &mut Logistics::default())
+            .unwrap();
+
+        // In range of the source already, but not on the container
+        assert_eq!(
+            world.state().intents,
+            vec![Intent::Move {
+                creep: "worker".to_string(),
+                target: pos(20, 21)
+            }]
+        );
+
+        world.state_mut().intents.clear();
+        world.state_mut().creeps.get_mut("worker").unwrap().pos = pos(20, 21);
+        creep.set_creep(world.creeps().pop().unwrap());
+        creep
+            .select_job(&world, &mut jobs, &mut Logistics::default())
+            .unwrap();
+
+        assert_eq!(
+            world.state().intents,
+            vec![Intent::Harvest {
+                creep: "worker".to_string(),
+                source: source.id
+            }]
+        );
+    }
+
+    #[test]
+    fn miners_pass_energy_on_to_links() {
+        let world = empty_room();
+        let source = world.get_source(world.add_source(pos(20, 21))).unwrap();
+        let container = world.add_structure(
+            StructureType::Container,
+            pos(20, 20),
+            Some(Store::new(2000)),
+        );
+        let link = world.add_structure(StructureType::Link, pos(21, 20), Some(Store::new(800)));
+        let mut jobs = vec![JobOffer::new(
+            Job::Mine(source.clone(), world.get_structure(container).unwrap()),
+            1,
+        )];
+
+        let mut creep = creep(&world, 50);
+        creep.set_role(Some(Role::Miner));
+        creep
+            .select_job(&world, &mut jobs, &mut Logistics::default())
+            .unwrap();
+
+        assert_eq!(
+            world.state().intents,
+            vec![
+                Intent::Harvest {
+                    creep: "worker".to_string(),
+                    source: source.id
+                },
+                Intent::Transfer {
+                    creep: "worker".to_string(),
+                    target: link,
+                    resource: ResourceType::Energy
+                }
+            ]
+        );
+    }
 }
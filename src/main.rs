@@ -1,5 +1,9 @@
 mod core;
 mod creeps;
+mod planner;
+#[cfg(test)]
+mod sim;
+mod world;
 
 use crate::core::*;
 use log::LevelFilter::Info;
@@ -0,0 +1,268 @@
+use super::{controller_structures, Layout, Traffic};
+use crate::core::{constants, Memory};
+use crate::world::World;
+use log::*;
+use screeps::{Position, ReturnCode, RoomName, StructureType, Terrain};
+use std::collections::HashMap;
+
+/// What gets built first when the controller allows several things at once
+fn importance(structure_type: StructureType) -> u32 {
+    match structure_type {
+        StructureType::Spawn => 0,
+        StructureType::Extension => 1,
+        StructureType::Tower => 2,
+        StructureType::Storage => 3,
+        StructureType::Road => 5,
+        _ => 4,
+    }
+}
+
+/// Places sites for the next structures of every planned room, then roads where creeps
+/// walk the most. At most `MAX_CONSTRUCTION_SITES` go up per room at a time, so builders
+/// finish one thing before the next.
+pub fn place_sites(world: &dyn World, memory: &Memory) {
+    for (room, room_memory) in &memory.rooms {
+        if let Some(layout) = &room_memory.layout {
+            place_room_sites(world, *room, layout, &room_memory.traffic);
+        }
+    }
+}
+
+fn place_room_sites(world: &dyn World, room: RoomName, layout: &Layout, traffic: &Traffic) {
+    let level = match world.room(room).and_then(|r| r.controller) {
+        Some(c) if c.my => c.level,
+        _ => return,
+    };
+    let structures = world.structures(room);
+    let sites = world.construction_sites(room);
+    let mut free = constants::MAX_CONSTRUCTION_SITES.saturating_sub(sites.len() as u32);
+    if free == 0 {
+        return;
+    }
+
+    let mut counts: HashMap<StructureType, u32> = HashMap::new();
+    for structure_type in structures
+        .iter()
+        .map(|s| s.structure_type)
+        .chain(sites.iter().map(|s| s.structure_type))
+    {
+        *counts.entry(structure_type).or_insert(0) += 1;
+    }
+
+    // Ramparts only hold with a tower to back them up
+    let defended = structures
+        .iter()
+        .any(|s| s.my && s.structure_type == StructureType::Tower);
+    let mut next: Vec<_> = layout
+        .structures
+        .iter()
+        .filter(|s| s.rcl <= level)
+        .filter(|s| defended || s.structure_type != StructureType::Rampart)
+        .filter(|s| {
+            let pos = Position::new(s.x, s.y, room);
+            let built = structures
+                .iter()
+                .any(|b| b.pos == pos && b.structure_type == s.structure_type);
+            !built && !sites.iter().any(|b| b.pos == pos)
+        })
+        .collect();
+    next.sort_by_key(|s| importance(s.structure_type));
+
+    for planned in next {
+        if free == 0 {
+            break;
+        }
+        let count = counts.entry(planned.structure_type).or_insert(0);
+        if *count >= controller_structures(planned.structure_type, level) {
+            continue;
+        }
+
+        let pos = Position::new(planned.x, planned.y, room);
+        match world.create_construction_site(pos, planned.structure_type) {
+            ReturnCode::Ok => {
+                info!("Placing a {:?} at {}", planned.structure_type, pos);
+                *count += 1;
+                free -= 1;
+            }
+            r => warn!(
+                "Couldn't place a {:?} at {}: {:?}",
+                planned.structure_type, pos, r
+            ),
+        }
+    }
+
+    if level < constants::ROAD_RCL {
+        return;
+    }
+    let terrain = world.terrain(room);
+    for (x, y) in traffic.busy(constants::TRAFFIC_ROAD_STEPS) {
+        if free == 0 {
+            break;
+        }
+        let pos = Position::new(x, y, room);
+        let exit = x == 0 || y == 0 || x == constants::ROOM_X - 1 || y == constants::ROOM_Y - 1;
+        let planned = layout.structures.iter().any(|s| (s.x, s.y) == (x, y));
+        let taken = structures.iter().any(|s| s.pos == pos) || sites.iter().any(|s| s.pos == pos);
+        if exit || planned || taken || terrain.get(x, y) == Terrain::Wall {
+            continue;
+        }
+
+        match world.create_construction_site(pos, StructureType::Road) {
+            ReturnCode::Ok => {
+                info!("Placing a road on the busy tile {}", pos);
+                free -= 1;
+            }
+            r => warn!("Couldn't place a road at {}: {:?}", pos, r),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::planner::plan_rooms;
+    use crate::world::{empty_room, pos, FakeWorld, Intent, Store};
+    use screeps::ResourceType;
+
+    fn world(level: u32) -> (FakeWorld, Memory) {
+        let world = empty_room();
+        world.add_controller(pos(40, 40), level);
+        world.add_spawn("Spawn1", pos(25, 25));
+        world.add_source(pos(10, 10));
+        let mut memory = Memory::default();
+        plan_rooms(&world, &mut memory);
+        (world, memory)
+    }
+
+    fn placed(world: &FakeWorld) -> Vec<StructureType> {
+        world
+            .state()
+            .intents
+            .iter()
+            .filter_map(|i| match i {
+                Intent::CreateSite { structure_type, .. } => Some(*structure_type),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sites_follow_the_controller_level_and_importance() {
+        let (world, memory) = world(3);
+
+        place_sites(&world, &memory);
+
+        assert_eq!(
+            placed(&world),
+            vec![StructureType::Extension; constants::MAX_CONSTRUCTION_SITES as usize]
+        );
+    }
+
+    #[test]
+    fn sites_are_capped_per_room() {
+        let (world, memory) = world(3);
+        world.add_site(StructureType::Road, pos(30, 30));
+
+        place_sites(&world, &memory);
+
+        assert_eq!(
+            placed(&world).len() as u32,
+            constants::MAX_CONSTRUCTION_SITES - 1
+        );
+    }
+
+    #[test]
+    fn built_structures_count_against_the_limits() {
+        let (world, memory) = world(3);
+        let layout = memory.rooms[&pos(0, 0).room_name()].layout.clone().unwrap();
+        let planned = |structure_type| {
+            layout
+                .structures
+                .iter()
+                .filter(move |s| s.structure_type == structure_type)
+                .map(|s| pos(s.x, s.y))
+        };
+        for extension in planned(StructureType::Extension).take(10) {
+            world.add_structure(StructureType::Extension, extension, Some(Store::new(50)));
+        }
+        // Where the layout has none
+        world.add_structure(StructureType::Tower, pos(45, 5), Some(Store::new(1000)));
+
+        place_sites(&world, &memory);
+
+        // Neither more extensions nor a second tower, but the tower lets ramparts go up
+        assert_eq!(
+            placed(&world),
+            vec![
+                StructureType::Container,
+                StructureType::Rampart,
+                StructureType::Rampart
+            ]
+        );
+        assert_eq!(
+            world.state().intents[0],
+            Intent::CreateSite {
+                pos: planned(StructureType::Container).next().unwrap(),
+                structure_type: StructureType::Container
+            }
+        );
+    }
+
+    #[test]
+    fn busy_tiles_get_roads() {
+        let (world, mut memory) = world(constants::ROAD_RCL);
+        let room_memory = memory.rooms.get_mut(&pos(0, 0).room_name()).unwrap();
+        room_memory.layout = Some(Layout::default());
+        world.state_mut().rooms.values_mut().for_each(|r| {
+            r.terrain.set(6, 45, Terrain::Wall);
+        });
+        for _ in 0..constants::TRAFFIC_ROAD_STEPS {
+            for tile in &[pos(5, 45), pos(6, 45), pos(0, 45), pos(25, 25)] {
+                room_memory.traffic.record(0, *tile);
+            }
+        }
+        room_memory.traffic.record(0, pos(7, 45));
+
+        place_sites(&world, &memory);
+
+        // Not on walls, exits, the spawn or quiet tiles
+        assert_eq!(
+            world.state().intents,
+            vec![Intent::CreateSite {
+                pos: pos(5, 45),
+                structure_type: StructureType::Road
+            }]
+        );
+    }
+
+    #[test]
+    fn mining_spots_get_containers_and_extractors() {
+        let (world, memory) = world(6);
+        world.add_mineral(pos(10, 40), ResourceType::Hydrogen);
+        let mut memory = memory;
+        memory.rooms.clear();
+        plan_rooms(&world, &mut memory);
+        let layout = memory.rooms[&pos(0, 0).room_name()].layout.clone().unwrap();
+        // Everything but the mining spots is built already
+        for s in &layout.structures {
+            let mining = matches!(
+                s.structure_type,
+                StructureType::Container | StructureType::Extractor
+            );
+            if !mining && s.rcl <= 6 && pos(s.x, s.y) != pos(25, 25) {
+                world.add_structure(s.structure_type, pos(s.x, s.y), None);
+            }
+        }
+
+        place_sites(&world, &memory);
+
+        assert_eq!(
+            placed(&world),
+            vec![
+                StructureType::Container,
+                StructureType::Extractor,
+                StructureType::Container
+            ]
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use crate::core::constants;
+use crate::world::{RoomTerrain, StructureData, World};
+use screeps::{StructureType, Terrain};
+use std::collections::VecDeque;
+
+/// More than any cut through a room can cost
+const UNCUTTABLE: u32 = 10_000;
+
+/// How many hits a structure gets repaired up to. Walls and ramparts can hold far more
+/// than is worth it early on, so theirs grow with the controller level.
+pub fn hits_target(world: &dyn World, structure: &StructureData) -> u32 {
+    match structure.structure_type {
+        StructureType::Rampart | StructureType::Wall => {
+            let level = world
+                .room(structure.pos.room_name())
+                .and_then(|r| r.controller)
+                .map_or(0, |c| c.level);
+            constants::DEFENSE_HITS[level.min(8) as usize].min(structure.hits_max)
+        }
+        _ => structure.hits_max,
+    }
+}
+
+/// Whether a tile is too close to the exits for anything but roads and containers
+fn near_exit(x: u32, y: u32) -> bool {
+    x <= 1 || y <= 1 || x >= constants::ROOM_X - 2 || y >= constants::ROOM_Y - 2
+}
+
+struct Edge {
+    to: usize,
+    capacity: u32,
+}
+
+/// A flow network solved with Dinic's algorithm
+struct Network {
+    edges: Vec<Edge>,
+    outgoing: Vec<Vec<usize>>,
+    level: Vec<u32>,
+    next: Vec<usize>,
+}
+
+impl Network {
+    fn new(nodes: usize) -> Self {
+        Self {
+            edges: Vec::new(),
+            outgoing: vec![Vec::new(); nodes],
+            level: vec![0; nodes],
+            next: vec![0; nodes],
+        }
+    }
+
+    fn connect(&mut self, from: usize, to: usize, capacity: u32) {
+        self.outgoing[from].push(self.edges.len());
+        self.edges.push(Edge { to, capacity });
+        self.outgoing[to].push(self.edges.len());
+        self.edges.push(Edge {
+            to: from,
+            capacity: 0,
+        });
+    }
+
+    /// Levels every node by its distance from `source` over the edges with capacity
+    /// left, `u32::MAX` for the ones out of reach.
+    fn reach(&mut self, source: usize) {
+        self.level.iter_mut().for_each(|l| *l = u32::MAX);
+        self.level[source] = 0;
+        let mut queue = VecDeque::from(vec![source]);
+        while let Some(node) = queue.pop_front() {
+            for &e in &self.outgoing[node] {
+                let to = self.edges[e].to;
+                if self.edges[e].capacity > 0 && self.level[to] == u32::MAX {
+                    self.level[to] = self.level[node] + 1;
+                    queue.push_back(to);
+                }
+            }
+        }
+    }
+
+    fn push(&mut self, node: usize, sink: usize, flow: u32) -> u32 {
+        if node == sink {
+            return flow;
+        }
+        while self.next[node] < self.outgoing[node].len() {
+            let e = self.outgoing[node][self.next[node]];
+            let to = self.edges[e].to;
+            if self.edges[e].capacity > 0 && self.level[to] == self.level[node] + 1 {
+                let pushed = self.push(to, sink, flow.min(self.edges[e].capacity));
+                if pushed > 0 {
+                    self.edges[e].capacity -= pushed;
+                    self.edges[e ^ 1].capacity += pushed;
+                    return pushed;
+                }
+            }
+            self.next[node] += 1;
+        }
+        0
+    }
+
+    fn saturate(&mut self, source: usize, sink: usize) {
+        loop {
+            self.reach(source);
+            if self.level[sink] == u32::MAX {
+                return;
+            }
+            self.next.iter_mut().for_each(|n| *n = 0);
+            while self.push(source, sink, UNCUTTABLE) > 0 {}
+        }
+    }
+}
+
+/// The fewest tiles to wall off so nothing can walk from the exits to the protected
+/// tiles. Every tile is a node split in two, joined by an edge that costs one tile to
+/// cut, so the minimum cut of the network is the cheapest perimeter.
+pub fn min_cut(terrain: &RoomTerrain, protected: &[(u32, u32)]) -> Vec<(u32, u32)> {
+    let tiles = (constants::ROOM_X * constants::ROOM_Y) as usize;
+    let (source, sink) = (2 * tiles, 2 * tiles + 1);
+    let index = |x: u32, y: u32| (y * constants::ROOM_X + x) as usize;
+    let walkable = |x: u32, y: u32| terrain.get(x, y) != Terrain::Wall;
+
+    let mut network = Network::new(2 * tiles + 2);
+    for y in 0..constants::ROOM_Y {
+        for x in 0..constants::ROOM_X {
+            if !walkable(x, y) {
+                continue;
+            }
+            let (inside, outside) = (2 * index(x, y), 2 * index(x, y) + 1);
+
+            let kept = protected.contains(&(x, y));
+            if near_exit(x, y) {
+                network.connect(outside, sink, UNCUTTABLE);
+            } else if kept {
+                network.connect(source, inside, UNCUTTABLE);
+            }
+            let cost = if near_exit(x, y) || kept {
+                UNCUTTABLE
+            } else {
+                1
+            };
+            network.connect(inside, outside, cost);
+
+            let xs = x.saturating_sub(1)..=(x + 1).min(constants::ROOM_X - 1);
+            for nx in xs {
+                for ny in y.saturating_sub(1)..=(y + 1).min(constants::ROOM_Y - 1) {
+                    if (nx, ny) != (x, y) && walkable(nx, ny) {
+                        network.connect(outside, 2 * index(nx, ny), UNCUTTABLE);
+                    }
+                }
+            }
+        }
+    }
+
+    network.saturate(source, sink);
+    network.reach(source);
+    (0..constants::ROOM_Y)
+        .flat_map(|y| (0..constants::ROOM_X).map(move |x| (x, y)))
+        .filter(|&(x, y)| {
+            let i = index(x, y);
+            network.level[2 * i] != u32::MAX && network.level[2 * i + 1] == u32::MAX
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{empty_room, pos};
+
+    #[test]
+    fn cuts_go_through_the_narrowest_gap() {
+        let mut terrain = RoomTerrain::default();
+        // A walled box from (10, 10) to (30, 30) with a gap of two tiles on its left
+        for i in 10..=30 {
+            for &(x, y) in &[(i, 10), (i, 30), (10, i), (30, i)] {
+                terrain.set(x, y, Terrain::Wall);
+            }
+        }
+        terrain.set(10, 19, Terrain::Plain);
+        terrain.set(10, 20, Terrain::Plain);
+
+        assert_eq!(min_cut(&terrain, &[(20, 20)]), vec![(10, 19), (10, 20)]);
+    }
+
+    #[test]
+    fn open_rooms_get_a_ring() {
+        let cut = min_cut(&RoomTerrain::default(), &[(25, 25)]);
+
+        // The smallest ring around a single tile
+        assert_eq!(cut.len(), 8);
+        assert!(cut
+            .iter()
+            .all(|&(x, y)| pos(x, y).get_range_to(&pos(25, 25)) == 1));
+    }
+
+    #[test]
+    fn rampart_targets_grow_with_the_controller() {
+        let world = empty_room();
+        world.add_controller(pos(40, 40), 3);
+        let rampart = world.add_structure(StructureType::Rampart, pos(20, 20), None);
+        let road = world.add_structure(StructureType::Road, pos(21, 20), None);
+
+        let rampart = world.get_structure(rampart).unwrap();
+        assert_eq!(hits_target(&world, &rampart), constants::DEFENSE_HITS[3]);
+        world.state_mut().rooms.values_mut().for_each(|r| {
+            r.controller.as_mut().unwrap().level = 8;
+        });
+        assert_eq!(hits_target(&world, &rampart), constants::DEFENSE_HITS[8]);
+
+        let road = world.get_structure(road).unwrap();
+        assert_eq!(hits_target(&world, &road), road.hits_max);
+    }
+}
@@ -0,0 +1,727 @@
+use super::min_cut;
+use crate::core::{constants, Memory};
+use crate::world::{RoomTerrain, World};
+use log::*;
+use screeps::{pathfinder::LocalCostMatrix, Position, RoomName, StructureType, Terrain};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("No controller of ours in {0}")]
+    NoController(RoomName),
+    #[error("No space for a base in {0}")]
+    NoSpace(RoomName),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// How far the anchor of a new base has to be from walls and exits, so the core fits
+/// around it.
+const ANCHOR_SPACE: u32 = 3;
+
+/// Structures can't go on the exits or right next to them.
+const MARGIN: u32 = 2;
+
+/// How far ramparts stay from the base buildings, out of the range of anything shooting
+/// from outside.
+const RAMPART_RANGE: u32 = 3;
+
+/// Ten labs in a 4x4 square around a diagonal road, all within range of the first two.
+const LAB_STAMP: [(u32, u32); 10] = [
+    (2, 1),
+    (1, 2),
+    (1, 0),
+    (2, 0),
+    (0, 1),
+    (3, 1),
+    (0, 2),
+    (3, 2),
+    (1, 3),
+    (2, 3),
+];
+const LAB_ROADS: [(u32, u32); 4] = [(0, 0), (1, 1), (2, 2), (3, 3)];
+
+/// Translates `CONTROLLER_STRUCTURES`: how many structures of a type a controller of the
+/// given level allows.
+pub fn controller_structures(structure_type: StructureType, rcl: u32) -> u32 {
+    use StructureType::*;
+
+    let limits = match structure_type {
+        Spawn => [0, 1, 1, 1, 1, 1, 1, 2, 3],
+        Extension => [0, 0, 5, 10, 20, 30, 40, 50, 60],
+        Road => [2500; 9],
+        Wall | Rampart => [0, 0, 2500, 2500, 2500, 2500, 2500, 2500, 2500],
+        Link => [0, 0, 0, 0, 0, 2, 3, 4, 6],
+        Storage => [0, 0, 0, 0, 1, 1, 1, 1, 1],
+        Tower => [0, 0, 0, 1, 1, 2, 2, 3, 6],
+        Observer | PowerSpawn | Nuker => [0, 0, 0, 0, 0, 0, 0, 0, 1],
+        Extractor | Terminal => [0, 0, 0, 0, 0, 0, 1, 1, 1],
+        Lab => [0, 0, 0, 0, 0, 0, 3, 6, 10],
+        Container => [5; 9],
+        Factory => [0, 0, 0, 0, 0, 0, 0, 1, 1],
+        KeeperLair | Portal | Controller | PowerBank | InvaderCore => [0; 9],
+    };
+    limits[rcl.min(8) as usize]
+}
+
+/// A structure of the layout and the controller level it can be built from.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PlannedStructure {
+    pub rcl: u32,
+    #[serde(rename = "type")]
+    pub structure_type: StructureType,
+    pub x: u32,
+    pub y: u32,
+}
+
+/// Where everything of a fully built room goes, in the order it should be built.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Layout {
+    pub structures: Vec<PlannedStructure>,
+}
+
+/// Plans the rooms of ours that don't have a layout yet.
+pub fn plan_rooms(world: &dyn World, memory: &mut Memory) {
+    for room in world.rooms() {
+        let owned = world
+            .room(room)
+            .and_then(|r| r.controller)
+            .is_some_and(|c| c.my);
+        let planned = memory.rooms.get(&room).is_some_and(|r| r.layout.is_some());
+        if !owned || planned {
+            continue;
+        }
+
+        match plan_room(world, room) {
+            Ok(layout) => {
+                info!("Planned {} structures in {}", layout.structures.len(), room);
+                memory.rooms.entry(room).or_default().layout = Some(layout);
+            }
+            Err(e) => warn!("{}", e),
+        }
+    }
+}
+
+/// Lays out the base for RCL8. Buildings go on a checkerboard around the first spawn,
+/// or the best open spot when there's none yet, with roads on the tiles in between.
+pub fn plan_room(world: &dyn World, room: RoomName) -> Result<Layout> {
+    let controller = world
+        .room(room)
+        .and_then(|r| r.controller)
+        .ok_or(Error::NoController(room))?;
+    let sources: Vec<_> = world.sources(room).into_iter().map(|s| s.pos).collect();
+    let minerals: Vec<_> = world.minerals(room).into_iter().map(|m| m.pos).collect();
+
+    let mut planner = Planner::new(world.terrain(room));
+    for pos in sources.iter().chain(&minerals).chain(Some(&controller.pos)) {
+        planner.reserve(pos.x(), pos.y());
+    }
+
+    let spawn = world
+        .spawns()
+        .into_iter()
+        .find(|s| s.pos.room_name() == room)
+        .map(|s| (s.pos.x(), s.pos.y()));
+    let targets: Vec<_> = sources.iter().chain(Some(&controller.pos)).collect();
+    let anchor = spawn
+        .or_else(|| planner.find_anchor(&targets))
+        .ok_or(Error::NoSpace(room))?;
+    planner.anchor(anchor);
+
+    planner.add(anchor, StructureType::Spawn);
+    // The storage link has to be told apart from the others by its range to the storage
+    if let Some((sx, sy)) = planner.add_near_anchor(StructureType::Storage) {
+        planner.add_closest(StructureType::Link, |(x, y)| {
+            x.max(sx) - x.min(sx) <= constants::LINK_RANGE
+                && y.max(sy) - y.min(sy) <= constants::LINK_RANGE
+        });
+    }
+
+    planner.add_controller_link(controller.pos);
+    for source in &sources {
+        planner.add_mining_spot(*source, constants::SOURCE_CONTAINER_RCL, true);
+    }
+    for mineral in &minerals {
+        planner.add((mineral.x(), mineral.y()), StructureType::Extractor);
+        planner.add_mining_spot(*mineral, constants::EXTRACTOR_RCL, false);
+    }
+
+    for structure_type in &[
+        StructureType::Terminal,
+        StructureType::Spawn,
+        StructureType::Spawn,
+        StructureType::Factory,
+        StructureType::PowerSpawn,
+    ] {
+        planner.add_near_anchor(*structure_type);
+    }
+    for _ in 0..controller_structures(StructureType::Tower, 8) {
+        planner.add_near_anchor(StructureType::Tower);
+    }
+    planner.add_labs();
+    for _ in 0..controller_structures(StructureType::Extension, 8) {
+        planner.add_near_anchor(StructureType::Extension);
+    }
+    planner.add_near_anchor(StructureType::Nuker);
+    planner.add_near_anchor(StructureType::Observer);
+    planner.add_roads();
+
+    let from = Position::new(anchor.0, anchor.1, room);
+    for source in &sources {
+        planner.add_path(
+            world,
+            from,
+            *source,
+            constants::RANGE_HARVEST,
+            constants::ROAD_RCL,
+        );
+    }
+    planner.add_path(
+        world,
+        from,
+        controller.pos,
+        constants::RANGE_UPGRADE_CONTROLLER,
+        constants::ROAD_RCL,
+    );
+    for mineral in &minerals {
+        planner.add_path(
+            world,
+            from,
+            *mineral,
+            constants::RANGE_HARVEST,
+            constants::EXTRACTOR_RCL,
+        );
+    }
+    planner.add_ramparts();
+
+    Ok(planner.layout)
+}
+
+fn index(x: u32, y: u32) -> usize {
+    (y * constants::ROOM_X + x) as usize
+}
+
+fn neighbours(x: u32, y: u32) -> impl Iterator<Item = (u32, u32)> {
+    let xs = x.saturating_sub(1)..=(x + 1).min(constants::ROOM_X - 1);
+    xs.flat_map(move |nx| {
+        (y.saturating_sub(1)..=(y + 1).min(constants::ROOM_Y - 1)).map(move |ny| (nx, ny))
+    })
+    .filter(move |&n| n != (x, y))
+}
+
+/// Steps from the closest start to every tile, moving only through passable ones.
+/// Unreachable tiles are `u32::MAX` away.
+fn flood(starts: &[(u32, u32)], passable: impl Fn(u32, u32) -> bool) -> Vec<u32> {
+    let mut distance = vec![u32::MAX; (constants::ROOM_X * constants::ROOM_Y) as usize];
+    let mut queue = VecDeque::new();
+    for &(x, y) in starts {
+        distance[index(x, y)] = 0;
+        queue.push_back((x, y));
+    }
+
+    while let Some((x, y)) = queue.pop_front() {
+        let next = distance[index(x, y)] + 1;
+        for (nx, ny) in neighbours(x, y) {
+            if passable(nx, ny) && distance[index(nx, ny)] == u32::MAX {
+                distance[index(nx, ny)] = next;
+                queue.push_back((nx, ny));
+            }
+        }
+    }
+    distance
+}
+
+struct Planner {
+    /// Steps from the anchor
+    distance: Vec<u32>,
+    counts: HashMap<StructureType, u32>,
+    /// Tiles the roads serve
+    base: Vec<(u32, u32)>,
+    layout: Layout,
+    /// Tiles kept free around sources, minerals and the controller
+    reserved: Vec<bool>,
+    /// Whether buildings go on odd or even tiles
+    parity: u32,
+    taken: Vec<bool>,
+    terrain: RoomTerrain,
+}
+
+impl Planner {
+    fn new(terrain: RoomTerrain) -> Self {
+        let tiles = (constants::ROOM_X * constants::ROOM_Y) as usize;
+        Self {
+            distance: vec![u32::MAX; tiles],
+            counts: HashMap::new(),
+            base: Vec::new(),
+            layout: Layout::default(),
+            reserved: vec![false; tiles],
+            parity: 0,
+            taken: vec![false; tiles],
+            terrain,
+        }
+    }
+
+    fn walkable(&self, x: u32, y: u32) -> bool {
+        self.terrain.get(x, y) != Terrain::Wall
+    }
+
+    fn inside(x: u32, y: u32) -> bool {
+        (MARGIN..constants::ROOM_X - MARGIN).contains(&x)
+            && (MARGIN..constants::ROOM_Y - MARGIN).contains(&y)
+    }
+
+    fn free(&self, x: u32, y: u32) -> bool {
+        Self::inside(x, y) && self.walkable(x, y) && !self.taken[index(x, y)]
+    }
+
+    fn buildable(&self, x: u32, y: u32) -> bool {
+        self.free(x, y) && !self.reserved[index(x, y)]
+    }
+
+    fn reserve(&mut self, x: u32, y: u32) {
+        self.reserved[index(x, y)] = true;
+        for (nx, ny) in neighbours(x, y) {
+            self.reserved[index(nx, ny)] = true;
+        }
+    }
+
+    /// The open tile closest to the targets on foot
+    fn find_anchor(&self, targets: &[&Position]) -> Option<(u32, u32)> {
+        let blocked: Vec<_> = (0..constants::ROOM_Y)
+            .flat_map(|y| (0..constants::ROOM_X).map(move |x| (x, y)))
+            .filter(|&(x, y)| !self.buildable(x, y))
+            .collect();
+        let space = flood(&blocked, |_, _| true);
+        let distances: Vec<_> = targets
+            .iter()
+            .map(|t| flood(&[(t.x(), t.y())], |x, y| self.walkable(x, y)))
+            .collect();
+
+        (0..constants::ROOM_Y)
+            .flat_map(|y| (0..constants::ROOM_X).map(move |x| (x, y)))
+            .filter(|&(x, y)| space[index(x, y)] >= ANCHOR_SPACE)
+            .filter_map(|(x, y)| {
+                let steps = distances.iter().map(|d| d[index(x, y)]);
+                if steps.clone().any(|d| d == u32::MAX) {
+                    None
+                } else {
+                    Some((steps.sum::<u32>(), (x, y)))
+                }
+            })
+            .min()
+            .map(|(_, tile)| tile)
+    }
+
+    fn anchor(&mut self, (x, y): (u32, u32)) {
+        self.parity = (x + y) % 2;
+        self.distance = flood(&[(x, y)], |x, y| self.walkable(x, y));
+    }
+
+    /// Adds the next structure of its type, to be built once the controller allows it
+    fn add(&mut self, tile: (u32, u32), structure_type: StructureType) {
+        let count = self.counts.entry(structure_type).or_insert(0);
+        match (0..=8).find(|&rcl| controller_structures(structure_type, rcl) > *count) {
+            Some(rcl) => {
+                *count += 1;
+                self.add_at(tile, structure_type, rcl);
+            }
+            None => warn!("No {:?} left to plan", structure_type),
+        }
+    }
+
+    fn add_at(&mut self, (x, y): (u32, u32), structure_type: StructureType, rcl: u32) {
+        self.taken[index(x, y)] = true;
+        self.layout.structures.push(PlannedStructure {
+            rcl,
+            structure_type,
+            x,
+            y,
+        });
+    }
+
+    /// Takes the closest free building tile that can be reached from a road
+    fn add_near_anchor(&mut self, structure_type: StructureType) -> Option<(u32, u32)> {
+        self.add_closest(structure_type, |_| true)
+    }
+
+    /// Like `add_near_anchor`, but only on the tiles `allowed` lets through
+    fn add_closest(
+        &mut self,
+        structure_type: StructureType,
+        allowed: impl Fn((u32, u32)) -> bool,
+    ) -> Option<(u32, u32)> {
+        let tile = (0..constants::ROOM_Y)
+            .flat_map(|y| (0..constants::ROOM_X).map(move |x| (x, y)))
+            .filter(|&(x, y)| (x + y) % 2 == self.parity && self.buildable(x, y))
+            .filter(|&tile| allowed(tile))
+            .filter(|&(x, y)| self.distance[index(x, y)] != u32::MAX)
+            .filter(|&(x, y)| {
+                neighbours(x, y).any(|(nx, ny)| (nx + ny) % 2 != self.parity && self.free(nx, ny))
+            })
+            .min_by_key(|&(x, y)| self.distance[index(x, y)]);
+
+        match tile {
+            Some(tile) => {
+                self.base.push(tile);
+                self.add(tile, structure_type);
+            }
+            None => warn!("No space left for a {:?}", structure_type),
+        }
+        tile
+    }
+
+    /// A link within `LINK_RANGE` of the controller, so upgraders next to it can reach
+    /// both
+    fn add_controller_link(&mut self, controller: Position) {
+        let (cx, cy) = (controller.x(), controller.y());
+        let range = constants::LINK_RANGE;
+        let tile = (cy.saturating_sub(range)..=cy + range)
+            .flat_map(|y| (cx.saturating_sub(range)..=cx + range).map(move |x| (x, y)))
+            .filter(|&(x, y)| self.buildable(x, y))
+            .min_by_key(|&(x, y)| self.distance[index(x, y)]);
+
+        if let Some(tile) = tile {
+            self.add(tile, StructureType::Link);
+        }
+    }
+
+    /// A container for the miner to stand on next to the deposit, and optionally a link
+    /// next to the container
+    fn add_mining_spot(&mut self, deposit: Position, rcl: u32, link: bool) {
+        let container = neighbours(deposit.x(), deposit.y())
+            .filter(|&(x, y)| self.free(x, y))
+            .min_by_key(|&(x, y)| self.distance[index(x, y)]);
+        let container = match container {
+            Some(tile) => tile,
+            None => return,
+        };
+        self.add_at(container, StructureType::Container, rcl);
+
+        if link {
+            let tile = neighbours(container.0, container.1)
+                .filter(|&(x, y)| self.free(x, y))
+                .min_by_key(|&(x, y)| (self.reserved[index(x, y)], self.distance[index(x, y)]));
+            if let Some(tile) = tile {
+                self.add(tile, StructureType::Link);
+            }
+        }
+    }
+
+    /// Roads along the cheapest path around the planned buildings, sharing the roads
+    /// planned so far
+    fn add_path(&mut self, world: &dyn World, from: Position, to: Position, range: u32, rcl: u32) {
+        let mut costs = LocalCostMatrix::new();
+        for s in &self.layout.structures {
+            let cost = match s.structure_type {
+                StructureType::Road => 1,
+                StructureType::Container => continue,
+                _ => u8::MAX,
+            };
+            costs.set(s.x as u8, s.y as u8, cost);
+        }
+
+        for pos in world.find_path(from, to, range, &costs) {
+            if self.free(pos.x(), pos.y()) {
+                self.add_at((pos.x(), pos.y()), StructureType::Road, rcl);
+            }
+        }
+    }
+
+    /// Ramparts on the shortest perimeter between the exits and the tiles around the base
+    /// buildings, once the first tower can defend them
+    fn add_ramparts(&mut self) {
+        let mut protected: Vec<_> = self
+            .layout
+            .structures
+            .iter()
+            .filter(|s| {
+                !matches!(
+                    s.structure_type,
+                    StructureType::Container
+                        | StructureType::Extractor
+                        | StructureType::Link
+                        | StructureType::Road
+                )
+            })
+            .flat_map(|s| {
+                let xs = s.x.saturating_sub(RAMPART_RANGE)..=s.x + RAMPART_RANGE;
+                let ys = s.y.saturating_sub(RAMPART_RANGE)..=s.y + RAMPART_RANGE;
+                xs.flat_map(move |x| ys.clone().map(move |y| (x, y)))
+            })
+            .filter(|&(x, y)| x < constants::ROOM_X && y < constants::ROOM_Y)
+            .collect();
+        protected.sort_unstable();
+        protected.dedup();
+
+        let rcl = (0..=8)
+            .find(|&rcl| controller_structures(StructureType::Tower, rcl) > 0)
+            .unwrap_or(8);
+        for tile in min_cut(&self.terrain, &protected) {
+            self.add_at(tile, StructureType::Rampart, rcl);
+        }
+    }
+
+    /// Puts the lab stamp where its centre is closest to the anchor
+    fn add_labs(&mut self) {
+        let origin = (0..constants::ROOM_Y)
+            .flat_map(|y| (0..constants::ROOM_X).map(move |x| (x, y)))
+            .filter(|&(x, y)| (x + y) % 2 != self.parity)
+            .filter(|&(x, y)| {
+                LAB_STAMP
+                    .iter()
+                    .all(|(dx, dy)| self.buildable(x + dx, y + dy))
+                    && LAB_ROADS.iter().all(|(dx, dy)| self.free(x + dx, y + dy))
+            })
+            .filter(|&(x, y)| self.distance[index(x + 1, y + 1)] != u32::MAX)
+            .min_by_key(|&(x, y)| self.distance[index(x + 1, y + 1)]);
+
+        match origin {
+            Some((x, y)) => {
+                for (dx, dy) in &LAB_STAMP {
+                    self.base.push((x + dx, y + dy));
+                    self.add((x + dx, y + dy), StructureType::Lab);
+                }
+            }
+            None => warn!("No space left for the labs"),
+        }
+    }
+
+    /// Roads on the free tiles next to the base, built along with the first structure
+    /// they lead to
+    fn add_roads(&mut self) {
+        let rcls: HashMap<_, _> = self
+            .layout
+            .structures
+            .iter()
+            .map(|s| ((s.x, s.y), s.rcl))
+            .collect();
+        let mut roads: Vec<((u32, u32), u32)> = Vec::new();
+        for &(x, y) in &self.base {
+            for (nx, ny) in neighbours(x, y) {
+                if (nx + ny) % 2 == self.parity || !self.buildable(nx, ny) {
+                    continue;
+                }
+                let rcl = rcls[&(x, y)];
+                match roads.iter_mut().find(|(tile, _)| *tile == (nx, ny)) {
+                    Some((_, r)) => *r = (*r).min(rcl),
+                    None => roads.push(((nx, ny), rcl)),
+                }
+            }
+        }
+
+        roads.sort_by_key(|&(tile, rcl)| (rcl, self.distance[index(tile.0, tile.1)]));
+        for (tile, rcl) in roads {
+            self.add_at(tile, StructureType::Road, rcl);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::creeps::{link_kind, LinkKind};
+    use crate::world::{empty_room, pos, FakeWorld};
+    use screeps::ResourceType;
+
+    fn mineral_room() -> FakeWorld {
+        let world = empty_room();
+        world.add_controller(pos(40, 40), 1);
+        world.add_source(pos(10, 10));
+        world.add_source(pos(40, 10));
+        world.add_mineral(pos(10, 40), ResourceType::Hydrogen);
+        world
+    }
+
+    fn count(layout: &Layout, structure_type: StructureType, rcl: u32) -> u32 {
+        layout
+            .structures
+            .iter()
+            .filter(|s| s.structure_type == structure_type && s.rcl <= rcl)
+            .count() as u32
+    }
+
+    #[test]
+    fn layouts_hold_everything_rcl8_allows() {
+        let world = mineral_room();
+        let layout = plan_room(&world, pos(0, 0).room_name()).unwrap();
+
+        for structure_type in &[
+            StructureType::Spawn,
+            StructureType::Extension,
+            StructureType::Storage,
+            StructureType::Tower,
+            StructureType::Observer,
+            StructureType::PowerSpawn,
+            StructureType::Extractor,
+            StructureType::Lab,
+            StructureType::Terminal,
+            StructureType::Nuker,
+            StructureType::Factory,
+        ] {
+            assert_eq!(
+                count(&layout, *structure_type, 8),
+                controller_structures(*structure_type, 8),
+                "{:?}",
+                structure_type
+            );
+        }
+        // Storage, controller and both sources
+        assert_eq!(count(&layout, StructureType::Link, 8), 4);
+        assert_eq!(count(&layout, StructureType::Container, 8), 3);
+        assert!(count(&layout, StructureType::Road, 8) > 0);
+
+        for rcl in 1..=8 {
+            for s in &layout.structures {
+                let built = count(&layout, s.structure_type, rcl);
+                assert!(built <= controller_structures(s.structure_type, rcl));
+            }
+        }
+
+        let mut tiles: Vec<_> = layout
+            .structures
+            .iter()
+            .filter(|s| s.structure_type != StructureType::Rampart)
+            .map(|s| (s.x, s.y))
+            .collect();
+        tiles.sort_unstable();
+        let planned = tiles.len();
+        tiles.dedup();
+        assert_eq!(tiles.len(), planned);
+    }
+
+    #[test]
+    fn ramparts_keep_their_distance_from_the_base() {
+        let world = mineral_room();
+        let layout = plan_room(&world, pos(0, 0).room_name()).unwrap();
+
+        let ramparts: Vec<_> = layout
+            .structures
+            .iter()
+            .filter(|s| s.structure_type == StructureType::Rampart)
+            .collect();
+        assert!(!ramparts.is_empty());
+        for rampart in ramparts {
+            assert_eq!(rampart.rcl, 3);
+            for s in &layout.structures {
+                if matches!(s.structure_type, StructureType::Spawn | StructureType::Lab) {
+                    assert!(pos(s.x, s.y).get_range_to(&pos(rampart.x, rampart.y)) > RAMPART_RANGE);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn links_can_be_told_apart() {
+        let world = mineral_room();
+        let layout = plan_room(&world, pos(0, 0).room_name()).unwrap();
+        for s in &layout.structures {
+            if matches!(
+                s.structure_type,
+                StructureType::Link | StructureType::Storage
+            ) {
+                world.add_structure(s.structure_type, pos(s.x, s.y), None);
+            }
+        }
+
+        let mut kinds: Vec<_> = world
+            .structures(pos(0, 0).room_name())
+            .iter()
+            .filter(|s| s.structure_type == StructureType::Link)
+            .map(|l| link_kind(&world, l))
+            .collect();
+        kinds.sort();
+
+        assert_eq!(
+            kinds,
+            vec![
+                LinkKind::Source,
+                LinkKind::Source,
+                LinkKind::Controller,
+                LinkKind::Storage
+            ]
+        );
+    }
+
+    #[test]
+    fn labs_are_in_range_of_the_first_two() {
+        let world = mineral_room();
+        let layout = plan_room(&world, pos(0, 0).room_name()).unwrap();
+
+        let labs: Vec<_> = layout
+            .structures
+            .iter()
+            .filter(|s| s.structure_type == StructureType::Lab)
+            .map(|s| pos(s.x, s.y))
+            .collect();
+        for lab in &labs {
+            assert!(lab.get_range_to(&labs[0]) <= 2);
+            assert!(lab.get_range_to(&labs[1]) <= 2);
+        }
+        assert_eq!(
+            layout
+                .structures
+                .iter()
+                .filter(|s| s.structure_type == StructureType::Lab)
+                .map(|s| s.rcl)
+                .take(3)
+                .collect::<Vec<_>>(),
+            vec![6, 6, 6]
+        );
+    }
+
+    #[test]
+    fn roads_lead_to_sources_and_controller() {
+        let world = mineral_room();
+        let layout = plan_room(&world, pos(0, 0).room_name()).unwrap();
+
+        let roads: Vec<_> = layout
+            .structures
+            .iter()
+            .filter(|s| s.structure_type == StructureType::Road && s.rcl == constants::ROAD_RCL)
+            .map(|s| pos(s.x, s.y))
+            .collect();
+        for (target, range) in &[(pos(10, 10), 2), (pos(40, 10), 2), (pos(40, 40), 4)] {
+            assert!(roads.iter().any(|r| r.get_range_to(target) <= *range));
+        }
+        assert!(layout
+            .structures
+            .iter()
+            .any(|s| s.structure_type == StructureType::Road
+                && s.rcl == constants::EXTRACTOR_RCL
+                && pos(s.x, s.y).get_range_to(&pos(10, 40)) <= 2));
+    }
+
+    #[test]
+    fn existing_spawns_anchor_the_base() {
+        let world = mineral_room();
+        world.add_spawn("Spawn1", pos(30, 20));
+
+        let layout = plan_room(&world, pos(0, 0).room_name()).unwrap();
+
+        assert_eq!(
+            layout.structures[0],
+            PlannedStructure {
+                rcl: 1,
+                structure_type: StructureType::Spawn,
+                x: 30,
+                y: 20
+            }
+        );
+    }
+
+    #[test]
+    fn owned_rooms_get_planned_once() {
+        let world = mineral_room();
+        let mut memory = Memory::default();
+
+        plan_rooms(&world, &mut memory);
+        let layout = memory.rooms[&pos(0, 0).room_name()].layout.clone().unwrap();
+        assert!(!layout.structures.is_empty());
+
+        world.add_spawn("Spawn1", pos(30, 20));
+        plan_rooms(&world, &mut memory);
+        assert_eq!(memory.rooms[&pos(0, 0).room_name()].layout, Some(layout));
+    }
+}
@@ -0,0 +1,9 @@
+pub mod construction;
+pub mod defense;
+pub mod layout;
+pub mod traffic;
+
+pub use construction::*;
+pub use defense::*;
+pub use layout::*;
+pub use traffic::*;
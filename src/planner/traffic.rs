@@ -0,0 +1,74 @@
+use crate::core::constants;
+use screeps::Position;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// How often creeps stepped onto each tile of a room, over the current window and the
+/// one before it.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Traffic {
+    current: BTreeMap<u32, u32>,
+    previous: BTreeMap<u32, u32>,
+    /// When the current window started
+    since: u32,
+}
+
+impl Traffic {
+    pub fn record(&mut self, time: u32, pos: Position) {
+        let elapsed = time.saturating_sub(self.since);
+        if elapsed >= constants::TRAFFIC_WINDOW {
+            self.previous = std::mem::take(&mut self.current);
+            if elapsed >= 2 * constants::TRAFFIC_WINDOW {
+                self.previous.clear();
+            }
+            self.since = time;
+        }
+
+        *self
+            .current
+            .entry(pos.y() * constants::ROOM_X + pos.x())
+            .or_insert(0) += 1;
+    }
+
+    /// The tiles stepped on at least `steps` times, busiest first
+    pub fn busy(&self, steps: u32) -> Vec<(u32, u32)> {
+        let mut totals = self.previous.clone();
+        for (tile, count) in &self.current {
+            *totals.entry(*tile).or_insert(0) += count;
+        }
+
+        let mut busy: Vec<_> = totals.into_iter().filter(|(_, c)| *c >= steps).collect();
+        busy.sort_by_key(|&(tile, count)| (std::cmp::Reverse(count), tile));
+        busy.into_iter()
+            .map(|(tile, _)| (tile % constants::ROOM_X, tile / constants::ROOM_X))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::pos;
+
+    #[test]
+    fn traffic_rolls_over_two_windows() {
+        let mut traffic = Traffic::default();
+        for _ in 0..3 {
+            traffic.record(0, pos(10, 20));
+        }
+        traffic.record(1, pos(11, 20));
+        traffic.record(1, pos(11, 20));
+        assert_eq!(traffic.busy(2), vec![(10, 20), (11, 20)]);
+
+        // The old steps still count in the next window
+        traffic.record(constants::TRAFFIC_WINDOW, pos(11, 20));
+        traffic.record(constants::TRAFFIC_WINDOW, pos(11, 20));
+        assert_eq!(traffic.busy(3), vec![(11, 20), (10, 20)]);
+
+        traffic.record(2 * constants::TRAFFIC_WINDOW, pos(12, 20));
+        assert_eq!(traffic.busy(2), vec![(11, 20)]);
+        traffic.record(4 * constants::TRAFFIC_WINDOW, pos(12, 20));
+        assert_eq!(traffic.busy(1), vec![(12, 20)]);
+    }
+}
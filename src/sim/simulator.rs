@@ -0,0 +1,915 @@
+use crate::core::{constants, game_loop, transfer_cost};
+use crate::creeps::{boosted_parts, Regulator};
+use crate::world::{
+    reaction_product, renew_cost, renew_ticks, CreepData, FakeState, FakeWorld, Intent,
+    RemainsData, SiteData, Store,
+};
+use screeps::{
+    constants::*, Part, Position, RawObjectId, ResourceType, RoomName, StructureType, Terrain,
+};
+use std::collections::{BTreeMap, HashMap};
+
+struct Spawning {
+    body: Vec<Part>,
+    name: String,
+    remaining: u32,
+}
+
+/// Advances a [`FakeWorld`] tick by tick: runs the game loop against it, then applies
+/// the recorded intents and the passive game mechanics (regeneration, aging, fatigue,
+/// spawning, decay). Everything is resolved in a fixed order, so runs are deterministic.
+pub struct Simulator {
+    pub world: FakeWorld,
+    regeneration: HashMap<RawObjectId, u32>,
+    regulators: BTreeMap<RoomName, Regulator>,
+    spawning: HashMap<String, Spawning>,
+}
+
+fn count(body: &[Part], part: Part) -> u32 {
+    body.iter().filter(|p| **p == part).count() as u32
+}
+
+fn is_blocked(state: &FakeState, pos: Position) -> bool {
+    let terrain = state
+        .rooms
+        .get(&pos.room_name())
+        .map(|r| r.terrain.get(pos.x(), pos.y()))
+        .unwrap_or(Terrain::Wall);
+
+    terrain == Terrain::Wall
+        || state.creeps.values().any(|c| c.pos == pos)
+        || state.sources.iter().any(|s| s.pos == pos)
+        || state
+            .rooms
+            .values()
+            .filter_map(|r| r.controller.as_ref())
+            .any(|c| c.pos == pos)
+        || state.structures.iter().any(|s| {
+            s.pos == pos
+                && s.structure_type != StructureType::Road
+                && s.structure_type != StructureType::Container
+                && s.structure_type != StructureType::Rampart
+        })
+}
+
+fn neighbours(pos: Position) -> impl Iterator<Item = Position> {
+    (-1..=1)
+        .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+        .filter(|&(dx, dy)| dx != 0 || dy != 0)
+        .filter_map(move |(dx, dy)| {
+            let x = pos.x() as i32 + dx;
+            let y = pos.y() as i32 + dy;
+            if x < 0 || y < 0 || x >= constants::ROOM_X as i32 || y >= constants::ROOM_Y as i32 {
+                None
+            } else {
+                Some(Position::new(x as u32, y as u32, pos.room_name()))
+            }
+        })
+}
+
+fn structure_store(structure_type: StructureType, rcl: u32) -> Option<Store> {
+    match structure_type {
+        StructureType::Container => Some(Store::new(CONTAINER_CAPACITY)),
+        StructureType::Extension => Some(Store::new(extension_energy_capacity(rcl))),
+        StructureType::Lab => Some(Store::lab()),
+        StructureType::Link => Some(Store::new(LINK_CAPACITY)),
+        StructureType::Spawn => Some(Store::new(SPAWN_ENERGY_CAPACITY)),
+        StructureType::Storage => Some(Store::new(STORAGE_CAPACITY)),
+        StructureType::Terminal => Some(Store::new(TERMINAL_CAPACITY)),
+        StructureType::Tower => Some(Store::new(TOWER_CAPACITY)),
+        _ => None,
+    }
+}
+
+impl Simulator {
+    pub fn new(world: FakeWorld) -> Self {
+        Self {
+            world,
+            regeneration: HashMap::new(),
+            regulators: BTreeMap::new(),
+            spawning: HashMap::new(),
+        }
+    }
+
+    /// Ticks until `done` holds, giving up after `max_ticks`. Returns the number of ticks
+    /// it took.
+    pub fn run_until<F: Fn(&FakeWorld) -> bool>(&mut self, max_ticks: u32, done: F) -> Option<u32> {
+        for t in 0..max_ticks {
+            if done(&self.world) {
+                return Some(t);
+            }
+            self.tick();
+        }
+
+        if done(&self.world) {
+            Some(max_ticks)
+        } else {
+            None
+        }
+    }
+
+    pub fn tick(&mut self) {
+        game_loop::tick(&self.world, &mut self.regulators);
+
+        let intents = std::mem::take(&mut self.world.state_mut().intents);
+        for intent in intents {
+            self.resolve(intent);
+        }
+
+        self.advance();
+    }
+
+    fn resolve(&mut self, intent: Intent) {
+        let mut state = self.world.state_mut();
+        let state = &mut *state;
+
+        match intent {
+            Intent::Attack { creep, target } => {
+                let damage = match state.creeps.get(&creep) {
+                    Some(c) => count(&c.body, Part::Attack) * ATTACK_POWER,
+                    None => return,
+                };
+                if let Some(h) = state.hostiles.iter_mut().find(|h| h.id == target) {
+                    h.hits = h.hits.saturating_sub(damage);
+                }
+                state.hostiles.retain(|h| h.hits > 0);
+            }
+            Intent::Boost { lab, creep } => {
+                let body = match state.creeps.get(&creep) {
+                    Some(c) => c.body.clone(),
+                    None => return,
+                };
+                let store = state
+                    .structures
+                    .iter_mut()
+                    .find(|s| s.id == lab)
+                    .and_then(|s| s.store.as_mut());
+                if let Some(store) = store {
+                    if let Some((compound, amount)) = store.mineral() {
+                        let energy = store.used_capacity(Some(ResourceType::Energy));
+                        let parts = boosted_parts(&body, compound)
+                            .min(amount / LAB_BOOST_MINERAL)
+                            .min(energy / LAB_BOOST_ENERGY);
+                        store.remove(compound, parts * LAB_BOOST_MINERAL);
+                        store.remove(ResourceType::Energy, parts * LAB_BOOST_ENERGY);
+                    }
+                }
+            }
+            Intent::Build { creep, site } => {
+                let i = match state.sites.iter().position(|s| s.id == site) {
+                    Some(i) => i,
+                    None => return,
+                };
+                if let Some(c) = state.creeps.get_mut(&creep) {
+                    let s = &mut state.sites[i];
+                    let amount = (count(&c.body, Part::Work) * BUILD_POWER)
+                        .min(c.store.used_capacity(Some(ResourceType::Energy)))
+                        .min(s.progress_total - s.progress);
+                    c.store.remove(ResourceType::Energy, amount);
+                    s.progress += amount;
+                }
+
+                if state.sites[i].progress >= state.sites[i].progress_total {
+                    let s = state.sites.remove(i);
+                    let rcl = state
+                        .rooms
+                        .get(&s.pos.room_name())
+                        .and_then(|r| r.controller.as_ref())
+                        .map(|c| c.level)
+                        .unwrap_or(0);
+                    state.add_structure(
+                        s.structure_type,
+                        s.pos,
+                        structure_store(s.structure_type, rcl),
+                    );
+                }
+            }
+            Intent::Harvest { creep, source } => {
+                let c = match state.creeps.get_mut(&creep) {
+                    Some(c) => c,
+                    None => return,
+                };
+                let work = count(&c.body, Part::Work);
+                let (resource, amount) =
+                    if let Some(s) = state.sources.iter_mut().find(|s| s.id == source) {
+                        let amount = (work * HARVEST_POWER).min(s.energy);
+                        self.regeneration.entry(s.id).or_insert(ENERGY_REGEN_TIME);
+                        s.energy -= amount;
+                        (ResourceType::Energy, amount)
+                    } else if let Some(m) = state.minerals.iter_mut().find(|m| m.id == source) {
+                        let amount = (work * HARVEST_MINERAL_POWER).min(m.amount);
+                        m.amount -= amount;
+                        if m.amount == 0 {
+                            m.ticks_to_regeneration = MINERAL_REGEN_TIME;
+                        }
+                        if let Some(e) = state.structures.iter_mut().find(|s| {
+                            s.pos == m.pos && s.structure_type == StructureType::Extractor
+                        }) {
+                            e.cooldown = EXTRACTOR_COOLDOWN;
+                        }
+                        (m.mineral_type, amount)
+                    } else {
+                        return;
+                    };
+                let free = c.store.free_capacity(Some(resource));
+                c.store.add(resource, amount.min(free));
+
+                // What doesn't fit drops, into the container the creep stands on if any
+                let pos = c.pos;
+                let mut dropped = amount.saturating_sub(free);
+                if let Some(store) = state
+                    .structures
+                    .iter_mut()
+                    .find(|s| s.pos == pos && s.structure_type == StructureType::Container)
+                    .and_then(|s| s.store.as_mut())
+                {
+                    let fits = dropped.min(store.free_capacity(Some(resource)));
+                    store.add(resource, fits);
+                    dropped -= fits;
+                }
+                state.drop_resource(pos, resource, dropped);
+            }
+            Intent::CreateSite {
+                pos,
+                structure_type,
+            } => {
+                let id = state.next_id();
+                state.sites.push(SiteData {
+                    id,
+                    pos,
+                    progress: 0,
+                    progress_total: structure_type.construction_cost().unwrap_or(1),
+                    structure_type,
+                });
+            }
+            Intent::Heal { creep, target } => {
+                let heal = match state.creeps.get(&creep) {
+                    Some(c) => count(&c.body, Part::Heal) * HEAL_POWER,
+                    None => return,
+                };
+                if let Some(c) = state.creeps.values_mut().find(|c| c.id == target) {
+                    c.hits = (c.hits + heal).min(c.hits_max);
+                }
+            }
+            Intent::Move { creep, target } => {
+                let from = match state.creeps.get(&creep) {
+                    Some(c) => c.pos,
+                    None => return,
+                };
+                let range = from.get_range_to(&target);
+                let step = neighbours(from)
+                    .filter(|p| p.get_range_to(&target) < range)
+                    .filter(|p| !is_blocked(state, *p))
+                    .min_by_key(|p| p.get_range_to(&target));
+
+                if let Some(step) = step {
+                    let on_road = state
+                        .structures
+                        .iter()
+                        .any(|s| s.pos == step && s.structure_type == StructureType::Road);
+                    let terrain = state.rooms[&step.room_name()]
+                        .terrain
+                        .get(step.x(), step.y());
+                    let multiplier = if on_road {
+                        1
+                    } else if terrain == Terrain::Swamp {
+                        10
+                    } else {
+                        2
+                    };
+
+                    let c = state.creeps.get_mut(&creep).unwrap();
+                    // Empty CARRY parts don't weigh anything
+                    let weight = c.body.len() as u32
+                        - count(&c.body, Part::Move)
+                        - if c.store.used_capacity(None) == 0 {
+                            count(&c.body, Part::Carry)
+                        } else {
+                            0
+                        };
+                    c.fatigue += weight * multiplier;
+                    c.pos = step;
+                }
+            }
+            Intent::Pickup { creep, resource } => {
+                if let (Some(c), Some(i)) = (
+                    state.creeps.get_mut(&creep),
+                    state.resources.iter().position(|r| r.id == resource),
+                ) {
+                    let r = &mut state.resources[i];
+                    let amount = r.amount.min(c.store.free_capacity(Some(r.resource_type)));
+                    c.store.add(r.resource_type, amount);
+                    r.amount -= amount;
+                    if r.amount == 0 {
+                        state.resources.remove(i);
+                    }
+                }
+            }
+            Intent::Recycle { creep, .. } => {
+                if let Some(c) = state.creeps.remove(&creep) {
+                    let cost = c.body.iter().map(|p| p.cost()).sum::<u32>();
+                    let energy = cost * c.ticks_to_live.unwrap_or(0) / CREEP_LIFE_TIME;
+                    Self::bury(state, c, energy);
+                }
+            }
+            Intent::Renew { spawn, creep } => {
+                let room = match state.spawns.iter().find(|s| s.name == spawn) {
+                    Some(s) => s.pos.room_name(),
+                    None => return,
+                };
+                if let Some(c) = state.creeps.get_mut(&creep) {
+                    c.ticks_to_live = c.ticks_to_live.map(|t| t + renew_ticks(&c.body));
+                    let cost = renew_cost(&c.body);
+                    Self::drain_room(state, room, cost);
+                }
+            }
+            Intent::Repair { creep, target } => {
+                if let (Some(c), Some(s)) = (
+                    state.creeps.get_mut(&creep),
+                    state.structures.iter_mut().find(|s| s.id == target),
+                ) {
+                    let amount = count(&c.body, Part::Work)
+                        .min(c.store.used_capacity(Some(ResourceType::Energy)));
+                    c.store.remove(ResourceType::Energy, amount);
+                    s.hits = (s.hits + amount * REPAIR_POWER).min(s.hits_max);
+                }
+            }
+            Intent::Spawn { spawn, body, name } => {
+                let room = match state.spawns.iter_mut().find(|s| s.name == spawn) {
+                    Some(s) => {
+                        s.spawning = Some(name.clone());
+                        s.pos.room_name()
+                    }
+                    None => return,
+                };
+
+                Self::drain_room(state, room, body.iter().map(|p| p.cost()).sum());
+                self.spawning.insert(
+                    spawn,
+                    Spawning {
+                        remaining: body.len() as u32 * CREEP_SPAWN_TIME,
+                        body,
+                        name,
+                    },
+                );
+            }
+            Intent::LinkTransfer {
+                link,
+                target,
+                amount,
+            } => {
+                let from = state.structures.iter().find(|s| s.id == link);
+                let to = state.structures.iter().find(|s| s.id == target);
+                let range = match (from, to) {
+                    (Some(l), Some(t)) => l.pos.get_range_to(&t.pos),
+                    _ => return,
+                };
+                let lost = (amount as f32 * LINK_LOSS_RATIO).ceil() as u32;
+                for s in state.structures.iter_mut() {
+                    if s.id == link {
+                        s.cooldown = LINK_COOLDOWN * range;
+                        s.store
+                            .iter_mut()
+                            .for_each(|s| s.remove(ResourceType::Energy, amount));
+                    } else if s.id == target {
+                        let store = s.store.iter_mut();
+                        store.for_each(|s| s.add(ResourceType::Energy, amount - lost));
+                    }
+                }
+            }
+            Intent::ReverseReaction { lab, lab1, lab2 } => {
+                let compound = match Self::lab_mineral(state, lab) {
+                    Some(c) => c,
+                    None => return,
+                };
+                if let Some([c1, c2]) = compound.reaction_components() {
+                    Self::react(state, lab, compound, [(lab1, c1), (lab2, c2)], true);
+                }
+            }
+            Intent::RunReaction { lab, lab1, lab2 } => {
+                let (m1, m2) = match (
+                    Self::lab_mineral(state, lab1),
+                    Self::lab_mineral(state, lab2),
+                ) {
+                    (Some(m1), Some(m2)) => (m1, m2),
+                    _ => return,
+                };
+                if let Some(product) = reaction_product(m1, m2) {
+                    Self::react(state, lab, product, [(lab1, m1), (lab2, m2)], false);
+                }
+            }
+            // There's no one to trade with in the simulation
+            Intent::CancelOrder { .. }
+            | Intent::ChangeOrderPrice { .. }
+            | Intent::CreateOrder { .. }
+            | Intent::Deal { .. }
+            | Intent::ExtendOrder { .. } => {}
+            Intent::TerminalSend {
+                terminal,
+                resource,
+                amount,
+                destination,
+            } => {
+                let from = match state.structures.iter().find(|s| s.id == terminal) {
+                    Some(t) => t.pos.room_name(),
+                    None => return,
+                };
+                let cost = transfer_cost(amount, from, destination);
+                for s in state.structures.iter_mut() {
+                    if s.id == terminal {
+                        s.cooldown = TERMINAL_COOLDOWN;
+                        s.store.iter_mut().for_each(|s| {
+                            s.remove(resource, amount);
+                            s.remove(ResourceType::Energy, cost);
+                        });
+                    } else if s.my
+                        && s.structure_type == StructureType::Terminal
+                        && s.pos.room_name() == destination
+                    {
+                        s.store.iter_mut().for_each(|s| s.add(resource, amount));
+                    }
+                }
+            }
+            Intent::TowerAttack { tower, target } => {
+                if let Some(h) = state.hostiles.iter_mut().find(|h| h.id == target) {
+                    h.hits = h.hits.saturating_sub(TOWER_POWER_ATTACK);
+                }
+                state.hostiles.retain(|h| h.hits > 0);
+                Self::drain_tower(state, tower);
+            }
+            Intent::TowerHeal { tower, target } => {
+                if let Some(c) = state.creeps.values_mut().find(|c| c.id == target) {
+                    c.hits = (c.hits + TOWER_POWER_HEAL).min(c.hits_max);
+                }
+                Self::drain_tower(state, tower);
+            }
+            Intent::TowerRepair { tower, target } => {
+                if let Some(s) = state.structures.iter_mut().find(|s| s.id == target) {
+                    s.hits = (s.hits + TOWER_POWER_REPAIR).min(s.hits_max);
+                }
+                Self::drain_tower(state, tower);
+            }
+            Intent::Transfer {
+                creep,
+                target,
+                resource,
+            } => {
+                if let (Some(c), Some(store)) = (
+                    state.creeps.get_mut(&creep),
+                    state
+                        .structures
+                        .iter_mut()
+                        .find(|s| s.id == target)
+                        .and_then(|s| s.store.as_mut()),
+                ) {
+                    let amount = c
+                        .store
+                        .used_capacity(Some(resource))
+                        .min(store.free_capacity(Some(resource)));
+                    c.store.remove(resource, amount);
+                    store.add(resource, amount);
+                }
+            }
+            Intent::Withdraw {
+                creep,
+                target,
+                resource,
+            } => {
+                let free = match state.creeps.get(&creep) {
+                    Some(c) => c.store.free_capacity(Some(resource)),
+                    None => return,
+                };
+                if let Some(store) = state.store_mut(target) {
+                    let amount = store.used_capacity(Some(resource)).min(free);
+                    store.remove(resource, amount);
+                    state
+                        .creeps
+                        .get_mut(&creep)
+                        .unwrap()
+                        .store
+                        .add(resource, amount);
+                }
+            }
+            Intent::Upgrade { creep, controller } => {
+                if let (Some(c), Some(ctrl)) = (
+                    state.creeps.get_mut(&creep),
+                    state
+                        .rooms
+                        .values_mut()
+                        .filter_map(|r| r.controller.as_mut())
+                        .find(|c| c.id == controller),
+                ) {
+                    let amount = (count(&c.body, Part::Work) * UPGRADE_CONTROLLER_POWER)
+                        .min(c.store.used_capacity(Some(ResourceType::Energy)));
+                    c.store.remove(ResourceType::Energy, amount);
+                    ctrl.progress += amount;
+
+                    while ctrl.progress_total != 0 && ctrl.progress >= ctrl.progress_total {
+                        ctrl.progress -= ctrl.progress_total;
+                        ctrl.level += 1;
+                        ctrl.progress_total = controller_levels(ctrl.level).unwrap_or(0);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Takes the energy from the spawns first, then from the extensions.
+    fn drain_room(state: &mut FakeState, room: RoomName, mut cost: u32) {
+        for typ in &[StructureType::Spawn, StructureType::Extension] {
+            for s in state
+                .structures
+                .iter_mut()
+                .filter(|s| s.pos.room_name() == room && s.structure_type == *typ)
+            {
+                if let Some(store) = s.store.as_mut() {
+                    let amount = store.used_capacity(Some(ResourceType::Energy)).min(cost);
+                    store.remove(ResourceType::Energy, amount);
+                    cost -= amount;
+                }
+            }
+        }
+    }
+
+    /// Leaves a tombstone with what the creep carried and the given energy.
+    fn bury(state: &mut FakeState, creep: CreepData, energy: u32) {
+        let mut store = creep.store;
+        if energy != 0 {
+            store.add(ResourceType::Energy, energy);
+        }
+        let id = state.next_id();
+        state.tombstones.push(RemainsData {
+            id,
+            pos: creep.pos,
+            store,
+            ticks_to_decay: creep.body.len() as u32 * TOMBSTONE_DECAY_PER_PART,
+        });
+    }
+
+    fn lab_mineral(state: &FakeState, lab: RawObjectId) -> Option<ResourceType> {
+        state
+            .structures
+            .iter()
+            .find(|s| s.id == lab)
+            .and_then(|s| s.store.as_ref())
+            .and_then(|s| s.mineral())
+            .map(|(r, _)| r)
+    }
+
+    /// Turns a reaction's worth of the reagents into the product, or back when reversing
+    fn react(
+        state: &mut FakeState,
+        lab: RawObjectId,
+        product: ResourceType,
+        reagents: [(RawObjectId, ResourceType); 2],
+        reverse: bool,
+    ) {
+        for s in state.structures.iter_mut() {
+            let store = match s.store.as_mut() {
+                Some(store) => store,
+                None => continue,
+            };
+            if s.id == lab {
+                s.cooldown = product.reaction_time().unwrap_or(0);
+                match reverse {
+                    true => store.remove(product, LAB_REACTION_AMOUNT),
+                    false => store.add(product, LAB_REACTION_AMOUNT),
+                }
+            }
+            for &(id, reagent) in &reagents {
+                if s.id == id {
+                    match reverse {
+                        true => store.add(reagent, LAB_REACTION_AMOUNT),
+                        false => store.remove(reagent, LAB_REACTION_AMOUNT),
+                    }
+                }
+            }
+        }
+    }
+
+    fn drain_tower(state: &mut FakeState, tower: RawObjectId) {
+        if let Some(store) = state
+            .structures
+            .iter_mut()
+            .find(|s| s.id == tower)
+            .and_then(|s| s.store.as_mut())
+        {
+            store.remove(ResourceType::Energy, TOWER_ENERGY_COST);
+        }
+    }
+
+    fn advance(&mut self) {
+        let mut state = self.world.state_mut();
+        let state = &mut *state;
+
+        state.time += 1;
+
+        for c in state.creeps.values_mut() {
+            c.ticks_to_live = c.ticks_to_live.map(|t| t.saturating_sub(1));
+            c.fatigue = c.fatigue.saturating_sub(2 * count(&c.body, Part::Move));
+        }
+        let dead: Vec<_> = state
+            .creeps
+            .iter()
+            .filter(|(_, c)| c.ticks_to_live == Some(0))
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in dead {
+            let c = state.creeps.remove(&name).unwrap();
+            Self::bury(state, c, 0);
+        }
+
+        for r in state.resources.iter_mut() {
+            r.amount -= r.amount.div_ceil(ENERGY_DECAY);
+        }
+        state.resources.retain(|r| r.amount != 0);
+
+        // Decayed remains drop what's left in them
+        let mut decayed = Vec::new();
+        for remains in [&mut state.tombstones, &mut state.ruins] {
+            for r in remains.iter_mut() {
+                r.ticks_to_decay = r.ticks_to_decay.saturating_sub(1);
+            }
+            decayed.extend(remains.iter().filter(|r| r.ticks_to_decay == 0).cloned());
+            remains.retain(|r| r.ticks_to_decay != 0);
+        }
+        for r in decayed {
+            for (resource, amount) in r.store.resources {
+                state.drop_resource(r.pos, resource, amount);
+            }
+        }
+
+        for s in state.structures.iter_mut() {
+            s.cooldown = s.cooldown.saturating_sub(1);
+        }
+
+        for m in state.minerals.iter_mut() {
+            if m.ticks_to_regeneration != 0 {
+                m.ticks_to_regeneration -= 1;
+                if m.ticks_to_regeneration == 0 {
+                    m.amount = m.density.amount();
+                }
+            }
+        }
+
+        for s in state.sources.iter_mut() {
+            if let Some(t) = self.regeneration.get_mut(&s.id) {
+                *t -= 1;
+                if *t == 0 {
+                    s.energy = s.energy_capacity;
+                    self.regeneration.remove(&s.id);
+                }
+            }
+        }
+
+        for spawn in state.spawns.clone() {
+            match self.spawning.get_mut(&spawn.name) {
+                Some(s) if s.remaining > 1 => s.remaining -= 1,
+                Some(_) => {
+                    let s = self.spawning.remove(&spawn.name).unwrap();
+                    // The creep stays inside the spawn until a neighbouring tile is free
+                    match neighbours(spawn.pos).find(|p| !is_blocked(state, *p)) {
+                        Some(pos) => {
+                            let id = state.next_id();
+                            let hits = s.body.len() as u32 * 100;
+                            state.creeps.insert(
+                                s.name.clone(),
+                                CreepData {
+                                    fatigue: 0,
+                                    hits,
+                                    hits_max: hits,
+                                    id,
+                                    name: s.name,
+                                    pos,
+                                    store: Store::new(count(&s.body, Part::Carry) * CARRY_CAPACITY),
+                                    ticks_to_live: Some(CREEP_LIFE_TIME),
+                                    body: s.body,
+                                },
+                            );
+                            if let Some(d) = state.spawns.iter_mut().find(|d| d.id == spawn.id) {
+                                d.spawning = None;
+                            }
+                        }
+                        None => {
+                            self.spawning
+                                .insert(spawn.name.clone(), Spawning { remaining: 1, ..s });
+                        }
+                    }
+                }
+                None => {
+                    // Spawns refill themselves slowly while the room is low on energy
+                    if state.energy_in_room(spawn.pos.room_name()).0 < SPAWN_ENERGY_CAPACITY {
+                        if let Some(store) = state
+                            .structures
+                            .iter_mut()
+                            .find(|s| s.id == spawn.id)
+                            .and_then(|s| s.store.as_mut())
+                        {
+                            if store.free_capacity(Some(ResourceType::Energy)) > 0 {
+                                store.add(ResourceType::Energy, 1);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::constants::BOOST_WAIT;
+    use crate::core::{load_memory, save_memory, CreepMemory};
+    use crate::creeps::Role;
+    use crate::world::{empty_room, pos, World};
+    use serde_json::Map;
+
+    fn rcl1_room() -> FakeWorld {
+        let world = empty_room();
+        world.add_controller(pos(25, 40), 1);
+        world.add_spawn("Spawn1", pos(25, 25));
+        world.add_source(pos(15, 20));
+        world.add_source(pos(35, 20));
+        world
+    }
+
+    #[test]
+    fn rcl1_room_reaches_rcl2() {
+        let mut sim = Simulator::new(rcl1_room());
+
+        let ticks = sim.run_until(1000, |w| {
+            w.room(pos(0, 0).room_name())
+                .and_then(|r| r.controller)
+                .map(|c| c.level >= 2)
+                .unwrap_or(false)
+        });
+
+        assert!(ticks.is_some(), "room didn't reach RCL2 in time");
+    }
+
+    #[test]
+    fn static_miners_fill_their_containers() {
+        let world = rcl1_room();
+        world.state_mut().rooms.values_mut().for_each(|r| {
+            r.controller.as_mut().unwrap().level = 2;
+        });
+        let container = pos(16, 21);
+        for source in &[container, pos(34, 21)] {
+            world.add_structure(
+                StructureType::Container,
+                *source,
+                Some(Store::new(CONTAINER_CAPACITY)),
+            );
+        }
+        let mut sim = Simulator::new(world);
+
+        let ticks = sim.run_until(1000, |w| {
+            w.creeps().iter().any(|c| c.pos == container)
+                && w.structures(container.room_name()).iter().any(|s| {
+                    s.pos == container
+                        && s.store
+                            .as_ref()
+                            .is_some_and(|s| s.used_capacity(Some(ResourceType::Energy)) != 0)
+                })
+        });
+
+        assert!(ticks.is_some(), "no miner on the container in time");
+    }
+
+    #[test]
+    fn labs_react_their_reagents() {
+        let world = rcl1_room();
+        let lab = |at, resource| {
+            let store = Store::lab();
+            world.add_structure(StructureType::Lab, at, Some(store.with(resource, 1000)))
+        };
+        lab(pos(30, 30), ResourceType::Oxygen);
+        lab(pos(31, 30), ResourceType::Hydrogen);
+        let output = world.add_structure(
+            StructureType::Lab,
+            pos(30, 32),
+            structure_store(StructureType::Lab, 1),
+        );
+        let mut sim = Simulator::new(world);
+
+        let ticks = sim.run_until(100, |w| {
+            w.get_structure(output)
+                .and_then(|s| s.store)
+                .is_some_and(|s| s.used_capacity(Some(ResourceType::Hydroxide)) >= 20)
+        });
+
+        assert!(ticks.is_some(), "no hydroxide made in time");
+    }
+
+    #[test]
+    fn boost_labs_get_their_energy_delivered() {
+        let world = rcl1_room();
+        world.state_mut().rooms.values_mut().for_each(|r| {
+            r.controller.as_mut().unwrap().level = 6;
+        });
+        let compound = ResourceType::CatalyzedGhodiumAcid;
+        let storage = Store::new(STORAGE_CAPACITY).with(ResourceType::Energy, 10_000);
+        world.add_structure(StructureType::Storage, pos(30, 26), Some(storage));
+        // The input labs are topped up already, so the boost lab is the only one short
+        for at in &[pos(30, 30), pos(31, 30)] {
+            let store = Store::lab().with(ResourceType::Energy, LAB_ENERGY_CAPACITY);
+            world.add_structure(StructureType::Lab, *at, Some(store));
+        }
+        // Full of the compound the way the haulers stock it, the energy is left to them
+        let store = Store::lab().with(compound, LAB_MINERAL_CAPACITY);
+        let lab = world.add_structure(StructureType::Lab, pos(30, 32), Some(store));
+        world.add_creep("hauler", pos(29, 26), Store::new(50));
+        let body = Role::Hauler.body(SPAWN_ENERGY_CAPACITY).unwrap();
+        let carry = count(&body, Part::Carry) * CARRY_CAPACITY;
+        if let Some(c) = world.state_mut().creeps.get_mut("hauler") {
+            c.body = body;
+            c.store = Store::new(carry);
+        }
+        world.add_creep("upgrader", pos(31, 33), Store::new(50));
+        let mut memory = load_memory(&world);
+        let home = Some(pos(0, 0).room_name());
+        for (name, role, boosts) in &[
+            ("hauler", Role::Hauler, vec![]),
+            ("upgrader", Role::Upgrader, vec![compound]),
+        ] {
+            memory.creeps.insert(
+                name.to_string(),
+                CreepMemory {
+                    boosts: boosts.clone(),
+                    home,
+                    job: None,
+                    lifecycle: None,
+                    next_job: None,
+                    role: Some(*role),
+                    extra: Map::new(),
+                },
+            );
+        }
+        save_memory(&world, &memory);
+        let mut sim = Simulator::new(world);
+
+        let ticks = sim.run_until(BOOST_WAIT, |w| {
+            w.get_structure(lab)
+                .and_then(|s| s.store)
+                .is_some_and(|s| s.used_capacity(Some(compound)) < LAB_MINERAL_CAPACITY)
+        });
+
+        assert!(ticks.is_some(), "upgrader wasn't boosted in time");
+    }
+
+    #[test]
+    fn walking_creeps_leave_traffic_behind() {
+        let mut sim = Simulator::new(rcl1_room());
+
+        sim.run_until(300, |_| false);
+
+        let memory = load_memory(&sim.world);
+        let traffic = &memory.rooms[&pos(0, 0).room_name()].traffic;
+        assert!(!traffic.busy(2).is_empty());
+    }
+
+    #[test]
+    fn spawning_takes_three_ticks_per_part() {
+        let mut sim = Simulator::new(rcl1_room());
+
+        sim.tick();
+        assert!(sim.world.spawns()[0].spawning.is_some());
+        let parts = sim.spawning.values().next().unwrap().body.len() as u32;
+
+        for _ in 1..parts * CREEP_SPAWN_TIME - 1 {
+            sim.tick();
+        }
+        assert!(sim.world.creeps().is_empty());
+        sim.tick();
+        assert_eq!(sim.world.creeps().len(), 1);
+        assert!(sim.world.spawns()[0].spawning.is_none());
+    }
+
+    #[test]
+    fn swamps_tire_creeps() {
+        let world = rcl1_room();
+        world.add_creep("walker", pos(10, 10), Store::new(50));
+        {
+            let mut state = world.state_mut();
+            let terrain = &mut state.rooms.get_mut(&pos(0, 0).room_name()).unwrap().terrain;
+            for y in 9..=11 {
+                terrain.set(11, y, Terrain::Swamp);
+            }
+        }
+        let mut sim = Simulator::new(world);
+
+        sim.world.state_mut().intents.push(Intent::Move {
+            creep: "walker".to_string(),
+            target: pos(12, 10),
+        });
+        let intents = std::mem::take(&mut sim.world.state_mut().intents);
+        for intent in intents {
+            sim.resolve(intent);
+        }
+
+        let walker = sim.world.state().creeps["walker"].clone();
+        // One WORK part on a swamp, the empty CARRY part doesn't count
+        assert_eq!(walker.fatigue, 10);
+    }
+}
@@ -0,0 +1,140 @@
+use super::{
+    ControllerData, CreepData, MineralData, OrderData, PriceData, RemainsData, ResourceData,
+    RoomData, RoomTerrain, SiteData, SourceData, SpawnData, StructureData,
+};
+use screeps::{
+    game::market::OrderType, pathfinder::LocalCostMatrix, Part, Position, RawObjectId,
+    ResourceType, ReturnCode, RoomName, StructureType,
+};
+
+/// Everything the AI reads from or does to the game goes through this trait, so the
+/// decision logic can run against the live server as well as against an in-memory fake.
+///
+/// Reads hand out plain snapshots, intents address objects by id (or by name for our
+/// own creeps and spawns) and report the game's `ReturnCode`.
+pub trait World {
+    fn cpu_used(&self) -> f64;
+    fn time(&self) -> u32;
+
+    fn room(&self, name: RoomName) -> Option<RoomData>;
+    fn rooms(&self) -> Vec<RoomName>;
+    fn terrain(&self, room: RoomName) -> RoomTerrain;
+    /// The steps from `from` to within `range` of `to`, without leaving the room. Tiles
+    /// with a cost in `costs` take that instead of the terrain's, 255 blocks them.
+    fn find_path(
+        &self,
+        from: Position,
+        to: Position,
+        range: u32,
+        costs: &LocalCostMatrix,
+    ) -> Vec<Position>;
+
+    fn construction_sites(&self, room: RoomName) -> Vec<SiteData>;
+    fn create_construction_site(&self, pos: Position, structure_type: StructureType) -> ReturnCode;
+    fn dropped_resources(&self, room: RoomName) -> Vec<ResourceData>;
+    fn hostile_creeps(&self, room: RoomName) -> Vec<CreepData>;
+    fn minerals(&self, room: RoomName) -> Vec<MineralData>;
+    fn my_creeps(&self, room: RoomName) -> Vec<CreepData>;
+    fn ruins(&self, room: RoomName) -> Vec<RemainsData>;
+    fn sources(&self, room: RoomName) -> Vec<SourceData>;
+    fn structures(&self, room: RoomName) -> Vec<StructureData>;
+    fn tombstones(&self, room: RoomName) -> Vec<RemainsData>;
+
+    fn get_construction_site(&self, id: RawObjectId) -> Option<SiteData>;
+    fn get_controller(&self, id: RawObjectId) -> Option<ControllerData>;
+    fn get_creep(&self, id: RawObjectId) -> Option<CreepData>;
+    fn get_mineral(&self, id: RawObjectId) -> Option<MineralData>;
+    fn get_resource(&self, id: RawObjectId) -> Option<ResourceData>;
+    fn get_ruin(&self, id: RawObjectId) -> Option<RemainsData>;
+    fn get_source(&self, id: RawObjectId) -> Option<SourceData>;
+    fn get_structure(&self, id: RawObjectId) -> Option<StructureData>;
+    fn get_tombstone(&self, id: RawObjectId) -> Option<RemainsData>;
+
+    fn creeps(&self) -> Vec<CreepData>;
+    fn creep_attack(&self, creep: &str, target: RawObjectId) -> ReturnCode;
+    fn creep_build(&self, creep: &str, site: RawObjectId) -> ReturnCode;
+    /// Harvests a source, or a mineral with an extractor on it
+    fn creep_harvest(&self, creep: &str, source: RawObjectId) -> ReturnCode;
+    fn creep_heal(&self, creep: &str, target: RawObjectId) -> ReturnCode;
+    fn creep_move_to(&self, creep: &str, target: Position) -> ReturnCode;
+    fn creep_pickup(&self, creep: &str, resource: RawObjectId) -> ReturnCode;
+    fn creep_repair(&self, creep: &str, target: RawObjectId) -> ReturnCode;
+    fn creep_say(&self, creep: &str, message: &str);
+    fn creep_transfer_all(
+        &self,
+        creep: &str,
+        target: RawObjectId,
+        resource: ResourceType,
+    ) -> ReturnCode;
+    fn creep_upgrade_controller(&self, creep: &str, controller: RawObjectId) -> ReturnCode;
+    /// Withdraws from a structure, tombstone or ruin
+    fn creep_withdraw_all(
+        &self,
+        creep: &str,
+        target: RawObjectId,
+        resource: ResourceType,
+    ) -> ReturnCode;
+
+    fn lab_boost_creep(&self, lab: RawObjectId, creep: &str) -> ReturnCode;
+    /// Splits the compound in `lab` back into `lab1` and `lab2`
+    fn lab_reverse_reaction(
+        &self,
+        lab: RawObjectId,
+        lab1: RawObjectId,
+        lab2: RawObjectId,
+    ) -> ReturnCode;
+    /// Makes the compound of what `lab1` and `lab2` hold in `lab`
+    fn lab_run_reaction(
+        &self,
+        lab: RawObjectId,
+        lab1: RawObjectId,
+        lab2: RawObjectId,
+    ) -> ReturnCode;
+    fn link_transfer_energy(
+        &self,
+        link: RawObjectId,
+        target: RawObjectId,
+        amount: u32,
+    ) -> ReturnCode;
+
+    fn terminal_send(
+        &self,
+        terminal: RawObjectId,
+        resource: ResourceType,
+        amount: u32,
+        destination: RoomName,
+    ) -> ReturnCode;
+
+    fn tower_attack(&self, tower: RawObjectId, target: RawObjectId) -> ReturnCode;
+    fn tower_heal(&self, tower: RawObjectId, target: RawObjectId) -> ReturnCode;
+    fn tower_repair(&self, tower: RawObjectId, target: RawObjectId) -> ReturnCode;
+
+    fn credits(&self) -> f64;
+    /// Everyone's orders for the resource, ours included
+    fn market_orders(&self, resource: ResourceType) -> Vec<OrderData>;
+    fn my_orders(&self) -> Vec<OrderData>;
+    /// Daily figures of the last two weeks
+    fn price_history(&self, resource: ResourceType) -> Vec<PriceData>;
+    /// Fills someone else's order through the terminal in `room`
+    fn market_deal(&self, order: &str, amount: u32, room: RoomName) -> ReturnCode;
+    fn market_create_order(
+        &self,
+        order_type: OrderType,
+        resource: ResourceType,
+        price: f64,
+        amount: u32,
+        room: RoomName,
+    ) -> ReturnCode;
+    fn market_cancel_order(&self, order: &str) -> ReturnCode;
+    fn market_extend_order(&self, order: &str, amount: u32) -> ReturnCode;
+    fn market_change_price(&self, order: &str, price: f64) -> ReturnCode;
+
+    fn spawns(&self) -> Vec<SpawnData>;
+    fn spawn_creep(&self, spawn: &str, body: &[Part], name: &str) -> ReturnCode;
+    fn spawn_recycle_creep(&self, spawn: &str, creep: &str) -> ReturnCode;
+    fn spawn_renew_creep(&self, spawn: &str, creep: &str) -> ReturnCode;
+
+    /// `Memory` as JSON
+    fn raw_memory(&self) -> String;
+    fn set_raw_memory(&self, raw: &str);
+}
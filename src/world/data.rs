@@ -0,0 +1,238 @@
+use crate::core::constants;
+use screeps::{
+    game::market::OrderType, Density, Part, Position, RawObjectId, ResourceType, RoomName,
+    StructureType, Terrain, LAB_ENERGY_CAPACITY, LAB_MINERAL_CAPACITY,
+};
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, Default)]
+pub struct Store {
+    pub capacity: u32,
+    /// Resources kept apart from the shared capacity, the way labs keep their energy
+    pub capacities: HashMap<ResourceType, u32>,
+    pub resources: HashMap<ResourceType, u32>,
+}
+
+impl Store {
+    pub fn new(capacity: u32) -> Self {
+        Self {
+            capacity,
+            capacities: HashMap::new(),
+            resources: HashMap::new(),
+        }
+    }
+
+    pub fn lab() -> Self {
+        Self::new(LAB_MINERAL_CAPACITY).with_capacity(ResourceType::Energy, LAB_ENERGY_CAPACITY)
+    }
+
+    pub fn with_capacity(mut self, resource: ResourceType, capacity: u32) -> Self {
+        self.capacities.insert(resource, capacity);
+        self
+    }
+
+    #[cfg(test)]
+    pub fn with(mut self, resource: ResourceType, amount: u32) -> Self {
+        self.resources.insert(resource, amount);
+        self
+    }
+
+    #[cfg(test)]
+    pub fn add(&mut self, resource: ResourceType, amount: u32) {
+        *self.resources.entry(resource).or_insert(0) += amount;
+    }
+
+    #[cfg(test)]
+    pub fn remove(&mut self, resource: ResourceType, amount: u32) {
+        let left = self.used_capacity(Some(resource)).saturating_sub(amount);
+        if left == 0 {
+            self.resources.remove(&resource);
+        } else {
+            self.resources.insert(resource, left);
+        }
+    }
+
+    /// What more fits of a resource, or of anything sharing the general capacity
+    pub fn free_capacity(&self, resource: Option<ResourceType>) -> u32 {
+        if let Some(capacity) = resource.and_then(|r| self.capacities.get(&r)) {
+            return capacity.saturating_sub(self.used_capacity(resource));
+        }
+        let shared: u32 = self
+            .resources
+            .iter()
+            .filter(|(r, _)| !self.capacities.contains_key(r))
+            .map(|(_, &a)| a)
+            .sum();
+        self.capacity.saturating_sub(shared)
+    }
+
+    /// Whatever it holds besides energy, the way labs hold a single mineral
+    pub fn mineral(&self) -> Option<(ResourceType, u32)> {
+        self.resources
+            .iter()
+            .find(|(&r, _)| r != ResourceType::Energy)
+            .map(|(&r, &a)| (r, a))
+    }
+
+    pub fn used_capacity(&self, resource: Option<ResourceType>) -> u32 {
+        match resource {
+            Some(r) => self.resources.get(&r).copied().unwrap_or(0),
+            None => self.resources.values().sum(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CreepData {
+    pub body: Vec<Part>,
+    #[allow(dead_code)]
+    pub fatigue: u32,
+    pub hits: u32,
+    pub hits_max: u32,
+    pub id: RawObjectId,
+    pub name: String,
+    pub pos: Position,
+    pub store: Store,
+    pub ticks_to_live: Option<u32>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ControllerData {
+    pub id: RawObjectId,
+    pub level: u32,
+    pub my: bool,
+    pub pos: Position,
+    #[allow(dead_code)]
+    pub progress: u32,
+    #[allow(dead_code)]
+    pub progress_total: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct MineralData {
+    pub amount: u32,
+    #[allow(dead_code)]
+    pub density: Density,
+    pub id: RawObjectId,
+    #[allow(dead_code)]
+    pub mineral_type: ResourceType,
+    pub pos: Position,
+    /// 0 while there's some left
+    #[allow(dead_code)]
+    pub ticks_to_regeneration: u32,
+}
+
+/// A market order, `amount` being what's left of it
+#[derive(Clone, Debug)]
+pub struct OrderData {
+    pub amount: u32,
+    pub id: String,
+    pub order_type: OrderType,
+    pub price: f64,
+    pub resource_type: ResourceType,
+    pub room: Option<RoomName>,
+}
+
+/// A day of trading in a resource
+#[derive(Clone, Debug)]
+pub struct PriceData {
+    pub avg_price: f64,
+    pub volume: u32,
+}
+
+/// A tombstone or ruin. Whatever is left in it drops to the ground once it decays.
+#[derive(Clone, Debug)]
+pub struct RemainsData {
+    pub id: RawObjectId,
+    pub pos: Position,
+    pub store: Store,
+    pub ticks_to_decay: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct RoomData {
+    pub controller: Option<ControllerData>,
+    pub energy_available: u32,
+    pub energy_capacity_available: u32,
+}
+
+/// A pile of dropped resources
+#[derive(Clone, Debug)]
+pub struct ResourceData {
+    pub amount: u32,
+    pub id: RawObjectId,
+    pub pos: Position,
+    pub resource_type: ResourceType,
+}
+
+#[derive(Clone, Debug)]
+pub struct SiteData {
+    pub id: RawObjectId,
+    pub pos: Position,
+    pub progress: u32,
+    pub progress_total: u32,
+    pub structure_type: StructureType,
+}
+
+#[derive(Clone, Debug)]
+pub struct SourceData {
+    pub energy: u32,
+    pub energy_capacity: u32,
+    pub id: RawObjectId,
+    pub pos: Position,
+}
+
+#[derive(Clone, Debug)]
+pub struct SpawnData {
+    #[allow(dead_code)]
+    pub id: RawObjectId,
+    pub name: String,
+    pub pos: Position,
+    /// Name of the creep being spawned
+    pub spawning: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct StructureData {
+    pub cooldown: u32,
+    pub hits: u32,
+    pub hits_max: u32,
+    pub id: RawObjectId,
+    pub my: bool,
+    pub pos: Position,
+    pub store: Option<Store>,
+    pub structure_type: StructureType,
+}
+
+#[derive(Clone)]
+pub struct RoomTerrain {
+    tiles: Vec<Terrain>,
+}
+
+impl RoomTerrain {
+    pub fn new(tiles: Vec<Terrain>) -> Self {
+        assert_eq!(
+            tiles.len(),
+            (constants::ROOM_X * constants::ROOM_Y) as usize
+        );
+        Self { tiles }
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Terrain {
+        self.tiles[(y * constants::ROOM_X + x) as usize]
+    }
+
+    #[cfg(test)]
+    pub fn set(&mut self, x: u32, y: u32, terrain: Terrain) {
+        self.tiles[(y * constants::ROOM_X + x) as usize] = terrain;
+    }
+}
+
+impl Default for RoomTerrain {
+    fn default() -> Self {
+        Self::new(vec![
+            Terrain::Plain;
+            (constants::ROOM_X * constants::ROOM_Y) as usize
+        ])
+    }
+}
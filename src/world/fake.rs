@@ -0,0 +1,1442 @@
+use super::{
+    ControllerData, CreepData, MineralData, OrderData, PriceData, RemainsData, ResourceData,
+    RoomData, RoomTerrain, SiteData, SourceData, SpawnData, Store, StructureData, World,
+};
+use crate::core::{constants, transfer_cost};
+use screeps::{
+    game::market::OrderType, pathfinder::LocalCostMatrix, Density, Part, Position, RawObjectId,
+    ResourceType, ReturnCode, RoomName, StructureType, Terrain, CREEP_LIFE_TIME, CREEP_SPAWN_TIME,
+    LAB_BOOST_ENERGY, LAB_BOOST_MINERAL, LAB_MINERAL_CAPACITY, LAB_REACTION_AMOUNT, MARKET_FEE,
+    SOURCE_ENERGY_CAPACITY, SPAWN_ENERGY_CAPACITY, SPAWN_RENEW_RATIO, TOWER_ENERGY_COST,
+    WALL_HITS_MAX,
+};
+use std::cell::{Ref, RefCell, RefMut};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+
+/// An intent accepted by the fake world. Nothing is applied to the world state, the
+/// intents are only recorded so tests (or a simulator) can inspect and resolve them.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Intent {
+    Attack {
+        creep: String,
+        target: RawObjectId,
+    },
+    Boost {
+        lab: RawObjectId,
+        creep: String,
+    },
+    Build {
+        creep: String,
+        site: RawObjectId,
+    },
+    CancelOrder {
+        order: String,
+    },
+    ChangeOrderPrice {
+        order: String,
+        price: f64,
+    },
+    CreateOrder {
+        order_type: OrderType,
+        resource: ResourceType,
+        price: f64,
+        amount: u32,
+        room: RoomName,
+    },
+    CreateSite {
+        pos: Position,
+        structure_type: StructureType,
+    },
+    Deal {
+        order: String,
+        amount: u32,
+        room: RoomName,
+    },
+    ExtendOrder {
+        order: String,
+        amount: u32,
+    },
+    Harvest {
+        creep: String,
+        source: RawObjectId,
+    },
+    Heal {
+        creep: String,
+        target: RawObjectId,
+    },
+    LinkTransfer {
+        link: RawObjectId,
+        target: RawObjectId,
+        amount: u32,
+    },
+    Move {
+        creep: String,
+        target: Position,
+    },
+    Pickup {
+        creep: String,
+        resource: RawObjectId,
+    },
+    Recycle {
+        spawn: String,
+        creep: String,
+    },
+    Renew {
+        spawn: String,
+        creep: String,
+    },
+    Repair {
+        creep: String,
+        target: RawObjectId,
+    },
+    ReverseReaction {
+        lab: RawObjectId,
+        lab1: RawObjectId,
+        lab2: RawObjectId,
+    },
+    RunReaction {
+        lab: RawObjectId,
+        lab1: RawObjectId,
+        lab2: RawObjectId,
+    },
+    Spawn {
+        spawn: String,
+        body: Vec<Part>,
+        name: String,
+    },
+    TerminalSend {
+        terminal: RawObjectId,
+        resource: ResourceType,
+        amount: u32,
+        destination: RoomName,
+    },
+    TowerAttack {
+        tower: RawObjectId,
+        target: RawObjectId,
+    },
+    TowerHeal {
+        tower: RawObjectId,
+        target: RawObjectId,
+    },
+    TowerRepair {
+        tower: RawObjectId,
+        target: RawObjectId,
+    },
+    Transfer {
+        creep: String,
+        target: RawObjectId,
+        resource: ResourceType,
+    },
+    Upgrade {
+        creep: String,
+        controller: RawObjectId,
+    },
+    Withdraw {
+        creep: String,
+        target: RawObjectId,
+        resource: ResourceType,
+    },
+}
+
+/// What `a` and `b` react into in a lab, in either order
+pub fn reaction_product(a: ResourceType, b: ResourceType) -> Option<ResourceType> {
+    constants::COMPOUNDS.iter().copied().find(|c| {
+        c.reaction_components()
+            .is_some_and(|[x, y]| (x, y) == (a, b) || (x, y) == (b, a))
+    })
+}
+
+/// Ticks of life a renewal adds
+pub fn renew_ticks(body: &[Part]) -> u32 {
+    600 / body.len().max(1) as u32
+}
+
+pub fn renew_cost(body: &[Part]) -> u32 {
+    let cost = body.iter().map(|p| p.cost()).sum::<u32>() as f32;
+    (SPAWN_RENEW_RATIO * cost / CREEP_SPAWN_TIME as f32 / body.len().max(1) as f32).ceil() as u32
+}
+
+#[derive(Default)]
+pub struct FakeRoom {
+    pub controller: Option<ControllerData>,
+    pub terrain: RoomTerrain,
+}
+
+#[derive(Default)]
+pub struct FakeState {
+    pub creeps: BTreeMap<String, CreepData>,
+    pub credits: f64,
+    pub hostiles: Vec<CreepData>,
+    pub intents: Vec<Intent>,
+    pub minerals: Vec<MineralData>,
+    pub my_orders: Vec<OrderData>,
+    /// Other players' orders
+    pub orders: Vec<OrderData>,
+    pub price_history: HashMap<ResourceType, Vec<PriceData>>,
+    pub resources: Vec<ResourceData>,
+    pub rooms: BTreeMap<RoomName, FakeRoom>,
+    pub ruins: Vec<RemainsData>,
+    pub sites: Vec<SiteData>,
+    pub sources: Vec<SourceData>,
+    pub spawns: Vec<SpawnData>,
+    pub structures: Vec<StructureData>,
+    pub time: u32,
+    pub tombstones: Vec<RemainsData>,
+    pub memory: String,
+    next_id: u32,
+}
+
+impl FakeState {
+    pub fn next_id(&mut self) -> RawObjectId {
+        self.next_id += 1;
+        RawObjectId::from([0, 0, self.next_id])
+    }
+
+    pub fn add_structure(
+        &mut self,
+        structure_type: StructureType,
+        pos: Position,
+        store: Option<Store>,
+    ) -> RawObjectId {
+        let id = self.next_id();
+        let hits = structure_type.initial_hits().unwrap_or(0);
+        let hits_max = match structure_type {
+            StructureType::Rampart | StructureType::Wall => WALL_HITS_MAX,
+            _ => hits,
+        };
+        self.structures.push(StructureData {
+            cooldown: 0,
+            hits,
+            hits_max,
+            id,
+            my: true,
+            pos,
+            store,
+            structure_type,
+        });
+        id
+    }
+
+    /// Drops the resource, onto the pile already lying there if there's one.
+    pub fn drop_resource(&mut self, pos: Position, resource_type: ResourceType, amount: u32) {
+        if amount == 0 {
+            return;
+        }
+
+        match self
+            .resources
+            .iter_mut()
+            .find(|r| r.pos == pos && r.resource_type == resource_type)
+        {
+            Some(r) => r.amount += amount,
+            None => {
+                let id = self.next_id();
+                self.resources.push(ResourceData {
+                    amount,
+                    id,
+                    pos,
+                    resource_type,
+                });
+            }
+        }
+    }
+
+    /// The store of the structure, tombstone or ruin.
+    pub fn store_mut(&mut self, id: RawObjectId) -> Option<&mut Store> {
+        if let Some(s) = self.structures.iter_mut().find(|s| s.id == id) {
+            return s.store.as_mut();
+        }
+        self.tombstones
+            .iter_mut()
+            .chain(self.ruins.iter_mut())
+            .find(|r| r.id == id)
+            .map(|r| &mut r.store)
+    }
+
+    pub fn energy_in_room(&self, room: RoomName) -> (u32, u32) {
+        self.structures
+            .iter()
+            .filter(|s| s.pos.room_name() == room && s.my)
+            .filter(|s| {
+                s.structure_type == StructureType::Spawn
+                    || s.structure_type == StructureType::Extension
+            })
+            .filter_map(|s| s.store.as_ref())
+            .fold((0, 0), |(energy, capacity), store| {
+                (
+                    energy + store.used_capacity(Some(ResourceType::Energy)),
+                    capacity + store.capacity,
+                )
+            })
+    }
+}
+
+/// A position in W1N1, the room tests play in
+pub fn pos(x: u32, y: u32) -> Position {
+    Position::new(x, y, RoomName::new("W1N1").unwrap())
+}
+
+/// W1N1 with nothing in it
+pub fn empty_room() -> FakeWorld {
+    let world = FakeWorld::new();
+    world.add_room(pos(0, 0).room_name());
+    world
+}
+
+/// W1N1 the way a room starts out, with a level 1 controller and a spawn
+pub fn world() -> FakeWorld {
+    let world = empty_room();
+    world.add_controller(pos(40, 40), 1);
+    world.add_spawn("Spawn1", pos(25, 25));
+    world
+}
+
+#[derive(Default)]
+pub struct FakeWorld {
+    state: RefCell<FakeState>,
+}
+
+impl FakeWorld {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn state(&self) -> Ref<'_, FakeState> {
+        self.state.borrow()
+    }
+
+    pub fn state_mut(&self) -> RefMut<'_, FakeState> {
+        self.state.borrow_mut()
+    }
+
+    /// Puts up another player's order
+    pub fn add_order(
+        &self,
+        order_type: OrderType,
+        resource_type: ResourceType,
+        price: f64,
+        amount: u32,
+        room: RoomName,
+    ) -> String {
+        let mut state = self.state_mut();
+        let id = state.next_id().to_string();
+        state.orders.push(OrderData {
+            amount,
+            id: id.clone(),
+            order_type,
+            price,
+            resource_type,
+            room: Some(room),
+        });
+        id
+    }
+
+    /// A fortnight of trading at the same price
+    pub fn set_price(&self, resource: ResourceType, avg_price: f64) {
+        let day = PriceData {
+            avg_price,
+            volume: 1000,
+        };
+        self.state_mut()
+            .price_history
+            .insert(resource, vec![day; 14]);
+    }
+
+    pub fn add_room(&self, name: RoomName) {
+        self.state_mut().rooms.insert(name, FakeRoom::default());
+    }
+
+    pub fn add_controller(&self, pos: Position, level: u32) -> RawObjectId {
+        let mut state = self.state_mut();
+        let id = state.next_id();
+        state.rooms.entry(pos.room_name()).or_default().controller = Some(ControllerData {
+            id,
+            level,
+            my: true,
+            pos,
+            progress: 0,
+            progress_total: screeps::controller_levels(level).unwrap_or(0),
+        });
+        id
+    }
+
+    pub fn add_creep(&self, name: &str, pos: Position, store: Store) -> RawObjectId {
+        let mut state = self.state_mut();
+        let id = state.next_id();
+        state.creeps.insert(
+            name.to_string(),
+            CreepData {
+                body: vec![Part::Work, Part::Carry, Part::Move],
+                fatigue: 0,
+                hits: 300,
+                hits_max: 300,
+                id,
+                name: name.to_string(),
+                pos,
+                store,
+                ticks_to_live: Some(screeps::CREEP_LIFE_TIME),
+            },
+        );
+        id
+    }
+
+    pub fn add_hostile(&self, pos: Position) -> RawObjectId {
+        let mut state = self.state_mut();
+        let id = state.next_id();
+        state.hostiles.push(CreepData {
+            body: vec![Part::Attack, Part::Move],
+            fatigue: 0,
+            hits: 100,
+            hits_max: 100,
+            id,
+            name: format!("invader-{}", id),
+            pos,
+            store: Store::default(),
+            ticks_to_live: Some(screeps::CREEP_LIFE_TIME),
+        });
+        id
+    }
+
+    pub fn add_mineral(&self, pos: Position, mineral_type: ResourceType) -> RawObjectId {
+        let mut state = self.state_mut();
+        let id = state.next_id();
+        state.minerals.push(MineralData {
+            amount: Density::Moderate.amount(),
+            density: Density::Moderate,
+            id,
+            mineral_type,
+            pos,
+            ticks_to_regeneration: 0,
+        });
+        id
+    }
+
+    pub fn add_resource(&self, pos: Position, resource_type: ResourceType, amount: u32) {
+        self.state_mut().drop_resource(pos, resource_type, amount);
+    }
+
+    pub fn add_ruin(&self, pos: Position, store: Store, ticks_to_decay: u32) -> RawObjectId {
+        let mut state = self.state_mut();
+        let id = state.next_id();
+        state.ruins.push(RemainsData {
+            id,
+            pos,
+            store,
+            ticks_to_decay,
+        });
+        id
+    }
+
+    pub fn add_site(&self, structure_type: StructureType, pos: Position) -> RawObjectId {
+        let mut state = self.state_mut();
+        let id = state.next_id();
+        state.sites.push(SiteData {
+            id,
+            pos,
+            progress: 0,
+            progress_total: structure_type.construction_cost().unwrap_or(1),
+            structure_type,
+        });
+        id
+    }
+
+    pub fn add_source(&self, pos: Position) -> RawObjectId {
+        let mut state = self.state_mut();
+        let id = state.next_id();
+        state.sources.push(SourceData {
+            energy: SOURCE_ENERGY_CAPACITY,
+            energy_capacity: SOURCE_ENERGY_CAPACITY,
+            id,
+            pos,
+        });
+        id
+    }
+
+    pub fn add_spawn(&self, name: &str, pos: Position) -> RawObjectId {
+        let id = self.add_structure(
+            StructureType::Spawn,
+            pos,
+            Some(
+                Store::new(SPAWN_ENERGY_CAPACITY).with(ResourceType::Energy, SPAWN_ENERGY_CAPACITY),
+            ),
+        );
+        self.state_mut().spawns.push(SpawnData {
+            id,
+            name: name.to_string(),
+            pos,
+            spawning: None,
+        });
+        id
+    }
+
+    pub fn add_tombstone(&self, pos: Position, store: Store, ticks_to_decay: u32) -> RawObjectId {
+        let mut state = self.state_mut();
+        let id = state.next_id();
+        state.tombstones.push(RemainsData {
+            id,
+            pos,
+            store,
+            ticks_to_decay,
+        });
+        id
+    }
+
+    pub fn add_structure(
+        &self,
+        structure_type: StructureType,
+        pos: Position,
+        store: Option<Store>,
+    ) -> RawObjectId {
+        self.state_mut().add_structure(structure_type, pos, store)
+    }
+
+    fn creep_action(
+        &self,
+        creep: &str,
+        target: Option<Position>,
+        range: u32,
+        needs_energy: bool,
+        intent: Intent,
+    ) -> ReturnCode {
+        let mut state = self.state_mut();
+        let c = match state.creeps.get(creep) {
+            Some(c) => c,
+            None => return ReturnCode::NotFound,
+        };
+        let target = match target {
+            Some(t) => t,
+            None => return ReturnCode::NotFound,
+        };
+
+        if needs_energy && c.store.used_capacity(Some(ResourceType::Energy)) == 0 {
+            ReturnCode::NotEnough
+        } else if !c.pos.in_range_to(&target, range) {
+            ReturnCode::NotInRange
+        } else {
+            state.intents.push(intent);
+            ReturnCode::Ok
+        }
+    }
+
+    fn terminal(&self, room: RoomName) -> Option<StructureData> {
+        self.state()
+            .structures
+            .iter()
+            .find(|s| {
+                s.my && s.structure_type == StructureType::Terminal && s.pos.room_name() == room
+            })
+            .cloned()
+    }
+
+    /// The minerals of the three labs, if they're labs in range of each other and ready
+    /// to react
+    fn lab_minerals(
+        &self,
+        lab: RawObjectId,
+        lab1: RawObjectId,
+        lab2: RawObjectId,
+    ) -> std::result::Result<[Option<(ResourceType, u32)>; 3], ReturnCode> {
+        let labs = [lab, lab1, lab2].map(|id| self.get_structure(id));
+        let [l, l1, l2] = match labs {
+            [Some(l), Some(l1), Some(l2)] => [l, l1, l2],
+            _ => return Err(ReturnCode::NotFound),
+        };
+        if [&l, &l1, &l2]
+            .iter()
+            .any(|s| s.structure_type != StructureType::Lab)
+        {
+            Err(ReturnCode::InvalidTarget)
+        } else if l.cooldown > 0 {
+            Err(ReturnCode::Tired)
+        } else if [&l1, &l2]
+            .iter()
+            .any(|s| !s.pos.in_range_to(&l.pos, constants::RANGE_REACTION))
+        {
+            Err(ReturnCode::NotInRange)
+        } else {
+            Ok([l, l1, l2].map(|s| s.store.and_then(|s| s.mineral())))
+        }
+    }
+
+    fn tower_action(
+        &self,
+        tower: RawObjectId,
+        target: Option<Position>,
+        intent: Intent,
+    ) -> ReturnCode {
+        let energy = match self.get_structure(tower) {
+            Some(t) if t.structure_type == StructureType::Tower => t
+                .store
+                .map(|s| s.used_capacity(Some(ResourceType::Energy)))
+                .unwrap_or(0),
+            _ => return ReturnCode::NotFound,
+        };
+
+        if target.is_none() {
+            ReturnCode::NotFound
+        } else if energy < TOWER_ENERGY_COST {
+            ReturnCode::NotEnough
+        } else {
+            self.state_mut().intents.push(intent);
+            ReturnCode::Ok
+        }
+    }
+}
+
+impl World for FakeWorld {
+    fn cpu_used(&self) -> f64 {
+        0.0
+    }
+
+    fn time(&self) -> u32 {
+        self.state().time
+    }
+
+    fn room(&self, name: RoomName) -> Option<RoomData> {
+        let state = self.state();
+        let (energy_available, energy_capacity_available) = state.energy_in_room(name);
+        state.rooms.get(&name).map(|r| RoomData {
+            controller: r.controller.clone(),
+            energy_available,
+            energy_capacity_available,
+        })
+    }
+
+    fn rooms(&self) -> Vec<RoomName> {
+        self.state().rooms.keys().copied().collect()
+    }
+
+    fn terrain(&self, room: RoomName) -> RoomTerrain {
+        self.state()
+            .rooms
+            .get(&room)
+            .map(|r| r.terrain.clone())
+            .unwrap_or_default()
+    }
+
+    fn find_path(
+        &self,
+        from: Position,
+        to: Position,
+        range: u32,
+        costs: &LocalCostMatrix,
+    ) -> Vec<Position> {
+        let room = from.room_name();
+        let terrain = self.terrain(room);
+        let cost = |x: u32, y: u32| match costs.get(x as u8, y as u8) {
+            0 => match terrain.get(x, y) {
+                Terrain::Plain => constants::PATH_PLAIN_COST,
+                Terrain::Swamp => constants::PATH_SWAMP_COST,
+                Terrain::Wall => u8::MAX,
+            },
+            c => c,
+        };
+        let index = |x: u32, y: u32| (y * constants::ROOM_X + x) as usize;
+
+        let tiles = (constants::ROOM_X * constants::ROOM_Y) as usize;
+        let mut best = vec![u32::MAX; tiles];
+        let mut previous = vec![None; tiles];
+        let mut queue = BinaryHeap::new();
+        best[index(from.x(), from.y())] = 0;
+        queue.push(Reverse((0, from.x(), from.y())));
+
+        while let Some(Reverse((steps, x, y))) = queue.pop() {
+            if steps > best[index(x, y)] {
+                continue;
+            }
+            if Position::new(x, y, room).in_range_to(&to, range) {
+                let mut path = Vec::new();
+                let mut tile = (x, y);
+                while tile != (from.x(), from.y()) {
+                    path.push(Position::new(tile.0, tile.1, room));
+                    tile = previous[index(tile.0, tile.1)].unwrap();
+                }
+                path.reverse();
+                return path;
+            }
+
+            for nx in x.saturating_sub(1)..=(x + 1).min(constants::ROOM_X - 1) {
+                for ny in y.saturating_sub(1)..=(y + 1).min(constants::ROOM_Y - 1) {
+                    let c = cost(nx, ny);
+                    let next = steps + c as u32;
+                    if c == u8::MAX || next >= best[index(nx, ny)] {
+                        continue;
+                    }
+                    best[index(nx, ny)] = next;
+                    previous[index(nx, ny)] = Some((x, y));
+                    queue.push(Reverse((next, nx, ny)));
+                }
+            }
+        }
+        Vec::new()
+    }
+
+    fn construction_sites(&self, room: RoomName) -> Vec<SiteData> {
+        self.state()
+            .sites
+            .iter()
+            .filter(|s| s.pos.room_name() == room)
+            .cloned()
+            .collect()
+    }
+
+    fn create_construction_site(&self, pos: Position, structure_type: StructureType) -> ReturnCode {
+        let mut state = self.state_mut();
+        let terrain = match state.rooms.get(&pos.room_name()) {
+            Some(r) => r.terrain.get(pos.x(), pos.y()),
+            None => return ReturnCode::NotInRange,
+        };
+
+        let taken = state.sites.iter().any(|s| s.pos == pos)
+            || state.structures.iter().any(|s| s.pos == pos)
+            || state
+                .intents
+                .iter()
+                .any(|i| matches!(i, Intent::CreateSite { pos: p, .. } if *p == pos));
+        if terrain == Terrain::Wall || taken {
+            ReturnCode::InvalidTarget
+        } else {
+            state.intents.push(Intent::CreateSite {
+                pos,
+                structure_type,
+            });
+            ReturnCode::Ok
+        }
+    }
+
+    fn dropped_resources(&self, room: RoomName) -> Vec<ResourceData> {
+        self.state()
+            .resources
+            .iter()
+            .filter(|r| r.pos.room_name() == room)
+            .cloned()
+            .collect()
+    }
+
+    fn hostile_creeps(&self, room: RoomName) -> Vec<CreepData> {
+        self.state()
+            .hostiles
+            .iter()
+            .filter(|c| c.pos.room_name() == room)
+            .cloned()
+            .collect()
+    }
+
+    fn minerals(&self, room: RoomName) -> Vec<MineralData> {
+        self.state()
+            .minerals
+            .iter()
+            .filter(|m| m.pos.room_name() == room)
+            .cloned()
+            .collect()
+    }
+
+    fn my_creeps(&self, room: RoomName) -> Vec<CreepData> {
+        self.state()
+            .creeps
+            .values()
+            .filter(|c| c.pos.room_name() == room)
+            .cloned()
+            .collect()
+    }
+
+    fn ruins(&self, room: RoomName) -> Vec<RemainsData> {
+        self.state()
+            .ruins
+            .iter()
+            .filter(|r| r.pos.room_name() == room)
+            .cloned()
+            .collect()
+    }
+
+    fn sources(&self, room: RoomName) -> Vec<SourceData> {
+        self.state()
+            .sources
+            .iter()
+            .filter(|s| s.pos.room_name() == room)
+            .cloned()
+            .collect()
+    }
+
+    fn structures(&self, room: RoomName) -> Vec<StructureData> {
+        self.state()
+            .structures
+            .iter()
+            .filter(|s| s.pos.room_name() == room)
+            .cloned()
+            .collect()
+    }
+
+    fn tombstones(&self, room: RoomName) -> Vec<RemainsData> {
+        self.state()
+            .tombstones
+            .iter()
+            .filter(|r| r.pos.room_name() == room)
+            .cloned()
+            .collect()
+    }
+
+    fn get_construction_site(&self, id: RawObjectId) -> Option<SiteData> {
+        self.state().sites.iter().find(|s| s.id == id).cloned()
+    }
+
+    fn get_controller(&self, id: RawObjectId) -> Option<ControllerData> {
+        self.state()
+            .rooms
+            .values()
+            .filter_map(|r| r.controller.as_ref())
+            .find(|c| c.id == id)
+            .cloned()
+    }
+
+    fn get_creep(&self, id: RawObjectId) -> Option<CreepData> {
+        let state = self.state();
+        state
+            .creeps
+            .values()
+            .chain(state.hostiles.iter())
+            .find(|c| c.id == id)
+            .cloned()
+    }
+
+    fn get_mineral(&self, id: RawObjectId) -> Option<MineralData> {
+        self.state().minerals.iter().find(|m| m.id == id).cloned()
+    }
+
+    fn get_resource(&self, id: RawObjectId) -> Option<ResourceData> {
+        self.state().resources.iter().find(|r| r.id == id).cloned()
+    }
+
+    fn get_ruin(&self, id: RawObjectId) -> Option<RemainsData> {
+        self.state().ruins.iter().find(|r| r.id == id).cloned()
+    }
+
+    fn get_source(&self, id: RawObjectId) -> Option<SourceData> {
+        self.state().sources.iter().find(|s| s.id == id).cloned()
+    }
+
+    fn get_structure(&self, id: RawObjectId) -> Option<StructureData> {
+        self.state().structures.iter().find(|s| s.id == id).cloned()
+    }
+
+    fn get_tombstone(&self, id: RawObjectId) -> Option<RemainsData> {
+        self.state().tombstones.iter().find(|r| r.id == id).cloned()
+    }
+
+    fn creeps(&self) -> Vec<CreepData> {
+        self.state().creeps.values().cloned().collect()
+    }
+
+    fn creep_attack(&self, creep: &str, target: RawObjectId) -> ReturnCode {
+        let pos = self.get_creep(target).map(|c| c.pos);
+        self.creep_action(
+            creep,
+            pos,
+            constants::RANGE_ATACK,
+            false,
+            Intent::Attack {
+                creep: creep.to_string(),
+                target,
+            },
+        )
+    }
+
+    fn creep_build(&self, creep: &str, site: RawObjectId) -> ReturnCode {
+        let target = self.get_construction_site(site).map(|s| s.pos);
+        self.creep_action(
+            creep,
+            target,
+            constants::RANGE_BUILD,
+            true,
+            Intent::Build {
+                creep: creep.to_string(),
+                site,
+            },
+        )
+    }
+
+    fn creep_harvest(&self, creep: &str, source: RawObjectId) -> ReturnCode {
+        if let Some(m) = self.get_mineral(source) {
+            let extractor = self
+                .state()
+                .structures
+                .iter()
+                .find(|s| s.pos == m.pos && s.structure_type == StructureType::Extractor)
+                .cloned();
+            return match extractor {
+                None => ReturnCode::NotFound,
+                Some(_) if m.amount == 0 => ReturnCode::NotEnough,
+                Some(e) if e.cooldown != 0 => ReturnCode::Tired,
+                Some(_) => self.creep_action(
+                    creep,
+                    Some(m.pos),
+                    constants::RANGE_HARVEST,
+                    false,
+                    Intent::Harvest {
+                        creep: creep.to_string(),
+                        source,
+                    },
+                ),
+            };
+        }
+
+        match self.get_source(source) {
+            Some(s) if s.energy == 0 => ReturnCode::NotEnough,
+            s => self.creep_action(
+                creep,
+                s.map(|s| s.pos),
+                constants::RANGE_HARVEST,
+                false,
+                Intent::Harvest {
+                    creep: creep.to_string(),
+                    source,
+                },
+            ),
+        }
+    }
+
+    fn creep_heal(&self, creep: &str, target: RawObjectId) -> ReturnCode {
+        let pos = self.get_creep(target).map(|c| c.pos);
+        self.creep_action(
+            creep,
+            pos,
+            constants::RANGE_HEAL,
+            false,
+            Intent::Heal {
+                creep: creep.to_string(),
+                target,
+            },
+        )
+    }
+
+    fn creep_move_to(&self, creep: &str, target: Position) -> ReturnCode {
+        let mut state = self.state_mut();
+        match state.creeps.get(creep) {
+            Some(c) if c.fatigue > 0 => return ReturnCode::Tired,
+            Some(_) => (),
+            None => return ReturnCode::NotFound,
+        }
+
+        state.intents.push(Intent::Move {
+            creep: creep.to_string(),
+            target,
+        });
+        ReturnCode::Ok
+    }
+
+    fn creep_pickup(&self, creep: &str, resource: RawObjectId) -> ReturnCode {
+        let full = self
+            .state()
+            .creeps
+            .get(creep)
+            .is_some_and(|c| c.store.free_capacity(None) == 0);
+        if full {
+            return ReturnCode::Full;
+        }
+
+        let pos = self.get_resource(resource).map(|r| r.pos);
+        self.creep_action(
+            creep,
+            pos,
+            constants::RANGE_PICKUP,
+            false,
+            Intent::Pickup {
+                creep: creep.to_string(),
+                resource,
+            },
+        )
+    }
+
+    fn creep_repair(&self, creep: &str, target: RawObjectId) -> ReturnCode {
+        let pos = self.get_structure(target).map(|s| s.pos);
+        self.creep_action(
+            creep,
+            pos,
+            constants::RANGE_REPAIR,
+            true,
+            Intent::Repair {
+                creep: creep.to_string(),
+                target,
+            },
+        )
+    }
+
+    fn creep_say(&self, _creep: &str, _message: &str) {}
+
+    fn creep_transfer_all(
+        &self,
+        creep: &str,
+        target: RawObjectId,
+        resource: ResourceType,
+    ) -> ReturnCode {
+        let carried = self
+            .state()
+            .creeps
+            .get(creep)
+            .map(|c| c.store.used_capacity(Some(resource)));
+        match self.get_structure(target) {
+            Some(s) if s.store.is_none() => ReturnCode::InvalidTarget,
+            Some(s) if s.store.as_ref().unwrap().free_capacity(Some(resource)) == 0 => {
+                ReturnCode::Full
+            }
+            _ if carried == Some(0) => ReturnCode::NotEnough,
+            s => self.creep_action(
+                creep,
+                s.map(|s| s.pos),
+                constants::RANGE_TRANSFER,
+                false,
+                Intent::Transfer {
+                    creep: creep.to_string(),
+                    target,
+                    resource,
+                },
+            ),
+        }
+    }
+
+    fn creep_upgrade_controller(&self, creep: &str, controller: RawObjectId) -> ReturnCode {
+        let pos = self.get_controller(controller).map(|c| c.pos);
+        self.creep_action(
+            creep,
+            pos,
+            constants::RANGE_UPGRADE_CONTROLLER,
+            true,
+            Intent::Upgrade {
+                creep: creep.to_string(),
+                controller,
+            },
+        )
+    }
+
+    fn creep_withdraw_all(
+        &self,
+        creep: &str,
+        target: RawObjectId,
+        resource: ResourceType,
+    ) -> ReturnCode {
+        let full = self
+            .state()
+            .creeps
+            .get(creep)
+            .is_some_and(|c| c.store.free_capacity(Some(resource)) == 0);
+        let target_store = match self.get_structure(target) {
+            Some(s) => Some((s.pos, s.store)),
+            None => self
+                .get_tombstone(target)
+                .or_else(|| self.get_ruin(target))
+                .map(|r| (r.pos, Some(r.store))),
+        };
+        match target_store {
+            Some((_, None)) => ReturnCode::InvalidTarget,
+            Some((_, Some(store))) if store.used_capacity(Some(resource)) == 0 => {
+                ReturnCode::NotEnough
+            }
+            _ if full => ReturnCode::Full,
+            t => self.creep_action(
+                creep,
+                t.map(|(pos, _)| pos),
+                constants::RANGE_WITHDRAW,
+                false,
+                Intent::Withdraw {
+                    creep: creep.to_string(),
+                    target,
+                    resource,
+                },
+            ),
+        }
+    }
+
+    fn lab_boost_creep(&self, lab: RawObjectId, creep: &str) -> ReturnCode {
+        let l = match self.get_structure(lab) {
+            Some(l) if l.structure_type == StructureType::Lab => l,
+            Some(_) => return ReturnCode::InvalidTarget,
+            None => return ReturnCode::NotFound,
+        };
+        let store = l.store.unwrap_or_default();
+        let mineral = store.mineral().map_or(0, |(_, amount)| amount);
+        if mineral < LAB_BOOST_MINERAL
+            || store.used_capacity(Some(ResourceType::Energy)) < LAB_BOOST_ENERGY
+        {
+            return ReturnCode::NotEnough;
+        }
+        self.creep_action(
+            creep,
+            Some(l.pos),
+            constants::RANGE_BOOST,
+            false,
+            Intent::Boost {
+                lab,
+                creep: creep.to_string(),
+            },
+        )
+    }
+
+    fn lab_reverse_reaction(
+        &self,
+        lab: RawObjectId,
+        lab1: RawObjectId,
+        lab2: RawObjectId,
+    ) -> ReturnCode {
+        let [m, m1, m2] = match self.lab_minerals(lab, lab1, lab2) {
+            Ok(minerals) => minerals,
+            Err(r) => return r,
+        };
+        let components = match m {
+            Some((r, amount)) if amount >= LAB_REACTION_AMOUNT => r.reaction_components(),
+            _ => return ReturnCode::NotEnough,
+        };
+        match components {
+            Some([c1, c2])
+                if m1.is_none_or(|(r, _)| r == c1) && m2.is_none_or(|(r, _)| r == c2) =>
+            {
+                self.state_mut()
+                    .intents
+                    .push(Intent::ReverseReaction { lab, lab1, lab2 });
+                ReturnCode::Ok
+            }
+            _ => ReturnCode::InvalidArgs,
+        }
+    }
+
+    fn lab_run_reaction(
+        &self,
+        lab: RawObjectId,
+        lab1: RawObjectId,
+        lab2: RawObjectId,
+    ) -> ReturnCode {
+        let [m, m1, m2] = match self.lab_minerals(lab, lab1, lab2) {
+            Ok(minerals) => minerals,
+            Err(r) => return r,
+        };
+        let product = match (m1, m2) {
+            (Some((r1, a1)), Some((r2, a2)))
+                if a1 >= LAB_REACTION_AMOUNT && a2 >= LAB_REACTION_AMOUNT =>
+            {
+                reaction_product(r1, r2)
+            }
+            _ => return ReturnCode::NotEnough,
+        };
+        match (product, m) {
+            (None, _) => ReturnCode::InvalidArgs,
+            (Some(p), Some((r, _))) if r != p => ReturnCode::InvalidTarget,
+            (_, Some((_, a))) if a + LAB_REACTION_AMOUNT > LAB_MINERAL_CAPACITY => ReturnCode::Full,
+            _ => {
+                self.state_mut()
+                    .intents
+                    .push(Intent::RunReaction { lab, lab1, lab2 });
+                ReturnCode::Ok
+            }
+        }
+    }
+
+    fn link_transfer_energy(
+        &self,
+        link: RawObjectId,
+        target: RawObjectId,
+        amount: u32,
+    ) -> ReturnCode {
+        let (from, to) = match (self.get_structure(link), self.get_structure(target)) {
+            (Some(l), Some(t)) => (l, t),
+            _ => return ReturnCode::NotFound,
+        };
+        let energy = |s: &StructureData| {
+            s.store
+                .as_ref()
+                .map_or(0, |s| s.used_capacity(Some(ResourceType::Energy)))
+        };
+        let free = to
+            .store
+            .as_ref()
+            .map_or(0, |s| s.free_capacity(Some(ResourceType::Energy)));
+
+        if from.structure_type != StructureType::Link || to.structure_type != StructureType::Link {
+            ReturnCode::InvalidTarget
+        } else if from.cooldown > 0 {
+            ReturnCode::Tired
+        } else if energy(&from) < amount {
+            ReturnCode::NotEnough
+        } else if free < amount {
+            ReturnCode::Full
+        } else {
+            self.state_mut().intents.push(Intent::LinkTransfer {
+                link,
+                target,
+                amount,
+            });
+            ReturnCode::Ok
+        }
+    }
+
+    fn terminal_send(
+        &self,
+        terminal: RawObjectId,
+        resource: ResourceType,
+        amount: u32,
+        destination: RoomName,
+    ) -> ReturnCode {
+        let from = match self.get_structure(terminal) {
+            Some(t) => t,
+            None => return ReturnCode::NotFound,
+        };
+        let held = |r| from.store.as_ref().map_or(0, |s| s.used_capacity(Some(r)));
+        let cost = transfer_cost(amount, from.pos.room_name(), destination);
+        let energy = match resource {
+            ResourceType::Energy => amount + cost,
+            _ => cost,
+        };
+
+        if from.structure_type != StructureType::Terminal {
+            ReturnCode::InvalidTarget
+        } else if from.cooldown > 0 {
+            ReturnCode::Tired
+        } else if held(resource) < amount || held(ResourceType::Energy) < energy {
+            ReturnCode::NotEnough
+        } else {
+            self.state_mut().intents.push(Intent::TerminalSend {
+                terminal,
+                resource,
+                amount,
+                destination,
+            });
+            ReturnCode::Ok
+        }
+    }
+
+    fn tower_attack(&self, tower: RawObjectId, target: RawObjectId) -> ReturnCode {
+        let pos = self.get_creep(target).map(|c| c.pos);
+        self.tower_action(tower, pos, Intent::TowerAttack { tower, target })
+    }
+
+    fn tower_heal(&self, tower: RawObjectId, target: RawObjectId) -> ReturnCode {
+        let pos = self.get_creep(target).map(|c| c.pos);
+        self.tower_action(tower, pos, Intent::TowerHeal { tower, target })
+    }
+
+    fn tower_repair(&self, tower: RawObjectId, target: RawObjectId) -> ReturnCode {
+        let pos = self.get_structure(target).map(|s| s.pos);
+        self.tower_action(tower, pos, Intent::TowerRepair { tower, target })
+    }
+
+    fn credits(&self) -> f64 {
+        self.state().credits
+    }
+
+    fn market_orders(&self, resource: ResourceType) -> Vec<OrderData> {
+        let state = self.state();
+        state
+            .orders
+            .iter()
+            .chain(&state.my_orders)
+            .filter(|o| o.resource_type == resource)
+            .cloned()
+            .collect()
+    }
+
+    fn my_orders(&self) -> Vec<OrderData> {
+        self.state().my_orders.clone()
+    }
+
+    fn price_history(&self, resource: ResourceType) -> Vec<PriceData> {
+        let state = self.state();
+        state
+            .price_history
+            .get(&resource)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn market_deal(&self, order: &str, amount: u32, room: RoomName) -> ReturnCode {
+        let o = match self.state().orders.iter().find(|o| o.id == order) {
+            Some(o) => o.clone(),
+            None => return ReturnCode::InvalidArgs,
+        };
+        let terminal = match self.terminal(room) {
+            Some(t) => t,
+            None => return ReturnCode::NotOwner,
+        };
+        let held = |r| {
+            terminal
+                .store
+                .as_ref()
+                .map_or(0, |s| s.used_capacity(Some(r)))
+        };
+        let cost = o.room.map_or(0, |r| transfer_cost(amount, room, r));
+        // Selling into a buy order sends the resource along with the energy
+        let enough = match o.order_type {
+            OrderType::Buy => held(o.resource_type) >= amount,
+            OrderType::Sell => self.credits() >= o.price * amount as f64,
+        };
+
+        if amount > o.amount {
+            ReturnCode::InvalidArgs
+        } else if terminal.cooldown > 0 {
+            ReturnCode::Tired
+        } else if !enough || held(ResourceType::Energy) < cost {
+            ReturnCode::NotEnough
+        } else {
+            self.state_mut().intents.push(Intent::Deal {
+                order: order.to_string(),
+                amount,
+                room,
+            });
+            ReturnCode::Ok
+        }
+    }
+
+    fn market_create_order(
+        &self,
+        order_type: OrderType,
+        resource: ResourceType,
+        price: f64,
+        amount: u32,
+        room: RoomName,
+    ) -> ReturnCode {
+        if self.terminal(room).is_none() {
+            ReturnCode::NotOwner
+        } else if self.credits() < price * amount as f64 * MARKET_FEE as f64 {
+            ReturnCode::NotEnough
+        } else {
+            self.state_mut().intents.push(Intent::CreateOrder {
+                order_type,
+                resource,
+                price,
+                amount,
+                room,
+            });
+            ReturnCode::Ok
+        }
+    }
+
+    fn market_cancel_order(&self, order: &str) -> ReturnCode {
+        if !self.state().my_orders.iter().any(|o| o.id == order) {
+            return ReturnCode::InvalidArgs;
+        }
+        self.state_mut().intents.push(Intent::CancelOrder {
+            order: order.to_string(),
+        });
+        ReturnCode::Ok
+    }
+
+    fn market_extend_order(&self, order: &str, amount: u32) -> ReturnCode {
+        let o = match self.state().my_orders.iter().find(|o| o.id == order) {
+            Some(o) => o.clone(),
+            None => return ReturnCode::InvalidArgs,
+        };
+        if self.credits() < o.price * amount as f64 * MARKET_FEE as f64 {
+            ReturnCode::NotEnough
+        } else {
+            self.state_mut().intents.push(Intent::ExtendOrder {
+                order: order.to_string(),
+                amount,
+            });
+            ReturnCode::Ok
+        }
+    }
+
+    fn market_change_price(&self, order: &str, price: f64) -> ReturnCode {
+        let o = match self.state().my_orders.iter().find(|o| o.id == order) {
+            Some(o) => o.clone(),
+            None => return ReturnCode::InvalidArgs,
+        };
+        // Only raising the price costs a fee
+        let fee = (price - o.price).max(0.0) * o.amount as f64 * MARKET_FEE as f64;
+        if self.credits() < fee {
+            ReturnCode::NotEnough
+        } else {
+            self.state_mut().intents.push(Intent::ChangeOrderPrice {
+                order: order.to_string(),
+                price,
+            });
+            ReturnCode::Ok
+        }
+    }
+
+    fn spawns(&self) -> Vec<SpawnData> {
+        self.state().spawns.clone()
+    }
+
+    fn spawn_creep(&self, spawn: &str, body: &[Part], name: &str) -> ReturnCode {
+        let room = match self.state().spawns.iter().find(|s| s.name == spawn) {
+            Some(s) if s.spawning.is_some() => return ReturnCode::Busy,
+            Some(s) => s.pos.room_name(),
+            None => return ReturnCode::NotFound,
+        };
+        let cost: u32 = body.iter().map(|p| p.cost()).sum();
+
+        let mut state = self.state_mut();
+        if body.is_empty() {
+            ReturnCode::InvalidArgs
+        } else if state.creeps.contains_key(name) {
+            ReturnCode::NameExists
+        } else if state
+            .intents
+            .iter()
+            .any(|i| matches!(i, Intent::Spawn { spawn: s, .. } if s == spawn))
+        {
+            ReturnCode::Busy
+        } else if state.energy_in_room(room).0 < cost {
+            ReturnCode::NotEnough
+        } else {
+            state.intents.push(Intent::Spawn {
+                spawn: spawn.to_string(),
+                body: body.to_vec(),
+                name: name.to_string(),
+            });
+            ReturnCode::Ok
+        }
+    }
+
+    fn spawn_recycle_creep(&self, spawn: &str, creep: &str) -> ReturnCode {
+        let mut state = self.state_mut();
+        match (
+            state.spawns.iter().find(|s| s.name == spawn),
+            state.creeps.get(creep),
+        ) {
+            (Some(s), Some(c)) if !s.pos.is_near_to(&c.pos) => ReturnCode::NotInRange,
+            (Some(_), Some(_)) => {
+                state.intents.push(Intent::Recycle {
+                    spawn: spawn.to_string(),
+                    creep: creep.to_string(),
+                });
+                ReturnCode::Ok
+            }
+            _ => ReturnCode::NotFound,
+        }
+    }
+
+    fn spawn_renew_creep(&self, spawn: &str, creep: &str) -> ReturnCode {
+        let mut state = self.state_mut();
+        let (s, c) = match (
+            state.spawns.iter().find(|s| s.name == spawn),
+            state.creeps.get(creep),
+        ) {
+            (Some(s), Some(c)) => (s, c),
+            _ => return ReturnCode::NotFound,
+        };
+
+        if s.spawning.is_some() {
+            ReturnCode::Busy
+        } else if !s.pos.is_near_to(&c.pos) {
+            ReturnCode::NotInRange
+        } else if c.ticks_to_live.unwrap_or(0) + renew_ticks(&c.body) > CREEP_LIFE_TIME {
+            ReturnCode::Full
+        } else if state.energy_in_room(s.pos.room_name()).0 < renew_cost(&c.body) {
+            ReturnCode::NotEnough
+        } else {
+            state.intents.push(Intent::Renew {
+                spawn: spawn.to_string(),
+                creep: creep.to_string(),
+            });
+            ReturnCode::Ok
+        }
+    }
+
+    fn raw_memory(&self) -> String {
+        self.state().memory.clone()
+    }
+
+    fn set_raw_memory(&self, raw: &str) {
+        self.state_mut().memory = raw.to_string();
+    }
+}
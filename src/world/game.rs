@@ -0,0 +1,702 @@
+use super::{
+    ControllerData, CreepData, MineralData, OrderData, PriceData, RemainsData, ResourceData,
+    RoomData, RoomTerrain, SiteData, SourceData, SpawnData, Store, StructureData, World,
+};
+use crate::core::constants;
+use screeps::{
+    constants::{TERRAIN_MASK_SWAMP, TERRAIN_MASK_WALL},
+    find,
+    game::market::{self, OrderType},
+    pathfinder::{self, LocalCostMatrix, SearchOptions},
+    prelude::*,
+    Attackable, ConstructionSite, Creep, LineDrawStyle, MarketResourceType, Mineral, MoveToOptions,
+    Part, PolyStyle, Position, RawObjectId, Resource, ResourceType, ReturnCode, RoomName, Ruin,
+    SizedRoomObject, Source, Structure, StructureController, StructureLab, StructureLink,
+    StructureTerminal, StructureTower, StructureType, Terrain, Tombstone,
+};
+use std::collections::HashMap;
+use stdweb::{js, unstable::TryInto};
+
+/// The live backend, forwarding to `screeps-game-api`.
+pub struct GameWorld;
+
+fn resolve<T: HasId + SizedRoomObject>(id: RawObjectId) -> Option<T> {
+    screeps::game::get_object_typed(id.into()).ok().flatten()
+}
+
+fn controller_data(c: &StructureController) -> ControllerData {
+    ControllerData {
+        id: c.untyped_id(),
+        level: c.level(),
+        my: c.my(),
+        pos: c.pos(),
+        progress: c.progress().unwrap_or(0),
+        progress_total: c.progress_total().unwrap_or(0),
+    }
+}
+
+fn creep_data(c: &Creep) -> CreepData {
+    CreepData {
+        body: c.body().into_iter().map(|p| p.part).collect(),
+        fatigue: c.fatigue(),
+        hits: c.hits(),
+        hits_max: c.hits_max(),
+        id: c.untyped_id(),
+        name: c.name(),
+        pos: c.pos(),
+        store: store_data(c),
+        ticks_to_live: c.ticks_to_live().ok(),
+    }
+}
+
+fn site_data(c: &ConstructionSite) -> SiteData {
+    SiteData {
+        id: c.untyped_id(),
+        pos: c.pos(),
+        progress: c.progress(),
+        progress_total: c.progress_total(),
+        structure_type: c.structure_type(),
+    }
+}
+
+fn mineral_data(m: &Mineral) -> MineralData {
+    MineralData {
+        amount: m.mineral_amount(),
+        density: m.density(),
+        id: m.untyped_id(),
+        mineral_type: m.mineral_type(),
+        pos: m.pos(),
+        ticks_to_regeneration: m.ticks_to_regeneration(),
+    }
+}
+
+/// Only orders for resources of this shard
+fn order_data(
+    id: String,
+    order_type: OrderType,
+    resource: MarketResourceType,
+    room: Option<RoomName>,
+    amount: u32,
+    price: f64,
+) -> Option<OrderData> {
+    match resource {
+        MarketResourceType::Resource(resource_type) => Some(OrderData {
+            amount,
+            id,
+            order_type,
+            price,
+            resource_type,
+            room,
+        }),
+        MarketResourceType::IntershardResource(_) => None,
+    }
+}
+
+fn remains_data<T: CanDecay + HasId + HasStore>(r: &T) -> RemainsData {
+    RemainsData {
+        id: r.untyped_id(),
+        pos: r.pos(),
+        store: store_data(r),
+        ticks_to_decay: r.ticks_to_decay(),
+    }
+}
+
+fn resource_data(r: &Resource) -> ResourceData {
+    ResourceData {
+        amount: r.amount(),
+        id: r.untyped_id(),
+        pos: r.pos(),
+        resource_type: r.resource_type(),
+    }
+}
+
+fn source_data(s: &Source) -> SourceData {
+    SourceData {
+        energy: s.energy(),
+        energy_capacity: s.energy_capacity(),
+        id: s.untyped_id(),
+        pos: s.pos(),
+    }
+}
+
+fn store_data(s: &dyn HasStore) -> Store {
+    // Single-resource stores (spawns, extensions, towers) have no general capacity
+    let capacity = match s.store_capacity(None) {
+        0 => s.store_capacity(Some(ResourceType::Energy)),
+        c => c,
+    };
+
+    Store {
+        capacity,
+        capacities: HashMap::new(),
+        resources: s
+            .store_types()
+            .into_iter()
+            .map(|r| (r, s.store_of(r)))
+            .collect(),
+    }
+}
+
+fn structure_data(s: &Structure) -> StructureData {
+    let (hits, hits_max) = s
+        .as_attackable()
+        .map(|a| (a.hits(), a.hits_max()))
+        .unwrap_or((0, 0));
+
+    StructureData {
+        cooldown: s.as_has_cooldown().map(|c| c.cooldown()).unwrap_or(0),
+        hits,
+        hits_max,
+        id: s.untyped_id(),
+        my: s.as_owned().map(|o| o.my()).unwrap_or(false),
+        pos: s.pos(),
+        store: s.as_has_store().map(|h| match s.structure_type() {
+            StructureType::Lab => Store {
+                resources: store_data(h).resources,
+                ..Store::lab()
+            },
+            _ => store_data(h),
+        }),
+        structure_type: s.structure_type(),
+    }
+}
+
+impl World for GameWorld {
+    fn cpu_used(&self) -> f64 {
+        screeps::game::cpu::get_used()
+    }
+
+    fn time(&self) -> u32 {
+        screeps::game::time()
+    }
+
+    fn room(&self, name: RoomName) -> Option<RoomData> {
+        screeps::game::rooms::get(name).map(|r| RoomData {
+            controller: r.controller().as_ref().map(controller_data),
+            energy_available: r.energy_available(),
+            energy_capacity_available: r.energy_capacity_available(),
+        })
+    }
+
+    fn rooms(&self) -> Vec<RoomName> {
+        screeps::game::rooms::keys()
+    }
+
+    fn terrain(&self, room: RoomName) -> RoomTerrain {
+        RoomTerrain::new(
+            screeps::RoomTerrain::constructor(room)
+                .get_raw_buffer()
+                .into_iter()
+                .map(|t| {
+                    if t & TERRAIN_MASK_WALL != 0 {
+                        Terrain::Wall
+                    } else if t & TERRAIN_MASK_SWAMP != 0 {
+                        Terrain::Swamp
+                    } else {
+                        Terrain::Plain
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    fn find_path(
+        &self,
+        from: Position,
+        to: Position,
+        range: u32,
+        costs: &LocalCostMatrix,
+    ) -> Vec<Position> {
+        let costs = costs.clone();
+        let options = SearchOptions::new()
+            .room_callback(move |_| costs.upload())
+            .plain_cost(constants::PATH_PLAIN_COST)
+            .swamp_cost(constants::PATH_SWAMP_COST)
+            .max_rooms(1);
+        pathfinder::search(&from, &to, range, options).load_local_path()
+    }
+
+    fn construction_sites(&self, room: RoomName) -> Vec<SiteData> {
+        screeps::game::rooms::get(room)
+            .map(|r| {
+                r.find(find::CONSTRUCTION_SITES)
+                    .iter()
+                    .map(site_data)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn create_construction_site(&self, pos: Position, structure_type: StructureType) -> ReturnCode {
+        pos.create_construction_site(structure_type)
+    }
+
+    fn dropped_resources(&self, room: RoomName) -> Vec<ResourceData> {
+        screeps::game::rooms::get(room)
+            .map(|r| {
+                r.find(find::DROPPED_RESOURCES)
+                    .iter()
+                    .map(resource_data)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn hostile_creeps(&self, room: RoomName) -> Vec<CreepData> {
+        screeps::game::rooms::get(room)
+            .map(|r| {
+                r.find(find::HOSTILE_CREEPS)
+                    .iter()
+                    .map(creep_data)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn minerals(&self, room: RoomName) -> Vec<MineralData> {
+        screeps::game::rooms::get(room)
+            .map(|r| r.find(find::MINERALS).iter().map(mineral_data).collect())
+            .unwrap_or_default()
+    }
+
+    fn my_creeps(&self, room: RoomName) -> Vec<CreepData> {
+        screeps::game::rooms::get(room)
+            .map(|r| r.find(find::MY_CREEPS).iter().map(creep_data).collect())
+            .unwrap_or_default()
+    }
+
+    fn ruins(&self, room: RoomName) -> Vec<RemainsData> {
+        screeps::game::rooms::get(room)
+            .map(|r| r.find(find::RUINS).iter().map(remains_data).collect())
+            .unwrap_or_default()
+    }
+
+    fn sources(&self, room: RoomName) -> Vec<SourceData> {
+        screeps::game::rooms::get(room)
+            .map(|r| r.find(find::SOURCES).iter().map(source_data).collect())
+            .unwrap_or_default()
+    }
+
+    fn structures(&self, room: RoomName) -> Vec<StructureData> {
+        screeps::game::rooms::get(room)
+            .map(|r| {
+                r.find(find::STRUCTURES)
+                    .iter()
+                    .map(structure_data)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn tombstones(&self, room: RoomName) -> Vec<RemainsData> {
+        screeps::game::rooms::get(room)
+            .map(|r| r.find(find::TOMBSTONES).iter().map(remains_data).collect())
+            .unwrap_or_default()
+    }
+
+    fn get_construction_site(&self, id: RawObjectId) -> Option<SiteData> {
+        resolve::<ConstructionSite>(id).as_ref().map(site_data)
+    }
+
+    fn get_controller(&self, id: RawObjectId) -> Option<ControllerData> {
+        resolve::<StructureController>(id)
+            .as_ref()
+            .map(controller_data)
+    }
+
+    fn get_creep(&self, id: RawObjectId) -> Option<CreepData> {
+        resolve::<Creep>(id).as_ref().map(creep_data)
+    }
+
+    fn get_mineral(&self, id: RawObjectId) -> Option<MineralData> {
+        resolve::<Mineral>(id).as_ref().map(mineral_data)
+    }
+
+    fn get_resource(&self, id: RawObjectId) -> Option<ResourceData> {
+        resolve::<Resource>(id).as_ref().map(resource_data)
+    }
+
+    fn get_ruin(&self, id: RawObjectId) -> Option<RemainsData> {
+        resolve::<Ruin>(id).as_ref().map(remains_data)
+    }
+
+    fn get_source(&self, id: RawObjectId) -> Option<SourceData> {
+        resolve::<Source>(id).as_ref().map(source_data)
+    }
+
+    fn get_structure(&self, id: RawObjectId) -> Option<StructureData> {
+        resolve::<Structure>(id).as_ref().map(structure_data)
+    }
+
+    fn get_tombstone(&self, id: RawObjectId) -> Option<RemainsData> {
+        resolve::<Tombstone>(id).as_ref().map(remains_data)
+    }
+
+    fn creeps(&self) -> Vec<CreepData> {
+        screeps::game::creeps::values()
+            .iter()
+            .map(creep_data)
+            .collect()
+    }
+
+    fn creep_attack(&self, creep: &str, target: RawObjectId) -> ReturnCode {
+        match (screeps::game::creeps::get(creep), resolve::<Creep>(target)) {
+            (Some(c), Some(t)) => c.attack(&t),
+            _ => ReturnCode::NotFound,
+        }
+    }
+
+    fn creep_build(&self, creep: &str, site: RawObjectId) -> ReturnCode {
+        match (
+            screeps::game::creeps::get(creep),
+            resolve::<ConstructionSite>(site),
+        ) {
+            (Some(c), Some(s)) => c.build(&s),
+            _ => ReturnCode::NotFound,
+        }
+    }
+
+    fn creep_harvest(&self, creep: &str, source: RawObjectId) -> ReturnCode {
+        let c = match screeps::game::creeps::get(creep) {
+            Some(c) => c,
+            None => return ReturnCode::NotFound,
+        };
+
+        if let Some(s) = resolve::<Source>(source) {
+            c.harvest(&s)
+        } else if let Some(m) = resolve::<Mineral>(source) {
+            c.harvest(&m)
+        } else {
+            ReturnCode::NotFound
+        }
+    }
+
+    fn creep_heal(&self, creep: &str, target: RawObjectId) -> ReturnCode {
+        match (screeps::game::creeps::get(creep), resolve::<Creep>(target)) {
+            (Some(c), Some(t)) => c.heal(&t),
+            _ => ReturnCode::NotFound,
+        }
+    }
+
+    fn creep_move_to(&self, creep: &str, target: Position) -> ReturnCode {
+        match screeps::game::creeps::get(creep) {
+            Some(c) => c.move_to_with_options(
+                &target,
+                MoveToOptions::new()
+                    .visualize_path_style(PolyStyle::default().line_style(LineDrawStyle::Dashed)),
+            ),
+            None => ReturnCode::NotFound,
+        }
+    }
+
+    fn creep_pickup(&self, creep: &str, resource: RawObjectId) -> ReturnCode {
+        match (
+            screeps::game::creeps::get(creep),
+            resolve::<Resource>(resource),
+        ) {
+            (Some(c), Some(r)) => c.pickup(&r),
+            _ => ReturnCode::NotFound,
+        }
+    }
+
+    fn creep_repair(&self, creep: &str, target: RawObjectId) -> ReturnCode {
+        match (
+            screeps::game::creeps::get(creep),
+            resolve::<Structure>(target),
+        ) {
+            (Some(c), Some(s)) => c.repair(&s),
+            _ => ReturnCode::NotFound,
+        }
+    }
+
+    fn creep_say(&self, creep: &str, message: &str) {
+        if let Some(c) = screeps::game::creeps::get(creep) {
+            c.say(message, false);
+        }
+    }
+
+    fn creep_transfer_all(
+        &self,
+        creep: &str,
+        target: RawObjectId,
+        resource: ResourceType,
+    ) -> ReturnCode {
+        match (
+            screeps::game::creeps::get(creep),
+            resolve::<Structure>(target),
+        ) {
+            (Some(c), Some(s)) => match s.as_transferable() {
+                Some(t) => c.transfer_all(t, resource),
+                None => ReturnCode::InvalidTarget,
+            },
+            _ => ReturnCode::NotFound,
+        }
+    }
+
+    fn creep_upgrade_controller(&self, creep: &str, controller: RawObjectId) -> ReturnCode {
+        match (
+            screeps::game::creeps::get(creep),
+            resolve::<StructureController>(controller),
+        ) {
+            (Some(c), Some(s)) => c.upgrade_controller(&s),
+            _ => ReturnCode::NotFound,
+        }
+    }
+
+    fn creep_withdraw_all(
+        &self,
+        creep: &str,
+        target: RawObjectId,
+        resource: ResourceType,
+    ) -> ReturnCode {
+        let c = match screeps::game::creeps::get(creep) {
+            Some(c) => c,
+            None => return ReturnCode::NotFound,
+        };
+
+        if let Some(s) = resolve::<Structure>(target) {
+            match s.as_withdrawable() {
+                Some(w) => c.withdraw_all(w, resource),
+                None => ReturnCode::InvalidTarget,
+            }
+        } else if let Some(t) = resolve::<Tombstone>(target) {
+            c.withdraw_all(&t, resource)
+        } else if let Some(r) = resolve::<Ruin>(target) {
+            c.withdraw_all(&r, resource)
+        } else {
+            ReturnCode::NotFound
+        }
+    }
+
+    fn lab_boost_creep(&self, lab: RawObjectId, creep: &str) -> ReturnCode {
+        match (
+            resolve::<StructureLab>(lab),
+            screeps::game::creeps::get(creep),
+        ) {
+            (Some(l), Some(c)) => l.boost_creep(&c, None),
+            _ => ReturnCode::NotFound,
+        }
+    }
+
+    fn lab_reverse_reaction(
+        &self,
+        lab: RawObjectId,
+        lab1: RawObjectId,
+        lab2: RawObjectId,
+    ) -> ReturnCode {
+        match (
+            resolve::<StructureLab>(lab),
+            resolve::<StructureLab>(lab1),
+            resolve::<StructureLab>(lab2),
+        ) {
+            (Some(l), Some(l1), Some(l2)) => l.reverse_reaction(&l1, &l2),
+            _ => ReturnCode::NotFound,
+        }
+    }
+
+    fn lab_run_reaction(
+        &self,
+        lab: RawObjectId,
+        lab1: RawObjectId,
+        lab2: RawObjectId,
+    ) -> ReturnCode {
+        match (
+            resolve::<StructureLab>(lab),
+            resolve::<StructureLab>(lab1),
+            resolve::<StructureLab>(lab2),
+        ) {
+            (Some(l), Some(l1), Some(l2)) => l.run_reaction(&l1, &l2),
+            _ => ReturnCode::NotFound,
+        }
+    }
+
+    fn link_transfer_energy(
+        &self,
+        link: RawObjectId,
+        target: RawObjectId,
+        amount: u32,
+    ) -> ReturnCode {
+        match (
+            resolve::<StructureLink>(link),
+            resolve::<StructureLink>(target),
+        ) {
+            (Some(l), Some(t)) => l.transfer_energy(&t, Some(amount)),
+            _ => ReturnCode::NotFound,
+        }
+    }
+
+    fn terminal_send(
+        &self,
+        terminal: RawObjectId,
+        resource: ResourceType,
+        amount: u32,
+        destination: RoomName,
+    ) -> ReturnCode {
+        match resolve::<StructureTerminal>(terminal) {
+            Some(t) => t.send(resource, amount, destination, None),
+            None => ReturnCode::NotFound,
+        }
+    }
+
+    fn tower_attack(&self, tower: RawObjectId, target: RawObjectId) -> ReturnCode {
+        match (resolve::<StructureTower>(tower), resolve::<Creep>(target)) {
+            (Some(t), Some(c)) => t.attack(&c),
+            _ => ReturnCode::NotFound,
+        }
+    }
+
+    fn tower_heal(&self, tower: RawObjectId, target: RawObjectId) -> ReturnCode {
+        match (resolve::<StructureTower>(tower), resolve::<Creep>(target)) {
+            (Some(t), Some(c)) => t.heal(&c),
+            _ => ReturnCode::NotFound,
+        }
+    }
+
+    fn tower_repair(&self, tower: RawObjectId, target: RawObjectId) -> ReturnCode {
+        match (
+            resolve::<StructureTower>(tower),
+            resolve::<Structure>(target),
+        ) {
+            (Some(t), Some(s)) => t.repair(&s),
+            _ => ReturnCode::NotFound,
+        }
+    }
+
+    fn credits(&self) -> f64 {
+        market::credits()
+    }
+
+    fn market_orders(&self, resource: ResourceType) -> Vec<OrderData> {
+        market::get_all_orders(Some(MarketResourceType::Resource(resource)))
+            .into_iter()
+            .filter_map(|o| {
+                order_data(
+                    o.id,
+                    o.order_type,
+                    o.resource_type,
+                    o.room_name,
+                    o.remaining_amount,
+                    o.price,
+                )
+            })
+            .collect()
+    }
+
+    fn my_orders(&self) -> Vec<OrderData> {
+        market::orders()
+            .into_values()
+            .filter_map(|o| {
+                order_data(
+                    o.id,
+                    o.order_type,
+                    o.resource_type,
+                    o.room_name,
+                    o.remaining_amount,
+                    o.price,
+                )
+            })
+            .collect()
+    }
+
+    fn price_history(&self, resource: ResourceType) -> Vec<PriceData> {
+        market::get_history(Some(MarketResourceType::Resource(resource)))
+            .into_iter()
+            .map(|h| PriceData {
+                avg_price: h.avg_price,
+                volume: h.volume,
+            })
+            .collect()
+    }
+
+    fn market_deal(&self, order: &str, amount: u32, room: RoomName) -> ReturnCode {
+        market::deal(order, amount, Some(room))
+    }
+
+    fn market_create_order(
+        &self,
+        order_type: OrderType,
+        resource: ResourceType,
+        price: f64,
+        amount: u32,
+        room: RoomName,
+    ) -> ReturnCode {
+        let resource = MarketResourceType::Resource(resource);
+        market::create_order(order_type, resource, price, amount, Some(room))
+    }
+
+    fn market_cancel_order(&self, order: &str) -> ReturnCode {
+        market::cancel_order(order)
+    }
+
+    fn market_extend_order(&self, order: &str, amount: u32) -> ReturnCode {
+        market::extend_order(order, amount)
+    }
+
+    fn market_change_price(&self, order: &str, price: f64) -> ReturnCode {
+        market::change_order_price(order, price)
+    }
+
+    fn spawns(&self) -> Vec<SpawnData> {
+        screeps::game::spawns::values()
+            .into_iter()
+            .map(|s| SpawnData {
+                id: s.untyped_id(),
+                name: s.name(),
+                pos: s.pos(),
+                spawning: s.spawning().map(|s| s.name()),
+            })
+            .collect()
+    }
+
+    fn spawn_creep(&self, spawn: &str, body: &[Part], name: &str) -> ReturnCode {
+        match screeps::game::spawns::get(spawn) {
+            Some(s) => s.spawn_creep(body, name),
+            None => ReturnCode::NotFound,
+        }
+    }
+
+    fn spawn_recycle_creep(&self, spawn: &str, creep: &str) -> ReturnCode {
+        match (
+            screeps::game::spawns::get(spawn),
+            screeps::game::creeps::get(creep),
+        ) {
+            (Some(s), Some(c)) => s.recycle_creep(&c),
+            _ => ReturnCode::NotFound,
+        }
+    }
+
+    fn spawn_renew_creep(&self, spawn: &str, creep: &str) -> ReturnCode {
+        match (
+            screeps::game::spawns::get(spawn),
+            screeps::game::creeps::get(creep),
+        ) {
+            (Some(s), Some(c)) => s.renew_creep(&c),
+            _ => ReturnCode::NotFound,
+        }
+    }
+
+    fn raw_memory(&self) -> String {
+        (js! {
+            return JSON.stringify(Memory);
+        })
+        .try_into()
+        .expect("expected JSON.stringify to return a string")
+    }
+
+    fn set_raw_memory(&self, raw: &str) {
+        js! { @(no_return)
+            const memory = JSON.parse(@{raw});
+            // moveTo caches paths in `_move` during the tick, after the memory was read
+            for (const name of Object.keys(memory.creeps || {})) {
+                const live = Memory.creeps && Memory.creeps[name];
+                if (live && live._move) {
+                    memory.creeps[name]._move = live._move;
+                }
+            }
+            for (const key of Object.keys(Memory)) {
+                delete Memory[key];
+            }
+            Object.assign(Memory, memory);
+        }
+    }
+}
@@ -0,0 +1,11 @@
+pub mod api;
+pub mod data;
+#[cfg(test)]
+pub mod fake;
+pub mod game;
+
+pub use api::*;
+pub use data::*;
+#[cfg(test)]
+pub use fake::*;
+pub use game::*;